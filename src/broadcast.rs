@@ -1,8 +1,12 @@
+#[cfg(feature = "prometheus")]
+use crate::builder::MeteredChannelBuilder;
+use crate::envelope::Envelope;
 use crate::error::SendError;
+#[cfg(feature = "prometheus")]
 use crate::metrics::ChannelMetrics;
-use std::sync::Arc;
+use crate::observer::{ChannelKind, ChannelObserver, HandleKind, Idle};
+use crate::trace::{self, debug, error, Op};
 use tokio::sync::broadcast;
-use tracing::{debug, error, instrument};
 
 /// A broadcast channel sender that integrates with Prometheus metrics.
 ///
@@ -31,68 +35,110 @@ use tracing::{debug, error, instrument};
 ///     assert_eq!(rx2.recv().await.unwrap(), 42);
 /// }
 /// ```
-#[derive(Debug, Clone)]
-pub struct Sender<T: Clone> {
-    inner: broadcast::Sender<T>,
-    gauge: prometheus::IntGauge,
-    total_messages: Option<prometheus::IntCounter>,
+#[derive(Debug)]
+pub struct Sender<
+    T: Clone,
+    #[cfg(feature = "prometheus")] O: ChannelObserver = ChannelMetrics,
+    #[cfg(not(feature = "prometheus"))] O: ChannelObserver,
+> {
+    inner: broadcast::Sender<Envelope<T>>,
+    observer: O,
+}
+
+impl<T: Clone, O: ChannelObserver + Clone> Clone for Sender<T, O> {
+    fn clone(&self) -> Self {
+        self.observer.on_attach(HandleKind::Sender);
+        Self {
+            inner: self.inner.clone(),
+            observer: self.observer.clone(),
+        }
+    }
 }
 
 /// A receiver for the broadcast channel
 #[derive(Debug)]
-pub struct Receiver<T: Clone> {
-    inner: broadcast::Receiver<T>,
-    gauge: Arc<prometheus::IntGauge>,
-    total_messages: Option<prometheus::IntCounter>,
+pub struct Receiver<
+    T: Clone,
+    #[cfg(feature = "prometheus")] O: ChannelObserver = ChannelMetrics,
+    #[cfg(not(feature = "prometheus"))] O: ChannelObserver,
+> {
+    inner: broadcast::Receiver<Envelope<T>>,
+    observer: O,
 }
 
 /// Creates a new broadcast channel with given capacity and metrics
+#[cfg(feature = "prometheus")]
 pub fn channel<T: Clone>(capacity: usize, metrics: ChannelMetrics) -> (Sender<T>, Receiver<T>) {
+    channel_with_observer(capacity, metrics)
+}
+
+/// Creates a new broadcast channel with given capacity, reporting to `observer`
+pub fn channel_with_observer<T: Clone, O: ChannelObserver + Clone>(
+    capacity: usize,
+    observer: O,
+) -> (Sender<T, O>, Receiver<T, O>) {
     let (tx, rx) = broadcast::channel(capacity);
-    let gauge = metrics.queue_size;
-    let total_messages = metrics.total_messages;
+    observer.on_open(ChannelKind::Broadcast);
+    observer.on_capacity(capacity);
 
     (
         Sender {
             inner: tx,
-            gauge: gauge.clone(),
-            total_messages: total_messages.clone(),
+            observer: observer.clone(),
         },
         Receiver {
             inner: rx,
-            gauge: Arc::new(gauge),
-            total_messages,
+            observer,
         },
     )
 }
 
-impl<T: Clone> Sender<T> {
+/// Creates a new broadcast channel with given capacity, registering metrics
+/// prefixed with `name` in the [default registry](crate::default_registry)
+///
+/// Channels created with the same name share their metrics.
+#[cfg(feature = "prometheus")]
+pub fn channel_named<T: Clone>(
+    name: &str,
+    capacity: usize,
+) -> Result<(Sender<T>, Receiver<T>), prometheus::Error> {
+    MeteredChannelBuilder::new()
+        .name(name)
+        .capacity(capacity)
+        .with_total()
+        .shared()
+        .build_broadcast()
+}
+
+impl<T: Clone, O: ChannelObserver + Clone> Sender<T, O> {
     /// Send a value to all receivers
-    #[instrument(skip(self, value), level = "debug")]
     pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let (context, span) = trace::send_span(&self.observer);
+        let envelope = Envelope::with_context(value, &self.observer, context);
+        span.in_scope(|| self.send_inner(envelope))
+    }
+
+    fn send_inner(&self, envelope: Envelope<T>) -> Result<(), SendError<T>> {
         debug!("attempting to broadcast value");
-        match self.inner.send(value) {
+        match self.inner.send(envelope) {
             Ok(_) => {
-                self.gauge.inc();
-                if let Some(ref counter) = self.total_messages {
-                    counter.inc();
-                }
+                self.observer.on_send();
                 debug!("value broadcasted successfully");
                 Ok(())
             }
             Err(e) => {
                 error!("failed to broadcast value");
-                Err(SendError::Closed(e.0))
+                Err(SendError::Closed(e.0.value))
             }
         }
     }
 
     /// Create a new receiver for this broadcast channel
-    pub fn subscribe(&self) -> Receiver<T> {
+    pub fn subscribe(&self) -> Receiver<T, O> {
+        self.observer.on_attach(HandleKind::Receiver);
         Receiver {
             inner: self.inner.subscribe(),
-            gauge: Arc::clone(&Arc::new(self.gauge.clone())),
-            total_messages: self.total_messages.clone(),
+            observer: self.observer.clone(),
         }
     }
 
@@ -100,36 +146,77 @@ impl<T: Clone> Sender<T> {
     pub fn receiver_count(&self) -> usize {
         self.inner.receiver_count()
     }
+
+    /// Get the observer receiving the channel's events
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
 }
 
-impl<T: Clone> Receiver<T> {
+impl<T: Clone, O: ChannelObserver> Receiver<T, O> {
     /// Receive the next value
     pub async fn recv(&mut self) -> Result<T, broadcast::error::RecvError> {
-        match self.inner.recv().await {
-            Ok(msg) => {
-                self.gauge.dec();
-                if let Some(ref counter) = self.total_messages {
-                    counter.inc();
+        let span = trace::span(&self.observer, Op::Recv);
+        let msg = span
+            .instrument(async {
+                let msg = match self.inner.try_recv() {
+                    Ok(envelope) => Ok(envelope),
+                    Err(broadcast::error::TryRecvError::Lagged(skipped)) => {
+                        Err(broadcast::error::RecvError::Lagged(skipped))
+                    }
+                    Err(broadcast::error::TryRecvError::Closed) => {
+                        Err(broadcast::error::RecvError::Closed)
+                    }
+                    Err(broadcast::error::TryRecvError::Empty) => {
+                        let _idle = Idle::new(&self.observer);
+                        self.inner.recv().await
+                    }
+                };
+                if let Err(broadcast::error::RecvError::Lagged(skipped)) = msg {
+                    self.observer.on_lag(skipped);
                 }
-                Ok(msg)
-            }
-            Err(e) => Err(e),
-        }
+                msg
+            })
+            .await?;
+        let value = msg.open(&self.observer);
+        self.observer.on_deliver();
+        Ok(value)
     }
 
     /// Try to receive a value without waiting
     pub fn try_recv(&mut self) -> Result<T, broadcast::error::TryRecvError> {
         match self.inner.try_recv() {
-            Ok(msg) => {
-                self.gauge.dec();
-                Ok(msg)
+            Ok(envelope) => Ok(envelope.open(&self.observer)),
+            Err(broadcast::error::TryRecvError::Lagged(skipped)) => {
+                self.observer.on_lag(skipped);
+                Err(broadcast::error::TryRecvError::Lagged(skipped))
             }
             Err(e) => Err(e),
         }
     }
 
+    /// Get the observer receiving the channel's events
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+}
+
+#[cfg(feature = "prometheus")]
+impl<T: Clone> Receiver<T, ChannelMetrics> {
     /// Get the total messages counter if enabled
     pub fn total_messages(&self) -> Option<&prometheus::IntCounter> {
-        self.total_messages.as_ref()
+        self.observer.total_messages.as_ref()
+    }
+}
+
+impl<T: Clone, O: ChannelObserver> Drop for Sender<T, O> {
+    fn drop(&mut self) {
+        self.observer.on_detach(HandleKind::Sender);
+    }
+}
+
+impl<T: Clone, O: ChannelObserver> Drop for Receiver<T, O> {
+    fn drop(&mut self) {
+        self.observer.on_detach(HandleKind::Receiver);
     }
 }
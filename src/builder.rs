@@ -0,0 +1,384 @@
+#[cfg(feature = "broadcast")]
+use crate::broadcast;
+#[cfg(feature = "mpsc")]
+use crate::channel;
+use crate::metrics::{default_registry, ChannelMetrics, ChannelMetricsOpts};
+#[cfg(feature = "overflow")]
+use crate::overflow::{self, OverflowPolicy};
+#[cfg(feature = "resizable")]
+use crate::resizable;
+#[cfg(feature = "tracing")]
+use crate::trace::ChannelTrace;
+#[cfg(feature = "watch")]
+use crate::watch;
+#[cfg(feature = "weighted")]
+use crate::weighted::{self, Limit, Weigh};
+use prometheus::Registry;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Builder for metered channels.
+///
+/// Collects the naming, registration and metric options shared by every
+/// channel type, then registers the matching [`ChannelMetrics`] and creates
+/// the channel with one of the `build_*` methods. Metrics are registered with
+/// the crate's [`default_registry`](crate::default_registry) unless another
+/// registry is given.
+///
+/// # Examples
+///
+/// ```rust
+/// use tokio_prometheus_metered_channel::MeteredChannelBuilder;
+/// use prometheus::Registry;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let registry = Registry::new();
+///     let (tx, mut rx) = MeteredChannelBuilder::new()
+///         .name("ingest")
+///         .help("ingest pipeline")
+///         .registry(&registry)
+///         .capacity(100)
+///         .with_total()
+///         .with_latency_histogram(vec![0.001, 0.01, 0.1, 1.0])
+///         .const_label("pipeline", "ingest")
+///         .build_mpsc()
+///         .unwrap();
+///
+///     tx.send(42).await.unwrap();
+///     assert_eq!(rx.recv().await, Some(42));
+///     assert_eq!(rx.total_messages().unwrap().get(), 1);
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MeteredChannelBuilder {
+    opts: ChannelMetricsOpts,
+    registry: Option<Registry>,
+    capacity: Option<usize>,
+    #[cfg(feature = "overflow")]
+    overflow_policy: OverflowPolicy,
+    shared: bool,
+    #[cfg(feature = "weighted")]
+    byte_budget: Option<usize>,
+    #[cfg(feature = "tracing")]
+    trace: ChannelTrace,
+}
+
+impl MeteredChannelBuilder {
+    /// Create a builder with no options set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start from the given metric options instead of the defaults
+    pub fn opts(mut self, opts: ChannelMetricsOpts) -> Self {
+        self.opts = opts;
+        self
+    }
+
+    /// Set the name prefix of the channel's metrics.
+    ///
+    /// Required unless a namespace or subsystem is set.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.opts.name = name.into();
+        self
+    }
+
+    /// Set the namespace prepended to the channel's metric names
+    pub fn namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.opts.namespace = namespace.into();
+        self
+    }
+
+    /// Set the subsystem inserted between the namespace and the name
+    pub fn subsystem(mut self, subsystem: impl Into<String>) -> Self {
+        self.opts.subsystem = subsystem.into();
+        self
+    }
+
+    /// Set the description spliced into the metrics' help text, defaulting to the name prefix
+    pub fn help(mut self, help: impl Into<String>) -> Self {
+        self.opts.help = help.into();
+        self
+    }
+
+    /// Replace the help text of the metric with the given suffix
+    pub fn metric_help(mut self, suffix: impl Into<String>, help: impl Into<String>) -> Self {
+        self.opts = self.opts.metric_help(suffix, help);
+        self
+    }
+
+    /// Register metrics with `registry` instead of the default registry
+    pub fn registry(mut self, registry: &Registry) -> Self {
+        self.registry = Some(registry.clone());
+        self
+    }
+
+    /// Set the channel capacity. Required for every channel type except watch.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Count the total number of messages going through the channel
+    pub fn with_total(mut self) -> Self {
+        self.opts.total_messages = true;
+        self
+    }
+
+    /// Record the time items spend queued in a histogram with the given buckets
+    pub fn with_latency_histogram(mut self, buckets: Vec<f64>) -> Self {
+        self.opts.latency_buckets = Some(buckets);
+        self
+    }
+
+    /// Record the time consumers spend on items received through a
+    /// [`MessageGuard`](crate::MessageGuard) in a histogram with the given buckets,
+    /// along with the number of items in flight
+    pub fn with_processing_histogram(mut self, buckets: Vec<f64>) -> Self {
+        self.opts.processing_buckets = Some(buckets);
+        self
+    }
+
+    /// Count the time receivers spend waiting on the empty channel and the
+    /// time between their receives, to compute consumer utilization
+    pub fn with_utilization(mut self) -> Self {
+        self.opts.utilization = true;
+        self
+    }
+
+    /// Count the items processed and abandoned when the channel shuts down, in
+    /// `{name}_drained_total` and `{name}_dropped_total`
+    pub fn with_drain(mut self) -> Self {
+        self.opts.drain = true;
+        self
+    }
+
+    /// Count the undelivered items sent to a [dead-letter channel](crate::DeadLetterSender),
+    /// in `{name}_dead_letters_total` and `{name}_dead_letters_lost_total`
+    pub fn with_dead_letters(mut self) -> Self {
+        self.opts.dead_letters = true;
+        self
+    }
+
+    /// Track send and receive rates in-process, read with [`ChannelMetrics::throughput`]
+    pub fn with_rates(mut self) -> Self {
+        self.opts.rates = true;
+        self
+    }
+
+    /// List the channel in [`live_channels`](crate::live_channels) with its
+    /// [`ChannelStats`](crate::ChannelStats)
+    pub fn with_stats(mut self) -> Self {
+        self.opts.stats = true;
+        self
+    }
+
+    /// Have the [`Watchdog`](crate::Watchdog) flag the channel in a `{name}_stalled`
+    /// gauge once it holds values without receive progress for `threshold`.
+    ///
+    /// Implies [`with_stats`](Self::with_stats).
+    pub fn with_stall_threshold(mut self, threshold: Duration) -> Self {
+        self.opts.stall_threshold = Some(threshold);
+        self
+    }
+
+    /// Set constant labels attached to every metric of the channel
+    pub fn const_labels(mut self, labels: HashMap<String, String>) -> Self {
+        self.opts.const_labels = labels;
+        self
+    }
+
+    /// Add a single constant label attached to every metric of the channel
+    pub fn const_label(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.opts.const_labels.insert(name.into(), value.into());
+        self
+    }
+
+    /// Set the policy applied by [`build_overflow`](Self::build_overflow) when the
+    /// channel is full. Lossy policies also register an eviction counter.
+    ///
+    /// Other channel types cannot apply a policy, so building them fails unless
+    /// it is [`OverflowPolicy::Block`].
+    #[cfg(feature = "overflow")]
+    pub fn overflow_policy(mut self, policy: OverflowPolicy) -> Self {
+        self.overflow_policy = policy;
+        self
+    }
+
+    /// Bound the channels built by [`build_weighted`](Self::build_weighted) by
+    /// the total weight of their values instead of their number
+    #[cfg(feature = "weighted")]
+    pub fn byte_budget(mut self, bytes: usize) -> Self {
+        self.byte_budget = Some(bytes);
+        self
+    }
+
+    /// Share metrics with other channels built with the same names and const labels
+    /// instead of failing, unregistering them once the last channel is dropped.
+    ///
+    /// See [`ChannelMetrics::get_or_register`].
+    pub fn shared(mut self) -> Self {
+        self.shared = true;
+        self
+    }
+
+    /// Set the level of the channel's spans and whether they propagate the
+    /// sender's trace context to receivers
+    #[cfg(feature = "tracing")]
+    pub fn trace(mut self, trace: ChannelTrace) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    /// Register the configured metrics without creating a channel
+    pub fn build_metrics(&self) -> Result<ChannelMetrics, prometheus::Error> {
+        self.register(ChannelMetricsOpts {
+            capacity: self.capacity.is_some(),
+            evicted_total: self.is_lossy(),
+            ..self.opts.clone()
+        })
+    }
+
+    /// Build an mpsc channel
+    #[cfg(feature = "mpsc")]
+    pub fn build_mpsc<T>(
+        &self,
+    ) -> Result<(channel::Sender<T>, channel::Receiver<T>), prometheus::Error> {
+        self.require_blocking()?;
+        let capacity = self.require_capacity()?;
+        Ok(channel::channel(capacity, self.bounded_metrics()?))
+    }
+
+    /// Build a broadcast channel
+    #[cfg(feature = "broadcast")]
+    pub fn build_broadcast<T: Clone>(
+        &self,
+    ) -> Result<(broadcast::Sender<T>, broadcast::Receiver<T>), prometheus::Error> {
+        self.require_blocking()?;
+        let capacity = self.require_capacity()?;
+        Ok(broadcast::channel(capacity, self.bounded_metrics()?))
+    }
+
+    /// Build a watch channel holding `initial`
+    #[cfg(feature = "watch")]
+    pub fn build_watch<T>(
+        &self,
+        initial: T,
+    ) -> Result<(watch::Sender<T>, watch::Receiver<T>), prometheus::Error> {
+        self.require_blocking()?;
+        let metrics = self.register(self.opts.clone())?;
+        Ok(watch::channel(initial, metrics))
+    }
+
+    /// Build a bounded channel applying the configured [`OverflowPolicy`]
+    #[cfg(feature = "overflow")]
+    pub fn build_overflow<T>(
+        &self,
+    ) -> Result<(overflow::Sender<T>, overflow::Receiver<T>), prometheus::Error> {
+        let capacity = self.require_capacity()?;
+        let metrics = self.register(ChannelMetricsOpts {
+            capacity: true,
+            evicted_total: self.overflow_policy.is_lossy(),
+            ..self.opts.clone()
+        })?;
+        Ok(overflow::channel(capacity, self.overflow_policy, metrics))
+    }
+
+    /// Build a channel whose capacity can be changed at runtime
+    #[cfg(feature = "resizable")]
+    pub fn build_resizable<T>(
+        &self,
+    ) -> Result<(resizable::Sender<T>, resizable::Receiver<T>), prometheus::Error> {
+        self.require_blocking()?;
+        let capacity = self.require_capacity()?;
+        Ok(resizable::channel(capacity, self.bounded_metrics()?))
+    }
+
+    /// Build a channel weighing its values with `weigher`, bounded by the
+    /// configured [byte budget](Self::byte_budget) or else by the capacity.
+    /// Also registers the `queue_bytes` gauge and `bytes_total` counter.
+    #[cfg(feature = "weighted")]
+    pub fn build_weighted<T>(
+        &self,
+        weigher: impl Weigh<T> + 'static,
+    ) -> Result<(weighted::Sender<T>, weighted::Receiver<T>), prometheus::Error> {
+        self.require_blocking()?;
+        let limit = match self.byte_budget {
+            Some(bytes) => Limit::Bytes(bytes),
+            None => Limit::Items(self.require_capacity()?),
+        };
+        let metrics = self.register(ChannelMetricsOpts {
+            capacity: true,
+            bytes: true,
+            ..self.opts.clone()
+        })?;
+        Ok(weighted::channel(limit, weigher, metrics))
+    }
+
+    #[cfg(feature = "overflow")]
+    fn is_lossy(&self) -> bool {
+        self.overflow_policy.is_lossy()
+    }
+
+    #[cfg(not(feature = "overflow"))]
+    fn is_lossy(&self) -> bool {
+        false
+    }
+
+    #[cfg(any(feature = "mpsc", feature = "broadcast", feature = "resizable"))]
+    fn bounded_metrics(&self) -> Result<ChannelMetrics, prometheus::Error> {
+        self.register(ChannelMetricsOpts {
+            capacity: true,
+            ..self.opts.clone()
+        })
+    }
+
+    #[cfg(any(
+        feature = "mpsc",
+        feature = "broadcast",
+        feature = "overflow",
+        feature = "resizable",
+        feature = "weighted"
+    ))]
+    fn require_capacity(&self) -> Result<usize, prometheus::Error> {
+        self.capacity
+            .ok_or_else(|| prometheus::Error::Msg("channel capacity is required".to_string()))
+    }
+
+    /// Fail if an overflow policy was set for a channel type that cannot apply it
+    #[cfg(any(
+        feature = "mpsc",
+        feature = "broadcast",
+        feature = "watch",
+        feature = "resizable",
+        feature = "weighted"
+    ))]
+    fn require_blocking(&self) -> Result<(), prometheus::Error> {
+        #[cfg(feature = "overflow")]
+        if self.overflow_policy != OverflowPolicy::Block {
+            return Err(prometheus::Error::Msg(
+                "overflow policy is only applied by overflow channels".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn register(&self, opts: ChannelMetricsOpts) -> Result<ChannelMetrics, prometheus::Error> {
+        if opts.prefix().is_empty() {
+            return Err(prometheus::Error::Msg(
+                "channel name, namespace or subsystem is required".to_string(),
+            ));
+        }
+
+        let registry = self.registry.clone().unwrap_or_else(default_registry);
+        let metrics = if self.shared {
+            ChannelMetrics::get_or_register(&opts, &registry)?
+        } else {
+            ChannelMetrics::with_opts(&opts, &registry)?
+        };
+        #[cfg(feature = "tracing")]
+        let metrics = metrics.with_trace(self.trace);
+        Ok(metrics)
+    }
+}
@@ -1,214 +1,677 @@
+#[cfg(feature = "prometheus")]
+use crate::builder::MeteredChannelBuilder;
+use crate::envelope::Envelope;
 use crate::error::SendError;
+use crate::guard::MessageGuard;
+#[cfg(feature = "prometheus")]
 use crate::metrics::ChannelMetrics;
+use crate::observer::{ChannelKind, ChannelObserver, HandleKind, Idle, Waiting};
+use crate::trace::{self, debug, error, Op, SpanContext};
 use async_trait::async_trait;
 use futures::Sink;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 use tokio::sync::mpsc;
-use tracing::{debug, error, instrument, span, Instrument, Level};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::time::Instant;
+#[cfg(feature = "tracing")]
+use tracing::{Instrument, Span};
 
-/// A sender handle to a channel
-#[derive(Debug, Clone)]
-pub struct Sender<T> {
-    inner: mpsc::Sender<T>,
-    gauge: prometheus::IntGauge,
-    total_messages: Option<prometheus::IntCounter>,
+/// An item of the underlying channel
+#[derive(Debug)]
+enum Message<T> {
+    Value(Envelope<T>),
+    /// Queued by [`Sender::close_and_drain`] for the receiver to close the
+    /// channel once it gets there
+    Close,
+}
+
+impl<T> Message<T> {
+    fn into_value(self) -> T {
+        match self {
+            Message::Value(envelope) => envelope.value,
+            Message::Close => unreachable!("close markers are never handed back to senders"),
+        }
+    }
+}
+
+/// A sender handle to a channel, reporting to an observer of type `O`
+#[derive(Debug)]
+pub struct Sender<
+    T,
+    #[cfg(feature = "prometheus")] O: ChannelObserver = ChannelMetrics,
+    #[cfg(not(feature = "prometheus"))] O: ChannelObserver,
+> {
+    inner: mpsc::Sender<Message<T>>,
+    /// Set by [`close_and_drain`](Self::close_and_drain), failing the sends
+    /// of every sender while the receiver catches up
+    closing: Arc<AtomicBool>,
+    observer: O,
 }
 
-/// A receiver handle to a channel
+impl<T, O: ChannelObserver + Clone> Clone for Sender<T, O> {
+    fn clone(&self) -> Self {
+        self.observer.on_attach(HandleKind::Sender);
+        Self {
+            inner: self.inner.clone(),
+            closing: self.closing.clone(),
+            observer: self.observer.clone(),
+        }
+    }
+}
+
+/// A receiver handle to a channel, reporting to an observer of type `O`
 #[derive(Debug)]
-pub struct Receiver<T> {
-    inner: mpsc::Receiver<T>,
-    gauge: prometheus::IntGauge,
-    total_messages: Option<prometheus::IntCounter>,
+pub struct Receiver<
+    T,
+    #[cfg(feature = "prometheus")] O: ChannelObserver = ChannelMetrics,
+    #[cfg(not(feature = "prometheus"))] O: ChannelObserver,
+> {
+    inner: mpsc::Receiver<Message<T>>,
+    observer: O,
+    /// When [`poll_recv`](Self::poll_recv) started waiting on an empty channel
+    idle_since: Option<Instant>,
+    /// Whether the close has been reported, so that a close marker reached
+    /// after [`close`](Self::close) is not reported again
+    closed: bool,
+}
+
+/// Outcome of [`Receiver::drain`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DrainReport {
+    /// Number of values handed to the consumer
+    pub processed: usize,
+    /// Number of values still queued at the deadline, discarded
+    pub abandoned: usize,
 }
 
 /// A permit for sending a value
-pub struct Permit<'a, T> {
-    sender: &'a Sender<T>,
-    _permit: mpsc::Permit<'a, T>,
+pub struct Permit<
+    'a,
+    T,
+    #[cfg(feature = "prometheus")] O: ChannelObserver = ChannelMetrics,
+    #[cfg(not(feature = "prometheus"))] O: ChannelObserver,
+> {
+    sender: &'a Sender<T, O>,
+    _permit: mpsc::Permit<'a, Message<T>>,
 }
 
-impl<T> Permit<'_, T> {
+impl<T, O: ChannelObserver> Permit<'_, T, O> {
     /// Send a value using this permit
+    ///
+    /// If [`Sender::close_and_drain`] was called since the permit was
+    /// reserved, the value is dropped instead, as the close may already be
+    /// queued ahead of it.
     pub fn send(self, value: T) {
-        self._permit.send(value);
-        self.sender.gauge.inc();
-        if let Some(ref counter) = self.sender.total_messages {
-            counter.inc();
+        if self.sender.is_closing() {
+            error!("failed to send value, channel closed");
+            return;
         }
+        self._permit
+            .send(Message::Value(Envelope::new(value, &self.sender.observer)));
+        self.sender.observer.on_send();
     }
 }
 
 /// Creates a new channel with the given buffer size and metrics
+#[cfg(feature = "prometheus")]
 pub fn channel<T>(buffer: usize, metrics: ChannelMetrics) -> (Sender<T>, Receiver<T>) {
+    channel_with_observer(buffer, metrics)
+}
+
+/// Creates a new channel with the given buffer size, reporting to `observer`
+pub fn channel_with_observer<T, O: ChannelObserver + Clone>(
+    buffer: usize,
+    observer: O,
+) -> (Sender<T, O>, Receiver<T, O>) {
     let (tx, rx) = mpsc::channel(buffer);
-    let gauge = metrics.queue_size;
-    let total_messages = metrics.total_messages;
+    observer.on_open(ChannelKind::Mpsc);
+    observer.on_capacity(buffer);
 
     (
         Sender {
             inner: tx,
-            gauge: gauge.clone(),
-            total_messages: total_messages.clone(),
+            closing: Arc::new(AtomicBool::new(false)),
+            observer: observer.clone(),
         },
         Receiver {
             inner: rx,
-            gauge,
-            total_messages,
+            observer,
+            idle_since: None,
+            closed: false,
         },
     )
 }
 
+/// Creates a new channel with the given buffer size, registering metrics
+/// prefixed with `name` in the [default registry](crate::default_registry)
+///
+/// Channels created with the same name share their metrics.
+#[cfg(feature = "prometheus")]
+pub fn channel_named<T>(
+    name: &str,
+    buffer: usize,
+) -> Result<(Sender<T>, Receiver<T>), prometheus::Error> {
+    MeteredChannelBuilder::new()
+        .name(name)
+        .capacity(buffer)
+        .with_total()
+        .shared()
+        .build_mpsc()
+}
+
 /// Creates a new channel with total message counting
+#[cfg(feature = "prometheus")]
 pub fn channel_with_total<T>(
     buffer: usize,
     gauge: &prometheus::IntGauge,
     total: &prometheus::IntCounter,
 ) -> (Sender<T>, Receiver<T>) {
-    let (tx, rx) = mpsc::channel(buffer);
-
-    (
-        Sender {
-            inner: tx,
-            gauge: gauge.clone(),
-            total_messages: Some(total.clone()),
-        },
-        Receiver {
-            inner: rx,
-            gauge: gauge.clone(),
-            total_messages: Some(total.clone()),
-        },
-    )
+    let metrics = ChannelMetrics::from_collectors(gauge.clone(), Some(total.clone()));
+    channel(buffer, metrics)
 }
 
-impl<T> Sender<T> {
+impl<T, O: ChannelObserver> Sender<T, O> {
     /// Try to send a value without waiting for capacity
     pub fn try_send(&self, value: T) -> Result<(), SendError<T>> {
-        match self.inner.try_send(value) {
+        if self.is_closing() {
+            return Err(SendError::Closed(value));
+        }
+        let message = Message::Value(Envelope::new(value, &self.observer));
+        match self.inner.try_send(message) {
             Ok(()) => {
-                self.gauge.inc();
-                if let Some(ref counter) = self.total_messages {
-                    counter.inc();
-                }
+                self.observer.on_send();
                 Ok(())
             }
-            Err(err) => Err(err.into()),
+            Err(err) => Err(SendError::from(err).map(Message::into_value)),
         }
     }
 
     /// Send a value, waiting for capacity if needed
-    #[instrument(skip(self, value), level = "debug")]
     pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let (context, span) = trace::send_span(&self.observer);
+        span.instrument(self.send_inner(value, context)).await
+    }
+
+    async fn send_inner(&self, value: T, context: SpanContext) -> Result<(), SendError<T>> {
         debug!("attempting to send value");
-        match self.inner.send(value).await {
-            Ok(()) => {
-                self.gauge.inc();
-                if let Some(ref counter) = self.total_messages {
-                    counter.inc();
-                }
+        let permit = match self.inner.try_reserve() {
+            Ok(permit) => Ok(permit),
+            Err(TrySendError::Full(())) => {
+                debug!("channel full, waiting for capacity");
+                let waiting = Waiting::new(&self.observer);
+                let permit = self.inner.reserve().await;
+                waiting.finish();
+                permit.map_err(|_| ())
+            }
+            Err(TrySendError::Closed(())) => Err(()),
+        };
+
+        // The channel may have been closed while waiting for capacity
+        match permit.and_then(|permit| self.unless_closing(permit)) {
+            Ok(permit) => {
+                let envelope = Envelope::with_context(value, &self.observer, context);
+                permit.send(Message::Value(envelope));
+                self.observer.on_send();
                 debug!("value sent successfully");
                 Ok(())
             }
-            Err(err) => {
-                error!(?err, "failed to send value");
-                Err(err.into())
+            Err(()) => {
+                error!("failed to send value, channel closed");
+                Err(SendError::Closed(value))
             }
         }
     }
 
-    /// Returns true if the channel has been closed
+    /// Returns true if the channel has been closed, or is being closed by
+    /// [`close_and_drain`](Self::close_and_drain)
     pub fn is_closed(&self) -> bool {
-        self.inner.is_closed()
+        self.is_closing() || self.inner.is_closed()
+    }
+
+    fn is_closing(&self) -> bool {
+        self.closing.load(Ordering::Acquire)
+    }
+
+    /// Give back a reserved slot if the channel is being closed
+    fn unless_closing<P>(&self, permit: P) -> Result<P, ()> {
+        if self.is_closing() {
+            Err(())
+        } else {
+            Ok(permit)
+        }
+    }
+
+    /// Wait until the channel is closed
+    #[cfg(feature = "prometheus")]
+    pub(crate) async fn closed(&self) {
+        self.inner.closed().await
+    }
+
+    /// Stop new sends, and wait until the receiver has taken the values queued
+    /// so far, or until it is dropped.
+    ///
+    /// Sends from every sender fail as soon as this is called. A close is
+    /// then queued behind the values already sent, waiting for capacity if
+    /// needed. Once the receiver reaches it, the channel is closed as if by
+    /// [`Receiver::close`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tokio_prometheus_metered_channel::{mpsc_channel_with_observer, NoopObserver};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let (tx, mut rx) = mpsc_channel_with_observer(10, NoopObserver);
+    ///     tx.send(1).await.unwrap();
+    ///     tx.send(2).await.unwrap();
+    ///
+    ///     let consumer = tokio::spawn(async move {
+    ///         let mut sum = 0;
+    ///         while let Some(value) = rx.recv().await {
+    ///             sum += value;
+    ///         }
+    ///         sum
+    ///     });
+    ///     tx.close_and_drain().await;
+    ///     assert!(tx.send(3).await.is_err());
+    ///
+    ///     drop(tx);
+    ///     assert_eq!(consumer.await.unwrap(), 3);
+    /// }
+    /// ```
+    pub async fn close_and_drain(&self) {
+        debug!("queueing channel close");
+        self.closing.store(true, Ordering::Release);
+        if self.inner.send(Message::Close).await.is_ok() {
+            self.inner.closed().await;
+        }
+    }
+
+    /// Get the observer receiving the channel's events
+    pub fn observer(&self) -> &O {
+        &self.observer
     }
 }
 
-impl<T> Receiver<T> {
+impl<T, O: ChannelObserver> Receiver<T, O> {
     /// Receive the next value
-    #[instrument(skip(self), level = "debug")]
     pub async fn recv(&mut self) -> Option<T> {
-        debug!("waiting to receive value");
-        let msg = self.inner.recv().await;
-        if msg.is_some() {
-            self.gauge.dec();
-            debug!("value received successfully");
-        } else {
-            debug!("channel closed, no more values");
+        let span = trace::span(&self.observer, Op::Recv);
+        let msg = span
+            .instrument(async {
+                debug!("waiting to receive value");
+                let msg = self.next().await;
+                if msg.is_some() {
+                    debug!("value received successfully");
+                } else {
+                    debug!("channel closed, no more values");
+                }
+                msg
+            })
+            .await;
+        msg.map(|envelope| self.received(envelope))
+    }
+
+    /// Poll to receive the next value, registering the current task for wakeup
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        loop {
+            let message = match self.inner.poll_recv(cx) {
+                Poll::Ready(message) => message,
+                Poll::Pending => {
+                    self.idle_since.get_or_insert_with(Instant::now);
+                    return Poll::Pending;
+                }
+            };
+            if let Some(start) = self.idle_since.take() {
+                self.observer.on_idle(start.elapsed());
+            }
+            match message {
+                Some(Message::Value(envelope)) => {
+                    return Poll::Ready(Some(self.received(envelope)))
+                }
+                Some(Message::Close) => self.close(),
+                None => return Poll::Ready(None),
+            }
         }
-        msg
+    }
+
+    /// Receive the next value in a guard recording how long the consumer
+    /// spends processing it
+    pub async fn recv_guarded(&mut self) -> Option<MessageGuard<T, O>>
+    where
+        O: Clone,
+    {
+        let value = self.recv().await?;
+        Some(MessageGuard::new(value, self.observer().clone()))
     }
 
     /// Try to receive a value without waiting
     pub fn try_recv(&mut self) -> Result<T, mpsc::error::TryRecvError> {
-        match self.inner.try_recv() {
-            Ok(msg) => {
-                self.gauge.dec();
-                Ok(msg)
+        self.try_next().map(|envelope| self.received(envelope))
+    }
+
+    /// Receive the next value along with the span it was sent from.
+    ///
+    /// The span is the sender's current span when the value was sent if the
+    /// channel propagates trace context (see [`ChannelTrace::propagate`](crate::ChannelTrace::propagate)), and
+    /// a disabled span otherwise. Instrumenting the processing of the value
+    /// with it keeps the trace going across the hop between tasks.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tokio_prometheus_metered_channel::{mpsc_channel_with_observer, ChannelObserver, ChannelTrace};
+    /// use tracing::Instrument;
+    ///
+    /// #[derive(Clone)]
+    /// struct Traced;
+    ///
+    /// impl ChannelObserver for Traced {
+    ///     fn trace(&self) -> ChannelTrace {
+    ///         ChannelTrace::new().propagate(true)
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let (tx, mut rx) = mpsc_channel_with_observer(10, Traced);
+    ///     tx.send(42)
+    ///         .instrument(tracing::info_span!("request"))
+    ///         .await
+    ///         .unwrap();
+    ///
+    ///     let (value, span) = rx.recv_traced().await.unwrap();
+    ///     async move { assert_eq!(value, 42) }.instrument(span).await;
+    /// }
+    /// ```
+    #[cfg(feature = "tracing")]
+    pub async fn recv_traced(&mut self) -> Option<(T, Span)> {
+        let span = trace::span(&self.observer, Op::Recv);
+        let msg = span.instrument(self.next()).await;
+        msg.map(|envelope| envelope.open_traced(&self.observer))
+    }
+
+    /// Try to receive a value along with the span it was sent from, without waiting
+    #[cfg(feature = "tracing")]
+    pub fn try_recv_traced(&mut self) -> Result<(T, Span), mpsc::error::TryRecvError> {
+        self.try_next()
+            .map(|envelope| envelope.open_traced(&self.observer))
+    }
+
+    /// Wait for the next envelope, reporting the time spent on an empty channel
+    async fn next(&mut self) -> Option<Envelope<T>> {
+        match self.try_next() {
+            Ok(envelope) => Some(envelope),
+            Err(mpsc::error::TryRecvError::Disconnected) => None,
+            Err(mpsc::error::TryRecvError::Empty) => {
+                // A wait started by `poll_recv` carries on here
+                let start = self.idle_since.take().unwrap_or_else(Instant::now);
+                let _idle = Idle::since(&self.observer, start);
+                loop {
+                    match self.inner.recv().await {
+                        Some(Message::Value(envelope)) => return Some(envelope),
+                        Some(Message::Close) => {
+                            self.inner.close();
+                            if !std::mem::replace(&mut self.closed, true) {
+                                self.observer.on_close();
+                            }
+                        }
+                        None => return None,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Take the next envelope if one is queued, closing the channel on the
+    /// way if a close was queued before it
+    fn try_next(&mut self) -> Result<Envelope<T>, mpsc::error::TryRecvError> {
+        loop {
+            match self.inner.try_recv()? {
+                Message::Value(envelope) => return Ok(envelope),
+                Message::Close => self.close(),
+            }
+        }
+    }
+
+    /// Discard the queued values, reporting them dropped, and return how many
+    /// there were
+    fn discard(&mut self) -> usize {
+        self.discard_with(|_, _| {})
+    }
+
+    /// Close the channel and discard the queued values like [`discard`](Self::discard),
+    /// handing each of them to `discarded` along with the observer
+    pub(crate) fn discard_with(&mut self, mut discarded: impl FnMut(T, &O)) -> usize {
+        self.inner.close();
+        let mut count = 0;
+        while let Ok(message) = self.inner.try_recv() {
+            if let Message::Value(envelope) = message {
+                count += 1;
+                discarded(envelope.value, &self.observer);
+            }
+        }
+        if count > 0 {
+            self.observer.on_drop(count);
+        }
+        count
+    }
+
+    /// Close the channel and hand the values still queued to `process` until
+    /// `timeout` elapses, discarding the rest.
+    ///
+    /// A value still being processed at the deadline is interrupted and counts
+    /// as abandoned. The values processed and abandoned are reported to the
+    /// observer, the abandoned ones still queued also as dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use tokio_prometheus_metered_channel::{mpsc_channel, ChannelMetrics, ChannelMetricsOpts};
+    /// use prometheus::Registry;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let opts = ChannelMetricsOpts::new("jobs").with_drain();
+    ///     let metrics = ChannelMetrics::with_opts(&opts, &Registry::new()).unwrap();
+    ///     let (tx, mut rx) = mpsc_channel(10, metrics);
+    ///     for i in 0..3 {
+    ///         tx.send(i).await.unwrap();
+    ///     }
+    ///
+    ///     let report = rx
+    ///         .drain(Duration::from_secs(1), |job| async move { println!("finishing job {}", job) })
+    ///         .await;
+    ///     assert_eq!((report.processed, report.abandoned), (3, 0));
+    ///     assert!(tx.send(4).await.is_err());
+    ///     assert_eq!(rx.observer().drained_total.as_ref().unwrap().get(), 3);
+    /// }
+    /// ```
+    pub async fn drain<F, Fut>(&mut self, timeout: Duration, process: F) -> DrainReport
+    where
+        F: FnMut(T) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        self.drain_with(timeout, process, |_, _| {}).await
+    }
+
+    /// Drain like [`drain`](Self::drain), handing the abandoned values to `discarded`
+    pub(crate) async fn drain_with<F, Fut>(
+        &mut self,
+        timeout: Duration,
+        mut process: F,
+        discarded: impl FnMut(T, &O),
+    ) -> DrainReport
+    where
+        F: FnMut(T) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        debug!("draining channel");
+        self.close();
+        let deadline = Instant::now() + timeout;
+        let mut processed = 0;
+        let mut interrupted = 0;
+        // The timeout only fires on an empty channel, as queued values are
+        // ready before it is checked
+        while Instant::now() < deadline {
+            let value = match tokio::time::timeout_at(deadline, self.recv()).await {
+                Ok(Some(value)) => value,
+                Ok(None) | Err(_) => break,
+            };
+            if tokio::time::timeout_at(deadline, process(value))
+                .await
+                .is_err()
+            {
+                interrupted = 1;
+                break;
             }
-            Err(e) => Err(e),
+            processed += 1;
+        }
+
+        let abandoned = interrupted + self.discard_with(discarded);
+        if abandoned > 0 {
+            debug!(abandoned, "drain timed out, discarded queued values");
+        }
+        self.observer.on_drain(processed, abandoned);
+        DrainReport {
+            processed,
+            abandoned,
         }
     }
 
+    fn received(&self, envelope: Envelope<T>) -> T {
+        envelope.open(&self.observer)
+    }
+
     /// Close the channel
     pub fn close(&mut self) {
-        self.inner.close()
+        self.inner.close();
+        if !std::mem::replace(&mut self.closed, true) {
+            self.observer.on_close();
+        }
     }
 
+    /// Get the observer receiving the channel's events
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+}
+
+#[cfg(feature = "prometheus")]
+impl<T> Receiver<T, ChannelMetrics> {
     /// Get the total messages counter if enabled
     pub fn total_messages(&self) -> Option<&prometheus::IntCounter> {
-        self.total_messages.as_ref()
+        self.observer.total_messages.as_ref()
+    }
+}
+
+impl<T, O: ChannelObserver> Drop for Sender<T, O> {
+    fn drop(&mut self) {
+        self.observer.on_detach(HandleKind::Sender);
+    }
+}
+
+impl<T, O: ChannelObserver> Drop for Receiver<T, O> {
+    fn drop(&mut self) {
+        if let Some(start) = self.idle_since.take() {
+            self.observer.on_idle(start.elapsed());
+        }
+        // Report the values still queued instead of letting them vanish
+        self.discard();
+        self.observer.on_detach(HandleKind::Receiver);
     }
 }
 
 /// Trait for types that support permit-based sending
 #[async_trait]
-pub trait WithPermit<T>: Send + Sync {
+pub trait WithPermit<
+    T,
+    #[cfg(feature = "prometheus")] O: ChannelObserver = ChannelMetrics,
+    #[cfg(not(feature = "prometheus"))] O: ChannelObserver,
+>: Send + Sync
+{
     /// Reserve capacity to send a value
-    async fn reserve(&self) -> Result<Permit<'_, T>, SendError<()>>;
+    async fn reserve(&self) -> Result<Permit<'_, T, O>, SendError<()>>;
 
     /// Wait for a permit and a future to complete
-    async fn with_permit<F>(&self, future: F) -> Result<(Permit<'_, T>, F::Output), SendError<()>>
+    async fn with_permit<F>(
+        &self,
+        future: F,
+    ) -> Result<(Permit<'_, T, O>, F::Output), SendError<()>>
     where
         F: Future + Send,
         F::Output: Send;
 }
 
 #[async_trait]
-impl<T: Send> WithPermit<T> for Sender<T> {
-    async fn reserve(&self) -> Result<Permit<'_, T>, SendError<()>> {
-        match self.inner.reserve().await {
+impl<T: Send, O: ChannelObserver> WithPermit<T, O> for Sender<T, O> {
+    async fn reserve(&self) -> Result<Permit<'_, T, O>, SendError<()>> {
+        if self.is_closing() {
+            return Err(SendError::Closed(()));
+        }
+        // Polled once before waiting, rather than tried, so that reserving
+        // still yields to the runtime once the task's budget is spent
+        let reserve = self.inner.reserve();
+        tokio::pin!(reserve);
+        let polled = std::future::poll_fn(|cx| Poll::Ready(reserve.as_mut().poll(cx))).await;
+        let permit = match polled {
+            Poll::Ready(permit) => permit,
+            Poll::Pending => {
+                debug!("channel full, waiting for a permit");
+                let waiting = Waiting::new(&self.observer);
+                let permit = reserve.await;
+                waiting.finish();
+                permit
+            }
+        };
+        match permit
+            .map_err(|_| ())
+            .and_then(|permit| self.unless_closing(permit))
+        {
             Ok(permit) => Ok(Permit {
                 sender: self,
                 _permit: permit,
             }),
-            Err(_) => Err(SendError::Closed(())),
+            Err(()) => Err(SendError::Closed(())),
         }
     }
 
-    #[instrument(skip(self, future), level = "debug")]
-    async fn with_permit<F>(&self, future: F) -> Result<(Permit<'_, T>, F::Output), SendError<()>>
+    async fn with_permit<F>(
+        &self,
+        future: F,
+    ) -> Result<(Permit<'_, T, O>, F::Output), SendError<()>>
     where
         F: Future + Send,
         F::Output: Send,
     {
-        // First get a permit to ensure we have capacity
-        debug!("requesting permit");
-        let permit = self.reserve().await?;
-        debug!("permit acquired");
-
-        // Then execute the future - this ensures we don't lose the future's result
-        // if the permit acquisition is cancelled
-        let output = future
-            .instrument(span!(Level::DEBUG, "permit_future"))
-            .await;
+        let span = trace::span(&self.observer, Op::WithPermit);
+        span.instrument(async {
+            // First get a permit to ensure we have capacity
+            debug!("requesting permit");
+            let permit = self.reserve().await?;
+            debug!("permit acquired");
+
+            // Then execute the future - this ensures we don't lose the future's result
+            // if the permit acquisition is cancelled
+            #[cfg(feature = "tracing")]
+            let future = future.instrument(tracing::debug_span!("permit_future"));
+            let output = future.await;
 
-        debug!("future completed with permit");
-        Ok((permit, output))
+            debug!("future completed with permit");
+            Ok((permit, output))
+        })
+        .await
     }
 }
 
-impl<T> Sink<T> for Sender<T> {
+impl<T, O: ChannelObserver> Sink<T> for Sender<T, O> {
     type Error = SendError<T>;
 
     fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
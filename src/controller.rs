@@ -0,0 +1,197 @@
+use crate::metrics::ControllerMetrics;
+use crate::resizable::ResizeHandle;
+use crate::trace::{debug, info};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+
+/// What a [`CapacityController`] tries to achieve.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ControllerTarget {
+    /// Keep the queue at this fraction of the capacity, between 0 and 1.
+    ///
+    /// The capacity grows when the queue is fuller than the target and
+    /// shrinks when it is emptier.
+    Utilization(f64),
+    /// Keep the mean time values spend queued at or below this duration.
+    ///
+    /// The capacity shrinks when values wait longer than the target, and
+    /// grows only while the channel is full and values wait less.
+    Latency(Duration),
+}
+
+/// Configuration of a [`CapacityController`]
+#[derive(Debug, Clone)]
+pub struct ControllerConfig {
+    /// Smallest capacity the controller may set
+    pub min_capacity: usize,
+    /// Largest capacity the controller may set
+    pub max_capacity: usize,
+    /// Value the controller steers towards
+    pub target: ControllerTarget,
+    /// Time between two adjustments
+    pub interval: Duration,
+    /// Largest factor by which a single adjustment may grow or shrink the capacity
+    pub max_step: f64,
+    /// Relative change below which the capacity is left untouched
+    pub tolerance: f64,
+}
+
+impl ControllerConfig {
+    /// Create a configuration with a one second interval, a maximum step of 2
+    /// and a tolerance of 10%
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min_capacity` is 0 or greater than `max_capacity`.
+    pub fn new(min_capacity: usize, max_capacity: usize, target: ControllerTarget) -> Self {
+        assert!(min_capacity > 0, "minimum capacity must be greater than 0");
+        assert!(
+            min_capacity <= max_capacity,
+            "minimum capacity cannot exceed maximum capacity"
+        );
+
+        Self {
+            min_capacity,
+            max_capacity,
+            target,
+            interval: Duration::from_secs(1),
+            max_step: 2.0,
+            tolerance: 0.1,
+        }
+    }
+}
+
+/// Periodically adjusts the capacity of a [resizable](crate::resizable) channel.
+///
+/// # Examples
+///
+/// ```rust
+/// use tokio_prometheus_metered_channel::{
+///     resizable_channel, CapacityController, ChannelMetrics, ControllerConfig, ControllerMetrics,
+///     ControllerTarget,
+/// };
+/// use prometheus::Registry;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let registry = Registry::new();
+///     let metrics = ChannelMetrics::new_with_capacity("example", "example", &registry).unwrap();
+///     let (tx, _rx) = resizable_channel::<u64>(4, metrics);
+///
+///     let config = ControllerConfig::new(2, 64, ControllerTarget::Utilization(0.5));
+///     let mut controller = CapacityController::new(
+///         tx.resize_handle(),
+///         config,
+///         ControllerMetrics::new("example", "example", &registry).unwrap(),
+///     );
+///
+///     for i in 0..4 {
+///         tx.send(i).await.unwrap();
+///     }
+///
+///     // A full queue is twice the target utilization, so the capacity doubles
+///     assert_eq!(controller.tick(), Some(8));
+/// }
+/// ```
+#[derive(Debug)]
+pub struct CapacityController {
+    handle: ResizeHandle,
+    config: ControllerConfig,
+    metrics: ControllerMetrics,
+}
+
+impl CapacityController {
+    /// Create a controller for the channel behind `handle`
+    pub fn new(handle: ResizeHandle, config: ControllerConfig, metrics: ControllerMetrics) -> Self {
+        Self {
+            handle,
+            config,
+            metrics,
+        }
+    }
+
+    /// Run one adjustment step, returning the new capacity if it changed
+    pub fn tick(&mut self) -> Option<usize> {
+        let capacity = self.handle.capacity();
+        let len = self.handle.len();
+
+        let (observed, desired) = match self.config.target {
+            ControllerTarget::Utilization(target) => {
+                let observed = len as f64 / capacity as f64;
+                (observed, len as f64 / target)
+            }
+            ControllerTarget::Latency(target) => {
+                let latency = match self.handle.take_latency() {
+                    Some(latency) => latency,
+                    None => {
+                        debug!(capacity, len, "no values received, holding capacity");
+                        return None;
+                    }
+                };
+                let observed = latency.as_secs_f64();
+                let desired = capacity as f64 * target.as_secs_f64() / observed.max(f64::EPSILON);
+                if desired > capacity as f64 && len < capacity {
+                    debug!(
+                        capacity,
+                        len, observed, "channel not saturated, holding capacity"
+                    );
+                    self.metrics.observed.set(observed);
+                    return None;
+                }
+                (observed, desired)
+            }
+        };
+        self.metrics.observed.set(observed);
+
+        let step = self.config.max_step.max(1.0);
+        let desired = desired
+            .clamp(capacity as f64 / step, capacity as f64 * step)
+            .ceil()
+            .clamp(
+                self.config.min_capacity as f64,
+                self.config.max_capacity as f64,
+            ) as usize;
+
+        let change = desired.abs_diff(capacity) as f64 / capacity as f64;
+        if desired == capacity || change < self.config.tolerance {
+            debug!(capacity, len, observed, "capacity within tolerance");
+            return None;
+        }
+
+        let direction = if desired > capacity { "grow" } else { "shrink" };
+        self.handle.set_capacity(desired);
+        self.metrics
+            .adjustments
+            .with_label_values(&[direction])
+            .inc();
+        info!(
+            old_capacity = capacity,
+            new_capacity = desired,
+            len,
+            observed,
+            direction,
+            "adjusted channel capacity"
+        );
+        Some(desired)
+    }
+
+    /// Run the controller in a background task until the channel is closed
+    pub fn spawn(mut self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.config.interval);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            // The first tick completes immediately
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+                if self.handle.is_closed() {
+                    debug!("channel closed, stopping capacity controller");
+                    break;
+                }
+                self.tick();
+            }
+        })
+    }
+}
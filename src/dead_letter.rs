@@ -0,0 +1,275 @@
+use crate::channel::{DrainReport, Receiver, Sender};
+use crate::error::SendError;
+#[cfg(feature = "prometheus")]
+use crate::metrics::ChannelMetrics;
+use crate::observer::ChannelObserver;
+use crate::trace::warning;
+use std::fmt;
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A value a channel could not deliver, captured by its dead-letter channel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadLetter<T> {
+    /// The undelivered value
+    pub value: T,
+    /// Why the value was not delivered
+    pub reason: DeadLetterReason,
+    /// Name of the channel the value was sent to
+    pub channel: Arc<str>,
+}
+
+/// Why a value ended up in a dead-letter channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeadLetterReason {
+    /// The channel was full when the value was sent with `try_send`
+    Full,
+    /// The channel was closed when the value was sent
+    Closed,
+    /// The value was still queued when the receiver was dropped or its drain timed out
+    Discarded,
+}
+
+impl DeadLetterReason {
+    /// Get the reason as a lowercase string
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeadLetterReason::Full => "full",
+            DeadLetterReason::Closed => "closed",
+            DeadLetterReason::Discarded => "discarded",
+        }
+    }
+}
+
+impl fmt::Display for DeadLetterReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Where a channel sends the values it could not deliver
+#[derive(Debug)]
+struct Sink<T, D: ChannelObserver> {
+    sender: Sender<DeadLetter<T>, D>,
+    channel: Arc<str>,
+}
+
+impl<T, D: ChannelObserver> Sink<T, D> {
+    fn new(sender: Sender<DeadLetter<T>, D>, channel: &str) -> Self {
+        Self {
+            sender,
+            channel: channel.into(),
+        }
+    }
+
+    /// Send `value` to the dead-letter channel without waiting, reporting
+    /// whether it had room to `observer`
+    fn capture(&self, value: T, reason: DeadLetterReason, observer: &impl ChannelObserver) {
+        let letter = DeadLetter {
+            value,
+            reason,
+            channel: self.channel.clone(),
+        };
+        let captured = self.sender.try_send(letter).is_ok();
+        if !captured {
+            warning!(channel = %self.channel, %reason, "dead-letter channel unavailable, value lost");
+        }
+        observer.on_dead_letter(captured);
+    }
+}
+
+impl<T, D: ChannelObserver + Clone> Clone for Sink<T, D> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+/// An mpsc sender handing the values it fails to send to a dead-letter channel.
+///
+/// The dead-letter channel is an ordinary bounded channel with its own
+/// observer. Values are sent to it without waiting; when it is full or closed
+/// they are lost. Both outcomes are reported to the observer of the wrapped
+/// channel with [`on_dead_letter`](ChannelObserver::on_dead_letter).
+///
+/// # Examples
+///
+/// ```rust
+/// use tokio_prometheus_metered_channel::{
+///     mpsc_channel_with_dead_letters, mpsc_channel_with_observer, CountingObserver,
+///     DeadLetterReason, SendError,
+/// };
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let (dead_tx, mut dead_rx) = mpsc_channel_with_observer(100, CountingObserver::new());
+///     let observer = CountingObserver::new();
+///     let (tx, _rx) = mpsc_channel_with_dead_letters(1, observer.clone(), dead_tx);
+///
+///     tx.try_send(1).unwrap();
+///     assert!(matches!(tx.try_send(2), Err(SendError::Full(()))));
+///
+///     let letter = dead_rx.recv().await.unwrap();
+///     assert_eq!((letter.value, letter.reason), (2, DeadLetterReason::Full));
+///     assert_eq!(observer.dead_letters(), 1);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct DeadLetterSender<
+    T,
+    #[cfg(feature = "prometheus")] O: ChannelObserver = ChannelMetrics,
+    #[cfg(not(feature = "prometheus"))] O: ChannelObserver,
+    #[cfg(feature = "prometheus")] D: ChannelObserver = ChannelMetrics,
+    #[cfg(not(feature = "prometheus"))] D: ChannelObserver,
+> {
+    inner: Sender<T, O>,
+    sink: Sink<T, D>,
+}
+
+impl<T, O: ChannelObserver, D: ChannelObserver> DeadLetterSender<T, O, D> {
+    /// Wrap `sender` so that the values it fails to send go to `dead_letters`
+    pub fn new(sender: Sender<T, O>, dead_letters: Sender<DeadLetter<T>, D>) -> Self {
+        let sink = Sink::new(dead_letters, sender.observer().name());
+        Self {
+            inner: sender,
+            sink,
+        }
+    }
+
+    /// Try to send a value without waiting for capacity, sending it to the
+    /// dead-letter channel if the channel is full or closed
+    pub fn try_send(&self, value: T) -> Result<(), SendError<()>> {
+        self.inner.try_send(value).map_err(|err| match err {
+            SendError::Full(value) => {
+                self.capture(value, DeadLetterReason::Full);
+                SendError::Full(())
+            }
+            SendError::Closed(value) => {
+                self.capture(value, DeadLetterReason::Closed);
+                SendError::Closed(())
+            }
+        })
+    }
+
+    /// Send a value, waiting for capacity if needed, sending it to the
+    /// dead-letter channel if the channel is closed
+    pub async fn send(&self, value: T) -> Result<(), SendError<()>> {
+        self.inner.send(value).await.map_err(|err| {
+            let value = match err {
+                SendError::Full(value) | SendError::Closed(value) => value,
+            };
+            self.capture(value, DeadLetterReason::Closed);
+            SendError::Closed(())
+        })
+    }
+
+    fn capture(&self, value: T, reason: DeadLetterReason) {
+        self.sink.capture(value, reason, self.inner.observer());
+    }
+
+    /// Returns true if the channel has been closed
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    /// Get the wrapped sender, bypassing the dead-letter channel
+    pub fn inner(&self) -> &Sender<T, O> {
+        &self.inner
+    }
+}
+
+impl<T, O: ChannelObserver + Clone, D: ChannelObserver + Clone> Clone
+    for DeadLetterSender<T, O, D>
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            sink: self.sink.clone(),
+        }
+    }
+}
+
+/// An mpsc receiver handing the values it discards to a dead-letter channel.
+///
+/// Values still queued when the receiver is dropped, or when a
+/// [`drain`](Self::drain) times out, are sent to the dead-letter channel
+/// instead of being discarded. Everything else behaves like the wrapped
+/// [`Receiver`](crate::MpscReceiver), which it dereferences to.
+#[derive(Debug)]
+pub struct DeadLetterReceiver<
+    T,
+    #[cfg(feature = "prometheus")] O: ChannelObserver = ChannelMetrics,
+    #[cfg(not(feature = "prometheus"))] O: ChannelObserver,
+    #[cfg(feature = "prometheus")] D: ChannelObserver = ChannelMetrics,
+    #[cfg(not(feature = "prometheus"))] D: ChannelObserver,
+> {
+    inner: Receiver<T, O>,
+    sink: Sink<T, D>,
+}
+
+impl<T, O: ChannelObserver, D: ChannelObserver> DeadLetterReceiver<T, O, D> {
+    /// Wrap `receiver` so that the values it discards go to `dead_letters`
+    pub fn new(receiver: Receiver<T, O>, dead_letters: Sender<DeadLetter<T>, D>) -> Self {
+        let sink = Sink::new(dead_letters, receiver.observer().name());
+        Self {
+            inner: receiver,
+            sink,
+        }
+    }
+
+    /// Drain like [`Receiver::drain`], sending the abandoned values still
+    /// queued to the dead-letter channel. A value interrupted while being
+    /// processed was already handed over, so it is not captured.
+    pub async fn drain<F, Fut>(&mut self, timeout: Duration, process: F) -> DrainReport
+    where
+        F: FnMut(T) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        let sink = &self.sink;
+        self.inner
+            .drain_with(timeout, process, |value, observer| {
+                sink.capture(value, DeadLetterReason::Discarded, observer)
+            })
+            .await
+    }
+}
+
+impl<T, O: ChannelObserver, D: ChannelObserver> Deref for DeadLetterReceiver<T, O, D> {
+    type Target = Receiver<T, O>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<T, O: ChannelObserver, D: ChannelObserver> DerefMut for DeadLetterReceiver<T, O, D> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+impl<T, O: ChannelObserver, D: ChannelObserver> Drop for DeadLetterReceiver<T, O, D> {
+    fn drop(&mut self) {
+        let sink = &self.sink;
+        self.inner.discard_with(|value, observer| {
+            sink.capture(value, DeadLetterReason::Discarded, observer)
+        });
+    }
+}
+
+/// Create a bounded mpsc channel whose undelivered values go to `dead_letters`
+pub fn channel_with_dead_letters<T, O: ChannelObserver + Clone, D: ChannelObserver + Clone>(
+    buffer: usize,
+    observer: O,
+    dead_letters: Sender<DeadLetter<T>, D>,
+) -> (DeadLetterSender<T, O, D>, DeadLetterReceiver<T, O, D>) {
+    let (tx, rx) = crate::channel::channel_with_observer(buffer, observer);
+    (
+        DeadLetterSender::new(tx, dead_letters.clone()),
+        DeadLetterReceiver::new(rx, dead_letters),
+    )
+}
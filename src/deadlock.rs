@@ -0,0 +1,178 @@
+use crate::stats::{live_trackers, ChannelStats};
+use crate::tasks::Links;
+use std::fmt;
+use std::sync::Arc;
+
+/// A cycle of [named tasks](crate::track_task), each waiting to send to a
+/// channel consumed only by tasks stuck in the cycle.
+///
+/// None of the sends can complete until one of the tasks is cancelled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deadlock {
+    /// The waiting sends, starting with the task with the smallest name. Each
+    /// channel is consumed by the task of the next send, and the last one by
+    /// the task of the first.
+    pub cycle: Vec<BlockedSend>,
+}
+
+/// A named task waiting for capacity on a channel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockedSend {
+    /// Name of the waiting task
+    pub task: String,
+    /// Fully qualified prefix of the channel's metric names
+    pub channel: String,
+    /// Number of items in the channel
+    pub depth: usize,
+}
+
+impl fmt::Display for Deadlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for send in &self.cycle {
+            write!(
+                f,
+                "{} -> {} ({} queued) -> ",
+                send.task, send.channel, send.depth
+            )?;
+        }
+        match self.cycle.first() {
+            Some(send) => f.write_str(&send.task),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Find the cycles of named tasks blocked on each other's channels.
+///
+/// Only channels created with [stats](crate::ChannelMetricsOpts::with_stats)
+/// are followed. A channel is consumed by the tasks that received from it or
+/// were declared with [`ChannelMetrics::consumed_by`](crate::ChannelMetrics::consumed_by),
+/// and a waiting send is stuck when every consumer of its channel is stuck too.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+/// use tokio_prometheus_metered_channel::{find_deadlocks, track_task, MeteredChannelBuilder};
+/// use prometheus::Registry;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let registry = Registry::new();
+///     let builder = MeteredChannelBuilder::new().capacity(1).registry(&registry).with_stats();
+///     let (to_b, mut from_a) = builder.clone().name("a_to_b").build_mpsc::<u32>().unwrap();
+///     let (to_a, mut from_b) = builder.name("b_to_a").build_mpsc::<u32>().unwrap();
+///     to_b.observer().consumed_by("b");
+///     to_a.observer().consumed_by("a");
+///
+///     // Each task fills the other's inbox before reading its own
+///     let a = tokio::spawn(track_task("a", async move {
+///         for i in 0..2 {
+///             to_b.send(i).await.unwrap();
+///         }
+///         from_b.recv().await
+///     }));
+///     let b = tokio::spawn(track_task("b", async move {
+///         for i in 0..2 {
+///             to_a.send(i).await.unwrap();
+///         }
+///         from_a.recv().await
+///     }));
+///     tokio::time::sleep(Duration::from_millis(50)).await;
+///
+///     let deadlocks = find_deadlocks();
+///     assert_eq!(deadlocks.len(), 1);
+///     assert_eq!(
+///         deadlocks[0].to_string(),
+///         "a -> a_to_b (1 queued) -> b -> b_to_a (1 queued) -> a"
+///     );
+///     a.abort();
+///     b.abort();
+/// }
+/// ```
+pub fn find_deadlocks() -> Vec<Deadlock> {
+    let channels: Vec<_> = live_trackers()
+        .iter()
+        .map(|tracker| (tracker.stats(), tracker.tasks().get()))
+        .collect();
+    let waits: Vec<(Arc<str>, usize)> = channels
+        .iter()
+        .enumerate()
+        .flat_map(|(index, (_, links))| {
+            links
+                .waiting
+                .iter()
+                .map(move |task| (Arc::clone(task), index))
+        })
+        .collect();
+
+    // Start from every waiting task and drop those that can still make
+    // progress, until only the tasks waiting on each other remain
+    let mut stuck: Vec<Arc<str>> = Vec::new();
+    for (task, _) in &waits {
+        if !stuck.contains(task) {
+            stuck.push(Arc::clone(task));
+        }
+    }
+    loop {
+        let still_stuck: Vec<_> = stuck
+            .iter()
+            .filter(|task| {
+                waits
+                    .iter()
+                    .filter(|(waiting, _)| waiting == *task)
+                    .all(|(_, index)| blocked(&channels[*index].1, &stuck))
+            })
+            .cloned()
+            .collect();
+        if still_stuck.len() == stuck.len() {
+            break;
+        }
+        stuck = still_stuck;
+    }
+
+    let mut deadlocks = Vec::new();
+    let mut visited: Vec<Arc<str>> = Vec::new();
+    for start in &stuck {
+        let mut path: Vec<(Arc<str>, usize)> = Vec::new();
+        let mut task = Arc::clone(start);
+        while !visited.contains(&task) {
+            if let Some(position) = path.iter().position(|(waiting, _)| *waiting == task) {
+                deadlocks.push(deadlock(&path[position..], &channels));
+                break;
+            }
+            let (_, index) = waits
+                .iter()
+                .find(|(waiting, _)| *waiting == task)
+                .expect("stuck tasks are waiting");
+            let next = Arc::clone(&channels[*index].1.consumers[0]);
+            path.push((task, *index));
+            task = next;
+        }
+        visited.extend(path.into_iter().map(|(task, _)| task));
+    }
+    deadlocks.sort_by(|a, b| a.cycle[0].task.cmp(&b.cycle[0].task));
+    deadlocks
+}
+
+/// Whether a send waiting on a channel with these links is stuck behind the
+/// `stuck` tasks
+fn blocked(links: &Links, stuck: &[Arc<str>]) -> bool {
+    !links.consumers.is_empty() && links.consumers.iter().all(|task| stuck.contains(task))
+}
+
+fn deadlock(cycle: &[(Arc<str>, usize)], channels: &[(ChannelStats, Links)]) -> Deadlock {
+    let mut cycle: Vec<_> = cycle
+        .iter()
+        .map(|(task, index)| BlockedSend {
+            task: task.to_string(),
+            channel: channels[*index].0.name.clone(),
+            depth: channels[*index].0.len,
+        })
+        .collect();
+    let first = (0..cycle.len())
+        .min_by(|&a, &b| cycle[a].task.cmp(&cycle[b].task))
+        .unwrap_or_default();
+    cycle.rotate_left(first);
+    Deadlock { cycle }
+}
@@ -0,0 +1,50 @@
+use crate::observer::ChannelObserver;
+use crate::trace::SpanContext;
+use tokio::time::Instant;
+#[cfg(all(feature = "tracing", feature = "mpsc"))]
+use tracing::Span;
+
+/// A value in transit, stamped with its enqueue time when queue latency is
+/// measured and with the sender's span when trace context is propagated
+#[derive(Debug, Clone)]
+pub(crate) struct Envelope<T> {
+    pub(crate) value: T,
+    enqueued_at: Option<Instant>,
+    context: SpanContext,
+}
+
+impl<T> Envelope<T> {
+    #[cfg(feature = "mpsc")]
+    pub(crate) fn new<O: ChannelObserver + ?Sized>(value: T, observer: &O) -> Self {
+        Self::with_context(value, observer, SpanContext::capture(observer))
+    }
+
+    /// Wrap a value sent from the span captured in `context`
+    pub(crate) fn with_context<O: ChannelObserver + ?Sized>(
+        value: T,
+        observer: &O,
+        context: SpanContext,
+    ) -> Self {
+        Self {
+            value,
+            enqueued_at: observer.measures_latency().then(Instant::now),
+            context,
+        }
+    }
+
+    /// Unwrap the value, reporting it received along with how long it was
+    /// queued, and link the current span to the sender's
+    pub(crate) fn open<O: ChannelObserver + ?Sized>(self, observer: &O) -> T {
+        observer.on_recv(self.enqueued_at.map(|enqueued_at| enqueued_at.elapsed()));
+        self.context.link();
+        self.value
+    }
+
+    /// Unwrap the value like [`open`](Self::open), along with the sender's span
+    #[cfg(all(feature = "tracing", feature = "mpsc"))]
+    pub(crate) fn open_traced<O: ChannelObserver + ?Sized>(self, observer: &O) -> (T, Span) {
+        observer.on_recv(self.enqueued_at.map(|enqueued_at| enqueued_at.elapsed()));
+        self.context.link();
+        (self.value, self.context.into_span())
+    }
+}
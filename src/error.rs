@@ -43,6 +43,17 @@ pub enum SendError<T> {
     Full(T),
 }
 
+impl<T> SendError<T> {
+    /// Map the value carried by this error
+    #[cfg(feature = "mpsc")]
+    pub(crate) fn map<U>(self, f: impl FnOnce(T) -> U) -> SendError<U> {
+        match self {
+            SendError::Closed(value) => SendError::Closed(f(value)),
+            SendError::Full(value) => SendError::Full(f(value)),
+        }
+    }
+}
+
 impl<T> From<TrySendError<T>> for SendError<T> {
     fn from(e: TrySendError<T>) -> Self {
         match e {
@@ -0,0 +1,233 @@
+use crate::json;
+use crate::metrics::default_registry;
+use crate::stats::live_channels;
+#[cfg(feature = "tasks")]
+use crate::topology::topology;
+use crate::trace::{debug, error};
+use prometheus::{Encoder, Registry, TextEncoder};
+use std::fmt::Write as _;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+
+/// Largest request head the server reads before giving up on a request
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+
+/// Default time a connection has to be answered before it is dropped
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default number of connections answered at once
+const DEFAULT_MAX_CONNECTIONS: usize = 64;
+
+/// A small HTTP server exposing channel metrics and live channel stats.
+///
+/// It answers `GET /metrics` with the metrics gathered from a registry in the
+/// Prometheus text format, and `GET /channels` with the [`live_channels`](crate::live_channels)
+/// as a JSON array. With the `tasks` feature, it also answers `GET /topology`
+/// and `GET /topology.dot` with the [`topology`](crate::topology) of the
+/// channels as JSON and DOT. Each connection serves one request, and is
+/// dropped if it is not answered within the [timeout](Self::timeout).
+///
+/// # Examples
+///
+/// ```rust
+/// use tokio_prometheus_metered_channel::MetricsServer;
+/// use prometheus::Registry;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let registry = Registry::new();
+///     let server = MetricsServer::bind("127.0.0.1:0")
+///         .await
+///         .unwrap()
+///         .registry(&registry);
+///     println!("serving metrics on {}", server.local_addr().unwrap());
+///
+///     let task = server.spawn();
+///     task.abort();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct MetricsServer {
+    listener: TcpListener,
+    registry: Registry,
+    timeout: Duration,
+    max_connections: usize,
+}
+
+impl MetricsServer {
+    /// Bind a server to `addr`, serving the metrics of the
+    /// [default registry](crate::default_registry)
+    pub async fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr).await?,
+            registry: default_registry(),
+            timeout: DEFAULT_TIMEOUT,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+        })
+    }
+
+    /// Serve the metrics of `registry` instead of the default registry
+    pub fn registry(mut self, registry: &Registry) -> Self {
+        self.registry = registry.clone();
+        self
+    }
+
+    /// Drop connections not answered within `timeout`, 10 seconds by default
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Answer at most `max` connections at once, 64 by default. Further
+    /// connections wait in the listen backlog.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is 0.
+    pub fn max_connections(mut self, max: usize) -> Self {
+        assert!(max > 0, "max connections must be greater than 0");
+        self.max_connections = max;
+        self
+    }
+
+    /// Get the address the server is bound to
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Answer requests until the returned future is dropped
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub async fn serve(self) {
+        let connections = Arc::new(Semaphore::new(self.max_connections));
+        loop {
+            // Never closed, so acquiring only waits for a connection to finish
+            let permit = match connections.clone().acquire_owned().await {
+                Ok(permit) => permit,
+                Err(_) => return,
+            };
+            let stream = match self.listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    // Usually out of file descriptors, which frees up over time
+                    error!(error = %e, "failed to accept metrics connection");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+            let registry = self.registry.clone();
+            let timeout = self.timeout;
+            tokio::spawn(async move {
+                match tokio::time::timeout(timeout, answer(stream, &registry)).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => {
+                        debug!(error = %e, "failed to answer metrics request");
+                    }
+                    Err(_) => {
+                        debug!("metrics request timed out");
+                    }
+                }
+                drop(permit);
+            });
+        }
+    }
+
+    /// Run the server in a background task
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(self.serve())
+    }
+}
+
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+async fn answer(mut stream: TcpStream, registry: &Registry) -> io::Result<()> {
+    let mut head = vec![0; MAX_REQUEST_HEAD];
+    let mut len = 0;
+    while !head[..len].windows(4).any(|window| window == b"\r\n\r\n") {
+        if len == head.len() {
+            return respond(&mut stream, "431 Request Header Fields Too Large", &[], b"").await;
+        }
+        match stream.read(&mut head[len..]).await? {
+            0 => return Ok(()),
+            read => len += read,
+        }
+    }
+
+    let line = head[..len]
+        .split(|&byte| byte == b'\r')
+        .next()
+        .unwrap_or_default();
+    let line = String::from_utf8_lossy(line);
+    let mut parts = line.split(' ');
+    let method = parts.next().unwrap_or_default();
+    let path = parts
+        .next()
+        .and_then(|target| target.split('?').next())
+        .unwrap_or_default();
+
+    match (method, path) {
+        ("GET", "/metrics") => {
+            let encoder = TextEncoder::new();
+            let mut body = Vec::new();
+            if let Err(e) = encoder.encode(&registry.gather(), &mut body) {
+                error!(error = %e, "failed to encode metrics");
+                return respond(&mut stream, "500 Internal Server Error", &[], b"").await;
+            }
+            let content_type = [("Content-Type", encoder.format_type())];
+            respond(&mut stream, "200 OK", &content_type, &body).await
+        }
+        ("GET", "/channels") => {
+            let body = json::channels(&live_channels());
+            respond(&mut stream, "200 OK", JSON, body.as_bytes()).await
+        }
+        #[cfg(feature = "tasks")]
+        ("GET", "/topology") => {
+            let body = topology().to_json();
+            respond(&mut stream, "200 OK", JSON, body.as_bytes()).await
+        }
+        #[cfg(feature = "tasks")]
+        ("GET", "/topology.dot") => {
+            let body = topology().to_dot();
+            let content_type = [("Content-Type", "text/vnd.graphviz")];
+            respond(&mut stream, "200 OK", &content_type, body.as_bytes()).await
+        }
+        #[cfg(feature = "tasks")]
+        (_, "/topology" | "/topology.dot") => {
+            respond(&mut stream, "405 Method Not Allowed", ALLOW_GET, b"").await
+        }
+        (_, "/metrics" | "/channels") => {
+            respond(&mut stream, "405 Method Not Allowed", ALLOW_GET, b"").await
+        }
+        _ => respond(&mut stream, "404 Not Found", &[], b"").await,
+    }
+}
+
+const JSON: &[(&str, &str)] = &[("Content-Type", "application/json")];
+
+/// Every path only supports `GET`, which a 405 response has to list
+const ALLOW_GET: &[(&str, &str)] = &[("Allow", "GET")];
+
+async fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+) -> io::Result<()> {
+    let mut head = format!(
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        status,
+        body.len()
+    );
+    for (name, value) in headers {
+        let _ = write!(head, "{}: {}\r\n", name, value);
+    }
+    head.push_str("\r\n");
+
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body).await?;
+    stream.shutdown().await
+}
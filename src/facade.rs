@@ -0,0 +1,217 @@
+use crate::observer::{ChannelObserver, RecvClock};
+#[cfg(feature = "tracing")]
+use crate::trace::ChannelTrace;
+use metrics::{Counter, Gauge, Histogram, Label};
+use std::fmt;
+use std::time::Duration;
+
+/// A channel observer reporting through the [`metrics`] facade.
+///
+/// Metric handles are resolved with the recorder installed when the observer
+/// is created, so install the recorder first. Metric names follow the same
+/// `{name}_{suffix}` scheme as [`ChannelMetrics`](crate::ChannelMetrics).
+///
+/// # Examples
+///
+/// ```rust
+/// use tokio_prometheus_metered_channel::{mpsc_channel_with_observer, MetricsObserver};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let observer = MetricsObserver::new("ingest").with_latency();
+///     let (tx, mut rx) = mpsc_channel_with_observer(10, observer);
+///
+///     tx.send(42).await.unwrap();
+///     assert_eq!(rx.recv().await, Some(42));
+/// }
+/// ```
+#[derive(Clone)]
+pub struct MetricsObserver {
+    name: String,
+    labels: Vec<Label>,
+    queue_size: Gauge,
+    total_messages: Counter,
+    delivered_total: Counter,
+    dropped_total: Counter,
+    evicted_total: Counter,
+    lagged_total: Counter,
+    capacity: Gauge,
+    send_wait: Histogram,
+    queue_bytes: Gauge,
+    bytes_total: Counter,
+    in_flight: Gauge,
+    processing_time: Histogram,
+    // The facade's counters only count integers, so seconds accumulate in gauges
+    idle_time: Gauge,
+    busy_time: Gauge,
+    drained_total: Counter,
+    dead_letters_total: Counter,
+    dead_letters_lost_total: Counter,
+    recv_clock: RecvClock,
+    #[cfg(feature = "tracing")]
+    trace: ChannelTrace,
+    queue_latency: Option<Histogram>,
+}
+
+impl MetricsObserver {
+    /// Create an observer for metrics prefixed with `name`
+    pub fn new(name: &str) -> Self {
+        Self::with_labels(name, Vec::new())
+    }
+
+    /// Create an observer for metrics prefixed with `name`, attaching `labels` to each of them
+    pub fn with_labels(name: &str, labels: Vec<Label>) -> Self {
+        Self {
+            name: name.to_string(),
+            queue_size: metrics::gauge!(format!("{name}_queue_size"), labels.clone()),
+            total_messages: metrics::counter!(format!("{name}_total_messages"), labels.clone()),
+            delivered_total: metrics::counter!(format!("{name}_delivered_total"), labels.clone()),
+            dropped_total: metrics::counter!(format!("{name}_dropped_total"), labels.clone()),
+            evicted_total: metrics::counter!(format!("{name}_evicted_total"), labels.clone()),
+            lagged_total: metrics::counter!(format!("{name}_lagged_total"), labels.clone()),
+            capacity: metrics::gauge!(format!("{name}_capacity"), labels.clone()),
+            send_wait: metrics::histogram!(format!("{name}_send_wait_seconds"), labels.clone()),
+            queue_bytes: metrics::gauge!(format!("{name}_queue_bytes"), labels.clone()),
+            bytes_total: metrics::counter!(format!("{name}_bytes_total"), labels.clone()),
+            in_flight: metrics::gauge!(format!("{name}_in_flight"), labels.clone()),
+            processing_time: metrics::histogram!(
+                format!("{name}_processing_seconds"),
+                labels.clone()
+            ),
+            idle_time: metrics::gauge!(format!("{name}_idle_seconds_total"), labels.clone()),
+            busy_time: metrics::gauge!(format!("{name}_busy_seconds_total"), labels.clone()),
+            drained_total: metrics::counter!(format!("{name}_drained_total"), labels.clone()),
+            dead_letters_total: metrics::counter!(
+                format!("{name}_dead_letters_total"),
+                labels.clone()
+            ),
+            dead_letters_lost_total: metrics::counter!(
+                format!("{name}_dead_letters_lost_total"),
+                labels.clone()
+            ),
+            recv_clock: RecvClock::default(),
+            #[cfg(feature = "tracing")]
+            trace: ChannelTrace::default(),
+            queue_latency: None,
+            labels,
+        }
+    }
+
+    /// Record the time values spend queued in a `{name}_queue_latency_seconds` histogram
+    pub fn with_latency(mut self) -> Self {
+        self.queue_latency = Some(metrics::histogram!(
+            format!("{}_queue_latency_seconds", self.name),
+            self.labels.clone()
+        ));
+        self
+    }
+
+    /// Set the tracing configuration of channels reporting to this observer
+    #[cfg(feature = "tracing")]
+    pub fn with_trace(mut self, trace: ChannelTrace) -> Self {
+        self.trace = trace;
+        self
+    }
+}
+
+impl fmt::Debug for MetricsObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MetricsObserver")
+            .field("name", &self.name)
+            .field("labels", &self.labels)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ChannelObserver for MetricsObserver {
+    fn on_send(&self) {
+        self.queue_size.increment(1.0);
+        self.total_messages.increment(1);
+    }
+
+    fn on_recv(&self, queued: Option<Duration>) {
+        self.queue_size.decrement(1.0);
+        if let (Some(histogram), Some(queued)) = (&self.queue_latency, queued) {
+            histogram.record(queued.as_secs_f64());
+        }
+        if let Some(busy) = self.recv_clock.received() {
+            self.busy_time.increment(busy.as_secs_f64());
+        }
+    }
+
+    fn on_deliver(&self) {
+        self.delivered_total.increment(1);
+    }
+
+    fn on_drop(&self, count: usize) {
+        self.queue_size.decrement(count as f64);
+        self.dropped_total.increment(count as u64);
+    }
+
+    fn on_evict(&self, queued: bool) {
+        self.evicted_total.increment(1);
+        if queued {
+            self.queue_size.decrement(1.0);
+        }
+    }
+
+    fn on_lag(&self, skipped: u64) {
+        self.lagged_total.increment(skipped);
+    }
+
+    fn on_wait(&self, waited: Duration) {
+        self.send_wait.record(waited.as_secs_f64());
+    }
+
+    fn on_capacity(&self, capacity: usize) {
+        self.capacity.set(capacity as f64);
+    }
+
+    fn on_send_bytes(&self, bytes: usize) {
+        self.queue_bytes.increment(bytes as f64);
+        self.bytes_total.increment(bytes as u64);
+    }
+
+    fn on_recv_bytes(&self, bytes: usize) {
+        self.queue_bytes.decrement(bytes as f64);
+    }
+
+    fn on_process_start(&self) {
+        self.in_flight.increment(1.0);
+    }
+
+    fn on_processed(&self, elapsed: Duration) {
+        self.in_flight.decrement(1.0);
+        self.processing_time.record(elapsed.as_secs_f64());
+    }
+
+    fn on_drain(&self, processed: usize, _abandoned: usize) {
+        self.drained_total.increment(processed as u64);
+    }
+
+    fn on_dead_letter(&self, captured: bool) {
+        if captured {
+            self.dead_letters_total.increment(1);
+        } else {
+            self.dead_letters_lost_total.increment(1);
+        }
+    }
+
+    fn on_idle(&self, waited: Duration) {
+        self.idle_time.increment(waited.as_secs_f64());
+        self.recv_clock.idled(waited);
+    }
+
+    fn measures_latency(&self) -> bool {
+        self.queue_latency.is_some()
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[cfg(feature = "tracing")]
+    fn trace(&self) -> ChannelTrace {
+        self.trace
+    }
+}
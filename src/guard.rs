@@ -0,0 +1,105 @@
+#[cfg(feature = "prometheus")]
+use crate::metrics::ChannelMetrics;
+use crate::observer::ChannelObserver;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use tokio::time::Instant;
+
+/// A received value being processed by the consumer.
+///
+/// The guard dereferences to the value. The value counts as in flight until
+/// the guard is dropped or [unwrapped](Self::into_inner), at which point the
+/// time since it was received is reported to the channel's observer as its
+/// processing time.
+///
+/// # Examples
+///
+/// ```rust
+/// use tokio_prometheus_metered_channel::{mpsc_channel, ChannelMetrics, ChannelMetricsOpts};
+/// use prometheus::Registry;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let registry = Registry::new();
+///     let opts = ChannelMetricsOpts::new("jobs").with_processing_histogram(vec![0.01, 0.1, 1.0]);
+///     let metrics = ChannelMetrics::with_opts(&opts, &registry).unwrap();
+///     let in_flight = metrics.in_flight.clone().unwrap();
+///
+///     let (tx, mut rx) = mpsc_channel(10, metrics);
+///     tx.send(String::from("job")).await.unwrap();
+///
+///     let job = rx.recv_guarded().await.unwrap();
+///     assert_eq!(job.len(), 3);
+///     assert_eq!(in_flight.get(), 1);
+///
+///     drop(job);
+///     assert_eq!(in_flight.get(), 0);
+/// }
+/// ```
+pub struct MessageGuard<
+    T,
+    #[cfg(feature = "prometheus")] O: ChannelObserver = ChannelMetrics,
+    #[cfg(not(feature = "prometheus"))] O: ChannelObserver,
+> {
+    value: Option<T>,
+    observer: O,
+    started: Instant,
+}
+
+impl<T, O: ChannelObserver> MessageGuard<T, O> {
+    /// Start processing `value`, reporting to `observer`
+    pub(crate) fn new(value: T, observer: O) -> Self {
+        observer.on_process_start();
+        Self {
+            value: Some(value),
+            observer,
+            started: Instant::now(),
+        }
+    }
+
+    /// Get the time since the value was received
+    pub fn elapsed(&self) -> std::time::Duration {
+        self.started.elapsed()
+    }
+
+    /// Take the value out of the guard, ending its processing
+    pub fn into_inner(mut self) -> T {
+        // Reports the processing time when the emptied guard is dropped
+        self.value
+            .take()
+            .expect("guard holds a value until dropped")
+    }
+}
+
+impl<T, O: ChannelObserver> Deref for MessageGuard<T, O> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+            .as_ref()
+            .expect("guard holds a value until dropped")
+    }
+}
+
+impl<T, O: ChannelObserver> DerefMut for MessageGuard<T, O> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value
+            .as_mut()
+            .expect("guard holds a value until dropped")
+    }
+}
+
+impl<T, O: ChannelObserver> Drop for MessageGuard<T, O> {
+    fn drop(&mut self) {
+        self.observer.on_processed(self.started.elapsed());
+    }
+}
+
+impl<T: fmt::Debug, O: ChannelObserver> fmt::Debug for MessageGuard<T, O> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MessageGuard")
+            .field("value", &self.value)
+            .field("started", &self.started)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,78 @@
+//! Hand-written JSON rendering of channel state, to avoid a serialization
+//! dependency for a few flat objects.
+
+use crate::stats::ChannelStats;
+use std::fmt::Write as _;
+
+/// Render channel stats as a JSON array of objects
+#[cfg(feature = "exporter")]
+pub(crate) fn channels(channels: &[ChannelStats]) -> String {
+    let mut json = String::from("[");
+    for (i, stats) in channels.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        json.push('{');
+        push_stats_fields(&mut json, stats);
+        json.push('}');
+    }
+    json.push(']');
+    json
+}
+
+/// Append the fields of `stats`, without the enclosing braces
+pub(crate) fn push_stats_fields(json: &mut String, stats: &ChannelStats) {
+    json.push_str("\"name\":");
+    push_str(json, &stats.name);
+    json.push_str(",\"labels\":{");
+    for (i, (name, value)) in stats.labels.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        push_str(json, name);
+        json.push(':');
+        push_str(json, value);
+    }
+    json.push_str("},\"kind\":");
+    match stats.kind {
+        Some(kind) => push_str(json, kind.as_str()),
+        None => json.push_str("null"),
+    }
+    json.push_str(",\"capacity\":");
+    match stats.capacity {
+        Some(capacity) => {
+            let _ = write!(json, "{}", capacity);
+        }
+        None => json.push_str("null"),
+    }
+    let _ = write!(
+        json,
+        ",\"len\":{},\"senders\":{},\"receivers\":{},\"sent\":{},\"received\":{},\"dropped\":{},\"closed\":{}",
+        stats.len,
+        stats.senders,
+        stats.receivers,
+        stats.sent,
+        stats.received,
+        stats.dropped,
+        stats.closed
+    );
+}
+
+/// Append `value` as a JSON string
+pub(crate) fn push_str(json: &mut String, value: &str) {
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
@@ -17,6 +17,10 @@
 //! - [`mpsc_channel`]: Multi-producer, single-consumer channel with metrics
 //! - [`broadcast_channel`]: Multi-producer, multi-consumer broadcast channel
 //! - [`watch_channel`]: Single-producer, multi-consumer watch channel
+//! - [`overflow_channel`]: Bounded multi-producer channel with a configurable [`OverflowPolicy`]
+//! - [`resizable_channel`]: Bounded multi-producer channel whose capacity can change at runtime
+//! - [`weighted_channel`]: Multi-producer channel metering the weight of its values,
+//!   optionally bounded by a byte budget
 //!
 //! # Example
 //!
@@ -42,6 +46,63 @@
 //! }
 //! ```
 //!
+//! Channels can also register their metrics with a [default registry](default_registry)
+//! in a single line, sharing them between channels created with the same name:
+//!
+//! ```rust
+//! use tokio_prometheus_metered_channel::mpsc_channel_named;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let (tx, mut rx) = mpsc_channel_named("ingest", 100).unwrap();
+//! tx.send(42).await.unwrap();
+//! assert_eq!(rx.recv().await, Some(42));
+//! # }
+//! ```
+//!
+//! # Metrics backends
+//!
+//! Channels report their events to a [`ChannelObserver`]. The backend is chosen
+//! by the observer passed when creating the channel, each behind a cargo feature:
+//!
+//! - `prometheus` (default): [`ChannelMetrics`], registered with a Prometheus registry
+//! - `metrics`: [`MetricsObserver`], reporting through the `metrics` facade
+//! - `opentelemetry`: [`OtelObserver`], reporting through OpenTelemetry metrics
+//!
+//! [`NoopObserver`] is always available and records nothing, so crates that do
+//! not want metrics can use the same channel types by passing it to the
+//! `*_with_observer` constructors. With the `prometheus` feature, the observer
+//! type parameter of the channel handles defaults to [`ChannelMetrics`] and the
+//! plain constructors take one; without it, the observer type must be named.
+//! Either way the same code compiles with any set of features enabled.
+//! [`CountingObserver`] counts events in memory for tests.
+//!
+//! # Cargo features
+//!
+//! Everything below is enabled by default except the `metrics` and
+//! `opentelemetry` backends, `tasks` and the `exporter`. Disable default features to
+//! pick only what is needed:
+//!
+//! - `mpsc`, `broadcast`, `watch`, `overflow`, `resizable`, `weighted`: the channel families
+//! - `controller`: the [`CapacityController`] for resizable channels, which needs
+//!   the tokio runtime to spawn its task
+//! - `watchdog`: the [`Watchdog`] flagging channels that hold values without
+//!   receive progress, which also spawns a task
+//! - `prometheus`: the Prometheus backend and the builder, named channels,
+//!   merged receivers and rate limited senders built on it
+//! - `process`: the Prometheus process collector
+//! - `tracing`: spans and events for channel operations, configured per
+//!   channel with [`ChannelTrace`]
+//! - `tasks`: [named tasks](track_task) tracked by the channels they use,
+//!   [`find_deadlocks`] following blocked sends between them, and the
+//!   [`topology`] of the dataflow graph they form
+//! - `exporter`: the [`MetricsServer`], serving metrics and live channel stats
+//!   over HTTP
+//!
+//! The library itself only needs the `sync` and `time` features of tokio, the
+//! `rt` feature for background tasks and task names, and the `net` and
+//! `io-util` features for the exporter.
+//!
 //! # Credits
 //!
 //! This implementation is inspired by and builds upon work from:
@@ -50,41 +111,219 @@
 
 #![warn(missing_docs)]
 
+#[cfg(feature = "prometheus")]
+mod builder;
+#[cfg(feature = "mpsc")]
 mod channel;
+#[cfg(feature = "controller")]
+mod controller;
+#[cfg(feature = "mpsc")]
+mod dead_letter;
+#[cfg(feature = "tasks")]
+mod deadlock;
+#[cfg(any(
+    feature = "mpsc",
+    feature = "broadcast",
+    feature = "overflow",
+    feature = "weighted"
+))]
+mod envelope;
 mod error;
+#[cfg(feature = "exporter")]
+mod exporter;
+#[cfg(feature = "metrics")]
+mod facade;
+#[cfg(any(
+    feature = "mpsc",
+    feature = "overflow",
+    feature = "resizable",
+    feature = "weighted"
+))]
+mod guard;
+#[cfg(any(feature = "exporter", feature = "tasks"))]
+mod json;
+#[cfg(all(feature = "prometheus", feature = "mpsc"))]
+mod merge;
+#[cfg(feature = "prometheus")]
 mod metrics;
+mod observer;
+#[cfg(feature = "opentelemetry")]
+mod otel;
+#[cfg(all(feature = "prometheus", feature = "mpsc"))]
+mod rate_limit;
+#[cfg(feature = "prometheus")]
+mod rates;
+#[cfg(feature = "prometheus")]
+mod stats;
+#[cfg(feature = "tasks")]
+mod tasks;
+#[cfg(feature = "tasks")]
+mod topology;
+mod trace;
+#[cfg(feature = "watchdog")]
+mod watchdog;
 
 /// Watch channel implementation with prometheus metrics integration.
 ///
 /// This channel type allows watching for value changes.
 /// New receivers see the latest value and all subsequent changes.
+#[cfg(feature = "watch")]
 pub mod watch;
 
 /// Broadcast channel implementation with prometheus metrics integration.
 ///
 /// This channel type allows sending messages to multiple receivers.
 /// Each receiver gets a copy of each message sent after they subscribed.
+#[cfg(feature = "broadcast")]
 pub mod broadcast;
 
+/// Bounded channel implementation with a configurable overflow policy.
+///
+/// This channel type can block, reject, or evict values when full.
+/// Evictions are counted so lossy channels stay observable.
+#[cfg(feature = "overflow")]
+pub mod overflow;
+
+/// Bounded channel implementation whose capacity can be changed at runtime.
+///
+/// Capacity is enforced by a semaphore over an unbounded queue, so growing
+/// the channel immediately wakes waiting senders.
+#[cfg(feature = "resizable")]
+pub mod resizable;
+
+/// Channel implementation metering the weight of its values.
+///
+/// Values are weighed once when sent with a [`Weigh`](weighted::Weigh)
+/// implementation, usually a closure, and the channel is bounded either by a
+/// number of values or by a byte budget. [`ChannelMetrics`] created
+/// [with bytes](ChannelMetricsOpts::with_bytes) record the weights in
+/// `{name}_queue_bytes` and `{name}_bytes_total`.
+#[cfg(feature = "weighted")]
+pub mod weighted;
+
 #[cfg(test)]
 mod tests;
 
 // Re-export specific items from channel module
+#[cfg(all(feature = "prometheus", feature = "mpsc"))]
 pub use channel::{
-    channel as mpsc_channel, channel_with_total as mpsc_channel_with_total,
-    Receiver as MpscReceiver, Sender as MpscSender, WithPermit,
+    channel as mpsc_channel, channel_named as mpsc_channel_named,
+    channel_with_total as mpsc_channel_with_total,
+};
+#[cfg(feature = "mpsc")]
+pub use channel::{
+    channel_with_observer as mpsc_channel_with_observer, DrainReport, Receiver as MpscReceiver,
+    Sender as MpscSender, WithPermit,
 };
 
-pub use broadcast::channel as broadcast_channel;
+#[cfg(feature = "broadcast")]
+pub use broadcast::channel_with_observer as broadcast_channel_with_observer;
+#[cfg(all(feature = "prometheus", feature = "broadcast"))]
+pub use broadcast::{channel as broadcast_channel, channel_named as broadcast_channel_named};
+#[cfg(feature = "prometheus")]
+pub use builder::MeteredChannelBuilder;
+#[cfg(feature = "controller")]
+pub use controller::{CapacityController, ControllerConfig, ControllerTarget};
+#[cfg(feature = "mpsc")]
+pub use dead_letter::{
+    channel_with_dead_letters as mpsc_channel_with_dead_letters, DeadLetter, DeadLetterReason,
+    DeadLetterReceiver, DeadLetterSender,
+};
+#[cfg(feature = "tasks")]
+pub use deadlock::{find_deadlocks, BlockedSend, Deadlock};
 pub use error::SendError;
-pub use metrics::ChannelMetrics;
-pub use watch::channel as watch_channel;
+#[cfg(feature = "exporter")]
+pub use exporter::MetricsServer;
+#[cfg(feature = "metrics")]
+pub use facade::MetricsObserver;
+#[cfg(any(
+    feature = "mpsc",
+    feature = "overflow",
+    feature = "resizable",
+    feature = "weighted"
+))]
+pub use guard::MessageGuard;
+#[cfg(all(feature = "prometheus", feature = "mpsc"))]
+pub use merge::MergedReceiver;
+#[cfg(feature = "prometheus")]
+pub use metrics::{
+    default_registry, set_default_registry, ChannelMetrics, ChannelMetricsOpts, ControllerMetrics,
+    MergeMetrics, ThrottleMetrics,
+};
+pub use observer::{ChannelKind, ChannelObserver, CountingObserver, HandleKind, NoopObserver};
+#[cfg(feature = "opentelemetry")]
+pub use otel::OtelObserver;
+#[cfg(all(feature = "prometheus", feature = "overflow"))]
+pub use overflow::channel as overflow_channel;
+#[cfg(feature = "overflow")]
+pub use overflow::{channel_with_observer as overflow_channel_with_observer, OverflowPolicy};
+#[cfg(all(feature = "prometheus", feature = "mpsc"))]
+pub use rate_limit::RateLimitedSender;
+#[cfg(feature = "prometheus")]
+pub use rates::{Rates, Throughput};
+#[cfg(all(feature = "prometheus", feature = "resizable"))]
+pub use resizable::channel as resizable_channel;
+#[cfg(feature = "resizable")]
+pub use resizable::channel_with_observer as resizable_channel_with_observer;
+#[cfg(feature = "prometheus")]
+pub use stats::{live_channel, live_channels, ChannelStats};
+#[cfg(feature = "tasks")]
+pub use tasks::track_task;
+#[cfg(feature = "tasks")]
+pub use topology::{topology, ChannelLinks, Topology};
+#[cfg(feature = "tracing")]
+pub use trace::ChannelTrace;
+#[cfg(feature = "watch")]
+pub use watch::channel_with_observer as watch_channel_with_observer;
+#[cfg(all(feature = "prometheus", feature = "watch"))]
+pub use watch::{channel as watch_channel, channel_named as watch_channel_named};
+#[cfg(feature = "watchdog")]
+pub use watchdog::Watchdog;
+#[cfg(all(feature = "prometheus", feature = "weighted"))]
+pub use weighted::channel as weighted_channel;
+#[cfg(feature = "weighted")]
+pub use weighted::channel_with_observer as weighted_channel_with_observer;
 
 /// Re-exports of commonly used types
 pub mod prelude {
+    pub use crate::{ChannelObserver, CountingObserver, NoopObserver, SendError};
+
+    #[cfg(any(
+        feature = "mpsc",
+        feature = "overflow",
+        feature = "resizable",
+        feature = "weighted"
+    ))]
+    pub use crate::MessageGuard;
+
+    #[cfg(feature = "broadcast")]
+    pub use crate::broadcast_channel_with_observer;
+    #[cfg(feature = "resizable")]
+    pub use crate::resizable_channel_with_observer;
+    #[cfg(feature = "watch")]
+    pub use crate::watch_channel_with_observer;
+    #[cfg(feature = "mpsc")]
+    pub use crate::{mpsc_channel_with_observer, MpscReceiver, MpscSender, WithPermit};
+    #[cfg(feature = "overflow")]
+    pub use crate::{overflow_channel_with_observer, OverflowPolicy};
+    #[cfg(feature = "weighted")]
+    pub use crate::{weighted::Limit, weighted_channel_with_observer};
+
+    #[cfg(all(feature = "prometheus", feature = "overflow"))]
+    pub use crate::overflow_channel;
+    #[cfg(all(feature = "prometheus", feature = "resizable"))]
+    pub use crate::resizable_channel;
+    #[cfg(all(feature = "prometheus", feature = "weighted"))]
+    pub use crate::weighted_channel;
+    #[cfg(all(feature = "prometheus", feature = "broadcast"))]
+    pub use crate::{broadcast_channel, broadcast_channel_named};
+    #[cfg(all(feature = "prometheus", feature = "mpsc"))]
     pub use crate::{
-        broadcast::channel as broadcast_channel, mpsc_channel, mpsc_channel_with_total,
-        watch::channel as watch_channel, ChannelMetrics, MpscReceiver, MpscSender, SendError,
-        WithPermit,
+        mpsc_channel, mpsc_channel_named, mpsc_channel_with_total, MergeMetrics, MergedReceiver,
+        RateLimitedSender, ThrottleMetrics,
     };
+    #[cfg(all(feature = "prometheus", feature = "watch"))]
+    pub use crate::{watch_channel, watch_channel_named};
+    #[cfg(feature = "prometheus")]
+    pub use crate::{ChannelMetrics, ChannelMetricsOpts, MeteredChannelBuilder};
 }
@@ -0,0 +1,164 @@
+use crate::channel::Receiver;
+use crate::metrics::{ChannelMetrics, MergeMetrics};
+use crate::observer::ChannelObserver;
+use crate::trace::debug;
+use futures::Stream;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A receiver merging several metered mpsc receivers.
+///
+/// Sources are polled in weighted round-robin order: a source with weight `n`
+/// may yield up to `n` consecutive items before the next source gets a turn.
+/// Each item is returned together with the index of the source it came from,
+/// and per-source receive counters are exported so starvation is visible.
+///
+/// # Examples
+///
+/// ```rust
+/// use tokio_prometheus_metered_channel::{mpsc_channel, ChannelMetrics, MergeMetrics, MergedReceiver};
+/// use prometheus::Registry;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let registry = Registry::new();
+///     let (fast_tx, fast_rx) =
+///         mpsc_channel(10, ChannelMetrics::new_basic("fast", "fast", &registry).unwrap());
+///     let (slow_tx, slow_rx) =
+///         mpsc_channel(10, ChannelMetrics::new_basic("slow", "slow", &registry).unwrap());
+///
+///     let mut merged = MergedReceiver::new(MergeMetrics::new("merged", "merged", &registry).unwrap());
+///     merged.push_weighted("fast", fast_rx, 2);
+///     merged.push("slow", slow_rx);
+///
+///     fast_tx.send("a").await.unwrap();
+///     slow_tx.send("b").await.unwrap();
+///
+///     let (source, value) = merged.recv().await.unwrap();
+///     assert_eq!((merged.source_name(source), value), (Some("fast"), "a"));
+/// }
+/// ```
+#[derive(Debug)]
+pub struct MergedReceiver<T, O: ChannelObserver = ChannelMetrics> {
+    sources: Vec<Source<T, O>>,
+    cursor: usize,
+    credit: usize,
+    metrics: MergeMetrics,
+}
+
+#[derive(Debug)]
+struct Source<T, O: ChannelObserver> {
+    name: String,
+    receiver: Receiver<T, O>,
+    weight: usize,
+    received: prometheus::IntCounter,
+    closed: bool,
+}
+
+impl<T, O: ChannelObserver> MergedReceiver<T, O> {
+    /// Creates an empty merged receiver reporting to the given metrics
+    pub fn new(metrics: MergeMetrics) -> Self {
+        Self {
+            sources: Vec::new(),
+            cursor: 0,
+            credit: 0,
+            metrics,
+        }
+    }
+
+    /// Add a source with weight 1, returning its index
+    pub fn push(&mut self, name: impl Into<String>, receiver: Receiver<T, O>) -> usize {
+        self.push_weighted(name, receiver, 1)
+    }
+
+    /// Add a source with the given round-robin weight, returning its index
+    ///
+    /// # Panics
+    ///
+    /// Panics if `weight` is 0.
+    pub fn push_weighted(
+        &mut self,
+        name: impl Into<String>,
+        receiver: Receiver<T, O>,
+        weight: usize,
+    ) -> usize {
+        assert!(weight > 0, "source weight must be greater than 0");
+
+        let name = name.into();
+        let received = self.metrics.received.with_label_values(&[&name]);
+        self.sources.push(Source {
+            name,
+            receiver,
+            weight,
+            received,
+            closed: false,
+        });
+        self.sources.len() - 1
+    }
+
+    /// Get the name of the source at `index`
+    pub fn source_name(&self, index: usize) -> Option<&str> {
+        self.sources.get(index).map(|source| source.name.as_str())
+    }
+
+    /// Get the number of sources, including closed ones
+    pub fn source_count(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Receive the next value and the index of its source.
+    ///
+    /// Returns `None` once every source is closed and drained.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self), level = "debug"))]
+    pub async fn recv(&mut self) -> Option<(usize, T)> {
+        debug!("waiting to receive value from merged sources");
+        std::future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Poll to receive the next value and the index of its source
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<(usize, T)>> {
+        for _ in 0..self.sources.len() {
+            let index = self.cursor;
+            let source = &mut self.sources[index];
+
+            if !source.closed {
+                match source.receiver.poll_recv(cx) {
+                    Poll::Ready(Some(value)) => {
+                        source.received.inc();
+                        self.credit += 1;
+                        if self.credit >= source.weight {
+                            self.advance();
+                        }
+                        return Poll::Ready(Some((index, value)));
+                    }
+                    Poll::Ready(None) => {
+                        debug!(source = %source.name, "merged source closed");
+                        source.closed = true;
+                    }
+                    Poll::Pending => {}
+                }
+            }
+
+            self.advance();
+        }
+
+        if self.sources.iter().all(|source| source.closed) {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+
+    fn advance(&mut self) {
+        self.cursor = (self.cursor + 1) % self.sources.len();
+        self.credit = 0;
+    }
+}
+
+impl<T, O: ChannelObserver + Unpin> Stream for MergedReceiver<T, O> {
+    type Item = (usize, T);
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
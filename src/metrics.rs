@@ -1,4 +1,15 @@
-use prometheus::{IntCounter, IntGauge, Opts, Registry};
+use crate::observer::{ChannelKind, ChannelObserver, HandleKind, RecvClock};
+use crate::rates::{RateMeter, Throughput};
+use crate::stats::{ChannelStats, Tracker};
+#[cfg(feature = "tracing")]
+use crate::trace::ChannelTrace;
+use prometheus::core::Collector;
+use prometheus::{
+    Counter, Gauge, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::Duration;
 
 /// Metrics for channel monitoring
 #[derive(Clone, Debug)]
@@ -7,27 +18,273 @@ pub struct ChannelMetrics {
     pub queue_size: IntGauge,
     /// Total number of items that have gone through the channel
     pub total_messages: Option<IntCounter>,
+    /// Total number of items discarded by a lossy overflow policy
+    pub evicted_total: Option<IntCounter>,
+    /// Current maximum number of items the channel can hold
+    pub capacity: Option<IntGauge>,
+    /// Time items spent queued before being received, in seconds
+    pub queue_latency: Option<Histogram>,
+    /// Current total weight of the items in a [weighted](crate::weighted) channel
+    pub queue_bytes: Option<IntGauge>,
+    /// Total weight of the items that have gone through a [weighted](crate::weighted) channel
+    pub bytes_total: Option<IntCounter>,
+    /// Number of items received through a guard and still being processed
+    pub in_flight: Option<IntGauge>,
+    /// Time consumers spent processing items received through a guard, in seconds
+    pub processing_time: Option<Histogram>,
+    /// Total time receivers spent waiting on an empty channel, in seconds
+    pub idle_time: Option<Counter>,
+    /// Total time receivers spent between receives, not waiting on the channel, in seconds
+    pub busy_time: Option<Counter>,
+    /// Set to 1 by the [`Watchdog`](crate::Watchdog) while the channel holds values
+    /// without receive progress
+    pub stalled: Option<IntGauge>,
+    /// Total number of items handed to consumers by [drains](crate::MpscReceiver::drain)
+    pub drained_total: Option<IntCounter>,
+    /// Total number of queued items discarded without being received
+    pub dropped_total: Option<IntCounter>,
+    /// Total number of undelivered items sent to the [dead-letter channel](crate::DeadLetterSender)
+    pub dead_letters_total: Option<IntCounter>,
+    /// Total number of undelivered items lost because the dead-letter channel was full
+    pub dead_letters_lost_total: Option<IntCounter>,
+    /// Clock of the receiver holding this clone, feeding the idle and busy counters
+    recv_clock: RecvClock,
+    /// In-process send and receive rates, shared by every clone
+    rates: Option<Arc<RateMeter>>,
+    /// Live state of the channel listed by [`live_channels`](crate::live_channels)
+    tracker: Option<Arc<Tracker>>,
+    /// Fully qualified prefix of the metric names, naming the channel in spans
+    name: Arc<str>,
+    /// Tracing configuration of the channel's spans
+    #[cfg(feature = "tracing")]
+    trace: ChannelTrace,
+    /// Shared registration keeping the metrics registered, set by [`ChannelMetrics::get_or_register`]
+    _registration: Option<Arc<Registration>>,
+}
+
+/// Options controlling which channel metrics are created and how they are named.
+///
+/// Metric names are built from the namespace, subsystem, name and a per-metric
+/// suffix joined with underscores, skipping empty parts. By default help texts
+/// splice [`help`](Self::help) into a fixed template; [`metric_help`](Self::metric_help)
+/// replaces the help text of a single metric.
+///
+/// # Examples
+///
+/// ```rust
+/// use tokio_prometheus_metered_channel::{ChannelMetrics, ChannelMetricsOpts};
+/// use prometheus::Registry;
+///
+/// let registry = Registry::new();
+/// let opts = ChannelMetricsOpts::new("")
+///     .namespace("svc")
+///     .subsystem("channels")
+///     .const_label("pipeline", "ingest")
+///     .metric_help("queue_size", "Items waiting in the ingest pipeline")
+///     .with_total();
+/// let metrics = ChannelMetrics::with_opts(&opts, &registry).unwrap();
+///
+/// let families = registry.gather();
+/// assert_eq!(families[0].get_name(), "svc_channels_queue_size");
+/// assert_eq!(families[0].get_help(), "Items waiting in the ingest pipeline");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ChannelMetricsOpts {
+    /// Namespace prepended to every metric name
+    pub namespace: String,
+    /// Subsystem inserted between the namespace and the name
+    pub subsystem: String,
+    /// Name of the channel, prepended to every metric suffix
+    pub name: String,
+    /// Description of the channel spliced into the default help texts,
+    /// defaulting to the metric name prefix
+    pub help: String,
+    /// Constant labels attached to every metric
+    pub const_labels: HashMap<String, String>,
+    /// Help texts replacing the defaults, keyed by metric suffix
+    /// (`queue_size`, `total_messages`, `evicted_total`, `capacity`, `queue_latency_seconds`,
+    /// `queue_bytes`, `bytes_total`, `in_flight`, `processing_seconds`,
+    /// `idle_seconds_total`, `busy_seconds_total`, `stalled`, `drained_total`,
+    /// `dropped_total`, `dead_letters_total`, `dead_letters_lost_total`)
+    pub help_overrides: HashMap<String, String>,
+    /// Create the `total_messages` counter
+    pub total_messages: bool,
+    /// Create the `evicted_total` counter
+    pub evicted_total: bool,
+    /// Create the `capacity` gauge
+    pub capacity: bool,
+    /// Create the `queue_latency_seconds` histogram with these buckets
+    pub latency_buckets: Option<Vec<f64>>,
+    /// Create the `queue_bytes` gauge and `bytes_total` counter
+    pub bytes: bool,
+    /// Create the `in_flight` gauge and the `processing_seconds` histogram with these buckets
+    pub processing_buckets: Option<Vec<f64>>,
+    /// Create the `idle_seconds_total` and `busy_seconds_total` counters
+    pub utilization: bool,
+    /// Track send and receive rates in-process, read with [`ChannelMetrics::throughput`]
+    pub rates: bool,
+    /// List the channel in [`live_channels`](crate::live_channels)
+    pub stats: bool,
+    /// Create the `stalled` gauge, set once the channel holds values without
+    /// receive progress for this long
+    pub stall_threshold: Option<Duration>,
+    /// Create the `drained_total` and `dropped_total` counters
+    pub drain: bool,
+    /// Create the `dead_letters_total` and `dead_letters_lost_total` counters
+    pub dead_letters: bool,
+}
+
+impl ChannelMetricsOpts {
+    /// Create options for a channel with the given name, creating only the queue size gauge
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Set the namespace
+    pub fn namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = namespace.into();
+        self
+    }
+
+    /// Set the subsystem
+    pub fn subsystem(mut self, subsystem: impl Into<String>) -> Self {
+        self.subsystem = subsystem.into();
+        self
+    }
+
+    /// Set the description spliced into the default help texts
+    pub fn help(mut self, help: impl Into<String>) -> Self {
+        self.help = help.into();
+        self
+    }
+
+    /// Add a constant label attached to every metric
+    pub fn const_label(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.const_labels.insert(name.into(), value.into());
+        self
+    }
+
+    /// Replace the help text of the metric with the given suffix
+    pub fn metric_help(mut self, suffix: impl Into<String>, help: impl Into<String>) -> Self {
+        self.help_overrides.insert(suffix.into(), help.into());
+        self
+    }
+
+    /// Create the `total_messages` counter
+    pub fn with_total(mut self) -> Self {
+        self.total_messages = true;
+        self
+    }
+
+    /// Create the `evicted_total` counter
+    pub fn with_evicted(mut self) -> Self {
+        self.evicted_total = true;
+        self
+    }
+
+    /// Create the `capacity` gauge
+    pub fn with_capacity(mut self) -> Self {
+        self.capacity = true;
+        self
+    }
+
+    /// Create the `queue_latency_seconds` histogram with the given buckets
+    pub fn with_latency_histogram(mut self, buckets: Vec<f64>) -> Self {
+        self.latency_buckets = Some(buckets);
+        self
+    }
+
+    /// Create the `queue_bytes` gauge and `bytes_total` counter
+    pub fn with_bytes(mut self) -> Self {
+        self.bytes = true;
+        self
+    }
+
+    /// Create the `in_flight` gauge and the `processing_seconds` histogram with the given buckets
+    pub fn with_processing_histogram(mut self, buckets: Vec<f64>) -> Self {
+        self.processing_buckets = Some(buckets);
+        self
+    }
+
+    /// Create the `idle_seconds_total` and `busy_seconds_total` counters
+    pub fn with_utilization(mut self) -> Self {
+        self.utilization = true;
+        self
+    }
+
+    /// Track send and receive rates in-process, read with [`ChannelMetrics::throughput`]
+    pub fn with_rates(mut self) -> Self {
+        self.rates = true;
+        self
+    }
+
+    /// List the channel in [`live_channels`](crate::live_channels), keyed by its
+    /// [prefix](Self::prefix)
+    pub fn with_stats(mut self) -> Self {
+        self.stats = true;
+        self
+    }
+
+    /// Create the `stalled` gauge, which the [`Watchdog`](crate::Watchdog) sets
+    /// once the channel holds values without receive progress for `threshold`.
+    ///
+    /// Implies [`with_stats`](Self::with_stats).
+    pub fn with_stall_threshold(mut self, threshold: Duration) -> Self {
+        self.stall_threshold = Some(threshold);
+        self
+    }
+
+    /// Create the `drained_total` and `dropped_total` counters, counting the
+    /// items processed and abandoned when the channel shuts down
+    pub fn with_drain(mut self) -> Self {
+        self.drain = true;
+        self
+    }
+
+    /// Create the `dead_letters_total` and `dead_letters_lost_total` counters,
+    /// counting the undelivered items sent to a [dead-letter channel](crate::DeadLetterSender)
+    pub fn with_dead_letters(mut self) -> Self {
+        self.dead_letters = true;
+        self
+    }
+
+    /// The fully qualified prefix of the metric names, e.g. `svc_channels_ingest`
+    pub fn prefix(&self) -> String {
+        [&self.namespace, &self.subsystem, &self.name]
+            .iter()
+            .filter(|part| !part.is_empty())
+            .map(|part| part.as_str())
+            .collect::<Vec<_>>()
+            .join("_")
+    }
+
+    fn opts(&self, suffix: &str, default_help: impl FnOnce(&str) -> String) -> Opts {
+        let name = if self.name.is_empty() {
+            suffix.to_string()
+        } else {
+            format!("{}_{}", self.name, suffix)
+        };
+        let help = match self.help_overrides.get(suffix) {
+            Some(help) => help.clone(),
+            None if self.help.is_empty() => default_help(&self.prefix()),
+            None => default_help(&self.help),
+        };
+
+        Opts::new(name, help)
+            .namespace(self.namespace.clone())
+            .subsystem(self.subsystem.clone())
+            .const_labels(self.const_labels.clone())
+    }
 }
 
 impl ChannelMetrics {
     /// Create new channel metrics and register them with Prometheus
     pub fn new(name: &str, help: &str, registry: &Registry) -> Result<Self, prometheus::Error> {
-        let queue_size = IntGauge::with_opts(Opts::new(
-            format!("{}_queue_size", name),
-            format!("Current number of items in {} channel", help),
-        ))?;
-        registry.register(Box::new(queue_size.clone()))?;
-
-        let total_messages = IntCounter::with_opts(Opts::new(
-            format!("{}_total_messages", name),
-            format!("Total number of messages processed by {} channel", help),
-        ))?;
-        registry.register(Box::new(total_messages.clone()))?;
-
-        Ok(Self {
-            queue_size,
-            total_messages: Some(total_messages),
-        })
+        let opts = ChannelMetricsOpts::new(name).help(help).with_total();
+        Self::with_opts(&opts, registry)
     }
 
     /// Create metrics without total message counter
@@ -36,15 +293,925 @@ impl ChannelMetrics {
         help: &str,
         registry: &Registry,
     ) -> Result<Self, prometheus::Error> {
-        let queue_size = IntGauge::with_opts(Opts::new(
-            format!("{}_queue_size", name),
-            format!("Current number of items in {} channel", help),
-        ))?;
-        registry.register(Box::new(queue_size.clone()))?;
+        Self::with_opts(&ChannelMetricsOpts::new(name).help(help), registry)
+    }
+
+    /// Create metrics with total message and eviction counters, for channels
+    /// using a lossy [`OverflowPolicy`](crate::overflow::OverflowPolicy)
+    pub fn new_with_evicted(
+        name: &str,
+        help: &str,
+        registry: &Registry,
+    ) -> Result<Self, prometheus::Error> {
+        let opts = ChannelMetricsOpts::new(name)
+            .help(help)
+            .with_total()
+            .with_evicted();
+        Self::with_opts(&opts, registry)
+    }
+
+    /// Create metrics with total message counter and a capacity gauge, for
+    /// [resizable](crate::resizable) channels
+    pub fn new_with_capacity(
+        name: &str,
+        help: &str,
+        registry: &Registry,
+    ) -> Result<Self, prometheus::Error> {
+        let opts = ChannelMetricsOpts::new(name)
+            .help(help)
+            .with_total()
+            .with_capacity();
+        Self::with_opts(&opts, registry)
+    }
+
+    /// Wrap existing collectors, leaving their registration to the caller
+    #[cfg(feature = "mpsc")]
+    pub(crate) fn from_collectors(
+        queue_size: IntGauge,
+        total_messages: Option<IntCounter>,
+    ) -> Self {
+        let name = queue_size
+            .desc()
+            .first()
+            .map(|desc| desc.fq_name.trim_end_matches("_queue_size"))
+            .unwrap_or_default()
+            .into();
+        Self {
+            queue_size,
+            total_messages,
+            evicted_total: None,
+            capacity: None,
+            queue_latency: None,
+            queue_bytes: None,
+            bytes_total: None,
+            in_flight: None,
+            processing_time: None,
+            idle_time: None,
+            busy_time: None,
+            stalled: None,
+            drained_total: None,
+            dropped_total: None,
+            dead_letters_total: None,
+            dead_letters_lost_total: None,
+            recv_clock: RecvClock::default(),
+            rates: None,
+            tracker: None,
+            name,
+            #[cfg(feature = "tracing")]
+            trace: ChannelTrace::default(),
+            _registration: None,
+        }
+    }
+
+    /// Create the metrics described by `opts` and register them with Prometheus
+    pub fn with_opts(
+        opts: &ChannelMetricsOpts,
+        registry: &Registry,
+    ) -> Result<Self, prometheus::Error> {
+        let mut metrics = Self::create(opts)?;
+        register_all(registry, || metrics.collectors())?;
+        metrics.track(opts);
+        Ok(metrics)
+    }
+
+    /// Create the metrics described by `opts`, or share the ones already
+    /// created by a previous call with the same names and const labels.
+    ///
+    /// Unlike [`with_opts`](Self::with_opts), registering the same metrics twice
+    /// is not an error, so components restarted in-process or instantiated per
+    /// connection can share one metric set. The metrics are unregistered once
+    /// the last clone of every `ChannelMetrics` returned for them is dropped,
+    /// including the clones held by channels.
+    ///
+    /// Fails with [`prometheus::Error::AlreadyReg`] if the names are taken by
+    /// collectors not created through this function.
+    pub fn get_or_register(
+        opts: &ChannelMetricsOpts,
+        registry: &Registry,
+    ) -> Result<Self, prometheus::Error> {
+        let key = registration_key(opts);
+        let mut cache = registrations();
+
+        if let Some(index) = cache.iter().position(|entry| entry.key == key) {
+            match cache[index].registration.upgrade() {
+                Some(registration) => {
+                    let entry = &mut cache[index];
+                    entry.add_missing(opts)?;
+                    entry.register_into(registry)?;
+                    return Ok(Self {
+                        _registration: Some(registration),
+                        ..entry.metrics.clone()
+                    });
+                }
+                // The last user is being dropped concurrently
+                None => cache.swap_remove(index).unregister(),
+            }
+        }
+
+        let mut metrics = Self::create(opts)?;
+        register_all(registry, || metrics.collectors())?;
+        metrics.track(opts);
+
+        let registration = Arc::new(Registration { key: key.clone() });
+        cache.push(CachedRegistration {
+            key,
+            metrics: metrics.clone(),
+            registries: vec![registry.clone()],
+            registration: Arc::downgrade(&registration),
+        });
+
+        Ok(Self {
+            _registration: Some(registration),
+            ..metrics
+        })
+    }
+
+    /// List the channel with [`live_channels`](crate::live_channels) and the
+    /// watchdog as requested by `opts`, keeping the tracker it already has
+    fn track(&mut self, opts: &ChannelMetricsOpts) {
+        if self.tracker.is_none() && (opts.stats || opts.stall_threshold.is_some()) {
+            self.tracker = Some(Tracker::register(
+                opts.prefix().into(),
+                opts.const_labels.clone().into_iter().collect(),
+            ));
+        }
+        if let Some(ref tracker) = self.tracker {
+            if let (Some(threshold), Some(gauge)) = (opts.stall_threshold, &self.stalled) {
+                tracker.check_stalls(threshold, gauge.clone());
+            }
+            #[cfg(feature = "tasks")]
+            if let Some(ref rates) = self.rates {
+                tracker.measure_rates(Arc::clone(rates));
+            }
+        }
+    }
+
+    fn create(opts: &ChannelMetricsOpts) -> Result<Self, prometheus::Error> {
+        let queue_size = IntGauge::with_opts(opts.opts("queue_size", |help| {
+            format!("Current number of items in {} channel", help)
+        }))?;
+        let total_messages = opts
+            .total_messages
+            .then(|| {
+                IntCounter::with_opts(opts.opts("total_messages", |help| {
+                    format!("Total number of messages processed by {} channel", help)
+                }))
+            })
+            .transpose()?;
+        let evicted_total = opts
+            .evicted_total
+            .then(|| {
+                IntCounter::with_opts(opts.opts("evicted_total", |help| {
+                    format!("Total number of messages evicted from {} channel", help)
+                }))
+            })
+            .transpose()?;
+        let capacity = opts
+            .capacity
+            .then(|| {
+                IntGauge::with_opts(opts.opts("capacity", |help| {
+                    format!("Current capacity of {} channel", help)
+                }))
+            })
+            .transpose()?;
+        let queue_latency = opts
+            .latency_buckets
+            .as_ref()
+            .map(|buckets| {
+                let histogram_opts = opts.opts("queue_latency_seconds", |help| {
+                    format!("Time items spent queued in {} channel", help)
+                });
+                Histogram::with_opts(HistogramOpts::from(histogram_opts).buckets(buckets.clone()))
+            })
+            .transpose()?;
+        let queue_bytes = opts
+            .bytes
+            .then(|| {
+                IntGauge::with_opts(opts.opts("queue_bytes", |help| {
+                    format!("Current number of bytes queued in {} channel", help)
+                }))
+            })
+            .transpose()?;
+        let bytes_total = opts
+            .bytes
+            .then(|| {
+                IntCounter::with_opts(opts.opts("bytes_total", |help| {
+                    format!("Total number of bytes sent through {} channel", help)
+                }))
+            })
+            .transpose()?;
+        let in_flight = opts
+            .processing_buckets
+            .as_ref()
+            .map(|_| {
+                IntGauge::with_opts(opts.opts("in_flight", |help| {
+                    format!(
+                        "Current number of items being processed from {} channel",
+                        help
+                    )
+                }))
+            })
+            .transpose()?;
+        let processing_time = opts
+            .processing_buckets
+            .as_ref()
+            .map(|buckets| {
+                let histogram_opts = opts.opts("processing_seconds", |help| {
+                    format!("Time spent processing items from {} channel", help)
+                });
+                Histogram::with_opts(HistogramOpts::from(histogram_opts).buckets(buckets.clone()))
+            })
+            .transpose()?;
+        let idle_time = opts
+            .utilization
+            .then(|| {
+                Counter::with_opts(opts.opts("idle_seconds_total", |help| {
+                    format!(
+                        "Total time receivers spent waiting on empty {} channel",
+                        help
+                    )
+                }))
+            })
+            .transpose()?;
+        let busy_time = opts
+            .utilization
+            .then(|| {
+                Counter::with_opts(opts.opts("busy_seconds_total", |help| {
+                    format!(
+                        "Total time receivers spent between receives from {} channel",
+                        help
+                    )
+                }))
+            })
+            .transpose()?;
+        let stalled = opts
+            .stall_threshold
+            .map(|_| {
+                IntGauge::with_opts(opts.opts("stalled", |help| {
+                    format!(
+                        "Whether {} channel holds values without receive progress",
+                        help
+                    )
+                }))
+            })
+            .transpose()?;
+        let drained_total = opts
+            .drain
+            .then(|| {
+                IntCounter::with_opts(opts.opts("drained_total", |help| {
+                    format!(
+                        "Total number of items handed to consumers by drains of {} channel",
+                        help
+                    )
+                }))
+            })
+            .transpose()?;
+        let dropped_total = opts
+            .drain
+            .then(|| {
+                IntCounter::with_opts(opts.opts("dropped_total", |help| {
+                    format!(
+                        "Total number of queued items discarded from {} channel without being received",
+                        help
+                    )
+                }))
+            })
+            .transpose()?;
+        let dead_letters_total = opts
+            .dead_letters
+            .then(|| {
+                IntCounter::with_opts(opts.opts("dead_letters_total", |help| {
+                    format!(
+                        "Total number of undelivered items of {} channel sent to its dead-letter channel",
+                        help
+                    )
+                }))
+            })
+            .transpose()?;
+        let dead_letters_lost_total = opts
+            .dead_letters
+            .then(|| {
+                IntCounter::with_opts(opts.opts("dead_letters_lost_total", |help| {
+                    format!(
+                        "Total number of undelivered items of {} channel lost to a full dead-letter channel",
+                        help
+                    )
+                }))
+            })
+            .transpose()?;
+        let rates = opts.rates.then(|| Arc::new(RateMeter::new()));
 
         Ok(Self {
             queue_size,
-            total_messages: None,
+            total_messages,
+            evicted_total,
+            capacity,
+            queue_latency,
+            queue_bytes,
+            bytes_total,
+            in_flight,
+            processing_time,
+            idle_time,
+            busy_time,
+            stalled,
+            drained_total,
+            dropped_total,
+            dead_letters_total,
+            dead_letters_lost_total,
+            recv_clock: RecvClock::default(),
+            rates,
+            tracker: None,
+            name: opts.prefix().into(),
+            #[cfg(feature = "tracing")]
+            trace: ChannelTrace::default(),
+            _registration: None,
+        })
+    }
+
+    /// Get the fully qualified prefix of the metric names
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Read the send and receive rates and the current depth of the channel,
+    /// if [rates are tracked](ChannelMetricsOpts::with_rates).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tokio_prometheus_metered_channel::{mpsc_channel, ChannelMetrics, ChannelMetricsOpts};
+    /// use prometheus::Registry;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let opts = ChannelMetricsOpts::new("requests").with_rates();
+    ///     let metrics = ChannelMetrics::with_opts(&opts, &Registry::new()).unwrap();
+    ///
+    ///     let (tx, _rx) = mpsc_channel(10, metrics);
+    ///     tx.send(42).await.unwrap();
+    ///
+    ///     let throughput = tx.observer().throughput().unwrap();
+    ///     assert!(throughput.send.one_second > 0.0);
+    ///     assert_eq!(throughput.depth, 1);
+    /// }
+    /// ```
+    pub fn throughput(&self) -> Option<Throughput> {
+        self.rates
+            .as_ref()
+            .map(|rates| rates.throughput(self.queue_size.get()))
+    }
+
+    /// Get a snapshot of the channel's state, if it is
+    /// [listed](ChannelMetricsOpts::with_stats)
+    pub fn stats(&self) -> Option<ChannelStats> {
+        self.tracker.as_ref().map(|tracker| tracker.stats())
+    }
+
+    /// Declare that the [task](crate::track_task) named `task` sends to the
+    /// channel, before it sends anything. Only listed channels track tasks.
+    #[cfg(feature = "tasks")]
+    pub fn produced_by(&self, task: &str) {
+        if let Some(ref tracker) = self.tracker {
+            tracker.tasks().produced_by(task);
+        }
+    }
+
+    /// Declare that the [task](crate::track_task) named `task` receives from
+    /// the channel, before it receives anything. Only listed channels track tasks.
+    #[cfg(feature = "tasks")]
+    pub fn consumed_by(&self, task: &str) {
+        if let Some(ref tracker) = self.tracker {
+            tracker.tasks().consumed_by(task);
+        }
+    }
+
+    /// Set the tracing configuration of channels reporting to these metrics
+    #[cfg(feature = "tracing")]
+    pub fn with_trace(mut self, trace: ChannelTrace) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    fn collectors(&self) -> Vec<Box<dyn Collector>> {
+        let mut collectors: Vec<Box<dyn Collector>> = vec![Box::new(self.queue_size.clone())];
+        if let Some(ref counter) = self.total_messages {
+            collectors.push(Box::new(counter.clone()));
+        }
+        if let Some(ref counter) = self.evicted_total {
+            collectors.push(Box::new(counter.clone()));
+        }
+        if let Some(ref gauge) = self.capacity {
+            collectors.push(Box::new(gauge.clone()));
+        }
+        if let Some(ref histogram) = self.queue_latency {
+            collectors.push(Box::new(histogram.clone()));
+        }
+        if let Some(ref gauge) = self.queue_bytes {
+            collectors.push(Box::new(gauge.clone()));
+        }
+        if let Some(ref counter) = self.bytes_total {
+            collectors.push(Box::new(counter.clone()));
+        }
+        if let Some(ref gauge) = self.in_flight {
+            collectors.push(Box::new(gauge.clone()));
+        }
+        if let Some(ref histogram) = self.processing_time {
+            collectors.push(Box::new(histogram.clone()));
+        }
+        if let Some(ref counter) = self.idle_time {
+            collectors.push(Box::new(counter.clone()));
+        }
+        if let Some(ref counter) = self.busy_time {
+            collectors.push(Box::new(counter.clone()));
+        }
+        if let Some(ref gauge) = self.stalled {
+            collectors.push(Box::new(gauge.clone()));
+        }
+        if let Some(ref counter) = self.drained_total {
+            collectors.push(Box::new(counter.clone()));
+        }
+        if let Some(ref counter) = self.dropped_total {
+            collectors.push(Box::new(counter.clone()));
+        }
+        if let Some(ref counter) = self.dead_letters_total {
+            collectors.push(Box::new(counter.clone()));
+        }
+        if let Some(ref counter) = self.dead_letters_lost_total {
+            collectors.push(Box::new(counter.clone()));
+        }
+        collectors
+    }
+}
+
+impl ChannelObserver for ChannelMetrics {
+    fn on_send(&self) {
+        self.queue_size.inc();
+        if let Some(ref counter) = self.total_messages {
+            counter.inc();
+        }
+        if let Some(ref rates) = self.rates {
+            rates.sent();
+        }
+        if let Some(ref tracker) = self.tracker {
+            tracker.sent();
+        }
+    }
+
+    fn on_recv(&self, queued: Option<Duration>) {
+        self.queue_size.dec();
+        if let Some(ref rates) = self.rates {
+            rates.received();
+        }
+        if let Some(ref tracker) = self.tracker {
+            tracker.received();
+        }
+        if let (Some(histogram), Some(queued)) = (&self.queue_latency, queued) {
+            histogram.observe(queued.as_secs_f64());
+        }
+        if let Some(ref counter) = self.busy_time {
+            if let Some(busy) = self.recv_clock.received() {
+                counter.inc_by(busy.as_secs_f64());
+            }
+        }
+    }
+
+    fn on_deliver(&self) {
+        if let Some(ref counter) = self.total_messages {
+            counter.inc();
+        }
+    }
+
+    fn on_drop(&self, count: usize) {
+        self.queue_size.sub(count as i64);
+        if let Some(ref counter) = self.dropped_total {
+            counter.inc_by(count as u64);
+        }
+        if let Some(ref tracker) = self.tracker {
+            tracker.dropped(count);
+        }
+    }
+
+    fn on_evict(&self, queued: bool) {
+        if let Some(ref counter) = self.evicted_total {
+            counter.inc();
+        }
+        if queued {
+            self.queue_size.dec();
+            if let Some(ref tracker) = self.tracker {
+                tracker.evicted();
+            }
+        }
+    }
+
+    #[cfg(feature = "tasks")]
+    fn on_wait(&self, _waited: Duration) {
+        if let Some(ref tracker) = self.tracker {
+            tracker.tasks().wait_ended();
+        }
+    }
+
+    #[cfg(feature = "tasks")]
+    fn on_wait_start(&self) {
+        if let Some(ref tracker) = self.tracker {
+            tracker.tasks().wait_started();
+        }
+    }
+
+    #[cfg(feature = "tasks")]
+    fn on_wait_cancel(&self) {
+        if let Some(ref tracker) = self.tracker {
+            tracker.tasks().wait_ended();
+        }
+    }
+
+    fn on_capacity(&self, capacity: usize) {
+        if let Some(ref gauge) = self.capacity {
+            gauge.set(capacity as i64);
+        }
+        if let Some(ref tracker) = self.tracker {
+            tracker.set_capacity(capacity);
+        }
+    }
+
+    fn on_send_bytes(&self, bytes: usize) {
+        if let Some(ref gauge) = self.queue_bytes {
+            gauge.add(bytes as i64);
+        }
+        if let Some(ref counter) = self.bytes_total {
+            counter.inc_by(bytes as u64);
+        }
+    }
+
+    fn on_recv_bytes(&self, bytes: usize) {
+        if let Some(ref gauge) = self.queue_bytes {
+            gauge.sub(bytes as i64);
+        }
+    }
+
+    fn on_process_start(&self) {
+        if let Some(ref gauge) = self.in_flight {
+            gauge.inc();
+        }
+    }
+
+    fn on_processed(&self, elapsed: Duration) {
+        if let Some(ref gauge) = self.in_flight {
+            gauge.dec();
+        }
+        if let Some(ref histogram) = self.processing_time {
+            histogram.observe(elapsed.as_secs_f64());
+        }
+    }
+
+    fn on_idle(&self, waited: Duration) {
+        if let Some(ref counter) = self.idle_time {
+            counter.inc_by(waited.as_secs_f64());
+            self.recv_clock.idled(waited);
+        }
+    }
+
+    fn on_drain(&self, processed: usize, _abandoned: usize) {
+        if let Some(ref counter) = self.drained_total {
+            counter.inc_by(processed as u64);
+        }
+    }
+
+    fn on_dead_letter(&self, captured: bool) {
+        let counter = if captured {
+            &self.dead_letters_total
+        } else {
+            &self.dead_letters_lost_total
+        };
+        if let Some(counter) = counter {
+            counter.inc();
+        }
+    }
+
+    fn on_open(&self, kind: ChannelKind) {
+        if let Some(ref tracker) = self.tracker {
+            tracker.opened(kind);
+        }
+    }
+
+    fn on_attach(&self, handle: HandleKind) {
+        if let Some(ref tracker) = self.tracker {
+            tracker.attached(handle);
+        }
+    }
+
+    fn on_detach(&self, handle: HandleKind) {
+        if let Some(ref tracker) = self.tracker {
+            tracker.detached(handle);
+        }
+    }
+
+    fn on_close(&self) {
+        if let Some(ref tracker) = self.tracker {
+            tracker.close();
+        }
+    }
+
+    fn measures_latency(&self) -> bool {
+        self.queue_latency.is_some()
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[cfg(feature = "tracing")]
+    fn trace(&self) -> ChannelTrace {
+        self.trace
+    }
+}
+
+/// Registry configured with [`set_default_registry`]
+static DEFAULT_REGISTRY: Mutex<Option<Registry>> = Mutex::new(None);
+
+/// Set the registry used by channels created without an explicit registry.
+///
+/// This affects [`MeteredChannelBuilder`](crate::MeteredChannelBuilder) and the
+/// `*_channel_named` constructors for channels created after the call.
+pub fn set_default_registry(registry: &Registry) {
+    *DEFAULT_REGISTRY.lock().unwrap_or_else(|e| e.into_inner()) = Some(registry.clone());
+}
+
+/// Get the registry used by channels created without an explicit registry.
+///
+/// This is the registry passed to [`set_default_registry`], or
+/// [`prometheus::default_registry`] if none was set.
+pub fn default_registry() -> Registry {
+    DEFAULT_REGISTRY
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| prometheus::default_registry().clone())
+}
+
+/// Metric sets created by [`ChannelMetrics::get_or_register`], with the
+/// registries they were registered with
+static REGISTRATIONS: Mutex<Vec<CachedRegistration>> = Mutex::new(Vec::new());
+
+fn registrations() -> MutexGuard<'static, Vec<CachedRegistration>> {
+    REGISTRATIONS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn registration_key(opts: &ChannelMetricsOpts) -> String {
+    let mut labels: Vec<_> = opts.const_labels.iter().collect();
+    labels.sort();
+    format!("{}{:?}", opts.prefix(), labels)
+}
+
+/// Handle shared by every [`ChannelMetrics`] clone of a cached metric set,
+/// unregistering the metrics when the last one is dropped
+#[derive(Debug)]
+struct Registration {
+    key: String,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let mut cache = registrations();
+        let position = cache.iter().position(|entry| {
+            entry.key == self.key && std::ptr::eq(entry.registration.as_ptr(), self)
+        });
+        if let Some(index) = position {
+            cache.swap_remove(index).unregister();
+        }
+    }
+}
+
+struct CachedRegistration {
+    key: String,
+    metrics: ChannelMetrics,
+    registries: Vec<Registry>,
+    registration: Weak<Registration>,
+}
+
+impl CachedRegistration {
+    /// Create the collectors requested by `opts` that the cached set lacks
+    fn add_missing(&mut self, opts: &ChannelMetricsOpts) -> Result<(), prometheus::Error> {
+        let requested = ChannelMetricsOpts {
+            total_messages: opts.total_messages && self.metrics.total_messages.is_none(),
+            evicted_total: opts.evicted_total && self.metrics.evicted_total.is_none(),
+            capacity: opts.capacity && self.metrics.capacity.is_none(),
+            latency_buckets: opts
+                .latency_buckets
+                .clone()
+                .filter(|_| self.metrics.queue_latency.is_none()),
+            bytes: opts.bytes && self.metrics.queue_bytes.is_none(),
+            processing_buckets: opts
+                .processing_buckets
+                .clone()
+                .filter(|_| self.metrics.processing_time.is_none()),
+            utilization: opts.utilization && self.metrics.idle_time.is_none(),
+            rates: opts.rates && self.metrics.rates.is_none(),
+            stats: opts.stats && self.metrics.tracker.is_none(),
+            stall_threshold: opts
+                .stall_threshold
+                .filter(|_| self.metrics.stalled.is_none()),
+            drain: opts.drain && self.metrics.drained_total.is_none(),
+            dead_letters: opts.dead_letters && self.metrics.dead_letters_total.is_none(),
+            ..opts.clone()
+        };
+        let added = ChannelMetrics::create(&requested)?;
+
+        // The queue size gauge is already registered
+        let collectors = || added.collectors().into_iter().skip(1).collect();
+        for (index, registry) in self.registries.iter().enumerate() {
+            if let Err(e) = register_all(registry, collectors) {
+                for registry in &self.registries[..index] {
+                    unregister_all(registry, collectors());
+                }
+                return Err(e);
+            }
+        }
+
+        let metrics = &mut self.metrics;
+        metrics.total_messages = metrics.total_messages.take().or(added.total_messages);
+        metrics.evicted_total = metrics.evicted_total.take().or(added.evicted_total);
+        metrics.capacity = metrics.capacity.take().or(added.capacity);
+        metrics.queue_latency = metrics.queue_latency.take().or(added.queue_latency);
+        metrics.queue_bytes = metrics.queue_bytes.take().or(added.queue_bytes);
+        metrics.bytes_total = metrics.bytes_total.take().or(added.bytes_total);
+        metrics.in_flight = metrics.in_flight.take().or(added.in_flight);
+        metrics.processing_time = metrics.processing_time.take().or(added.processing_time);
+        metrics.idle_time = metrics.idle_time.take().or(added.idle_time);
+        metrics.busy_time = metrics.busy_time.take().or(added.busy_time);
+        metrics.rates = metrics.rates.take().or(added.rates);
+        metrics.stalled = metrics.stalled.take().or(added.stalled);
+        metrics.drained_total = metrics.drained_total.take().or(added.drained_total);
+        metrics.dropped_total = metrics.dropped_total.take().or(added.dropped_total);
+        metrics.dead_letters_total = metrics
+            .dead_letters_total
+            .take()
+            .or(added.dead_letters_total);
+        metrics.dead_letters_lost_total = metrics
+            .dead_letters_lost_total
+            .take()
+            .or(added.dead_letters_lost_total);
+        // Stall checks and rates added to a tracked set go to its tracker
+        metrics.track(&requested);
+        Ok(())
+    }
+
+    /// Register the cached collectors with `registry` if they are not already
+    fn register_into(&mut self, registry: &Registry) -> Result<(), prometheus::Error> {
+        let mut registered = Vec::new();
+        for (index, collector) in self.metrics.collectors().into_iter().enumerate() {
+            match registry.register(collector) {
+                Ok(()) => registered.push(index),
+                Err(prometheus::Error::AlreadyReg) => {}
+                Err(e) => {
+                    let collectors = self.metrics.collectors().into_iter().enumerate();
+                    unregister_all(
+                        registry,
+                        collectors
+                            .filter(|(index, _)| registered.contains(index))
+                            .map(|(_, collector)| collector),
+                    );
+                    return Err(e);
+                }
+            }
+        }
+        if !registered.is_empty() {
+            self.registries.push(registry.clone());
+        }
+        Ok(())
+    }
+
+    fn unregister(self) {
+        for registry in &self.registries {
+            unregister_all(registry, self.metrics.collectors());
+        }
+    }
+}
+
+/// Register every collector returned by `collectors`, unregistering the ones
+/// already registered if one fails so that none are left behind
+fn register_all(
+    registry: &Registry,
+    collectors: impl Fn() -> Vec<Box<dyn Collector>>,
+) -> Result<(), prometheus::Error> {
+    for (index, collector) in collectors().into_iter().enumerate() {
+        if let Err(e) = registry.register(collector) {
+            unregister_all(registry, collectors().into_iter().take(index));
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+fn unregister_all(registry: &Registry, collectors: impl IntoIterator<Item = Box<dyn Collector>>) {
+    for collector in collectors {
+        // Collectors may already have been removed by hand
+        let _ = registry.unregister(collector);
+    }
+}
+
+/// Metrics for monitoring a [`MergedReceiver`](crate::MergedReceiver)
+#[derive(Clone, Debug)]
+pub struct MergeMetrics {
+    /// Total number of items received from each source, labelled by source name
+    pub received: IntCounterVec,
+}
+
+impl MergeMetrics {
+    /// Create new merge metrics and register them with Prometheus
+    pub fn new(name: &str, help: &str, registry: &Registry) -> Result<Self, prometheus::Error> {
+        let received = IntCounterVec::new(
+            Opts::new(
+                format!("{}_source_received_total", name),
+                format!(
+                    "Total number of messages received per source by {} merge",
+                    help
+                ),
+            ),
+            &["source"],
+        )?;
+        registry.register(Box::new(received.clone()))?;
+
+        Ok(Self { received })
+    }
+}
+
+/// Metrics for monitoring a [`RateLimitedSender`](crate::RateLimitedSender)
+#[derive(Clone, Debug)]
+pub struct ThrottleMetrics {
+    /// Total number of sends that had to wait for a rate token
+    pub throttled_total: IntCounter,
+    /// Time spent waiting for a rate token, in seconds
+    pub throttle_wait: Histogram,
+    /// Time spent waiting for channel capacity once a token was acquired, in seconds
+    pub capacity_wait: Histogram,
+}
+
+impl ThrottleMetrics {
+    /// Create new throttle metrics and register them with Prometheus
+    pub fn new(name: &str, help: &str, registry: &Registry) -> Result<Self, prometheus::Error> {
+        let throttled_total = IntCounter::with_opts(Opts::new(
+            format!("{}_throttled_total", name),
+            format!("Total number of sends throttled by {} rate limiter", help),
+        ))?;
+        let throttle_wait = Histogram::with_opts(HistogramOpts::new(
+            format!("{}_throttle_wait_seconds", name),
+            format!("Time spent waiting for a rate token by {} sender", help),
+        ))?;
+        let capacity_wait = Histogram::with_opts(HistogramOpts::new(
+            format!("{}_capacity_wait_seconds", name),
+            format!("Time spent waiting for capacity by {} sender", help),
+        ))?;
+
+        register_all(registry, || {
+            vec![
+                Box::new(throttled_total.clone()),
+                Box::new(throttle_wait.clone()),
+                Box::new(capacity_wait.clone()),
+            ]
+        })?;
+
+        Ok(Self {
+            throttled_total,
+            throttle_wait,
+            capacity_wait,
+        })
+    }
+}
+
+/// Metrics for monitoring a [`CapacityController`](crate::CapacityController)
+#[derive(Clone, Debug)]
+pub struct ControllerMetrics {
+    /// Total number of capacity changes, labelled by direction (`grow` or `shrink`)
+    pub adjustments: IntCounterVec,
+    /// Last utilization ratio or queue latency (in seconds) observed by the controller
+    pub observed: Gauge,
+}
+
+impl ControllerMetrics {
+    /// Create new controller metrics and register them with Prometheus
+    pub fn new(name: &str, help: &str, registry: &Registry) -> Result<Self, prometheus::Error> {
+        let adjustments = IntCounterVec::new(
+            Opts::new(
+                format!("{}_controller_adjustments_total", name),
+                format!("Total number of capacity adjustments of {} channel", help),
+            ),
+            &["direction"],
+        )?;
+        let observed = Gauge::with_opts(Opts::new(
+            format!("{}_controller_observed", name),
+            format!(
+                "Last value observed by the capacity controller of {} channel",
+                help
+            ),
+        ))?;
+
+        register_all(registry, || {
+            vec![Box::new(adjustments.clone()), Box::new(observed.clone())]
+        })?;
+
+        Ok(Self {
+            adjustments,
+            observed,
         })
     }
 }
@@ -0,0 +1,804 @@
+#[cfg(feature = "tracing")]
+use crate::trace::ChannelTrace;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+#[cfg(any(feature = "prometheus", feature = "metrics", feature = "opentelemetry"))]
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+#[cfg(any(
+    feature = "prometheus",
+    feature = "metrics",
+    feature = "opentelemetry",
+    feature = "mpsc",
+    feature = "broadcast",
+    feature = "watch",
+    feature = "overflow",
+    feature = "resizable",
+    feature = "weighted"
+))]
+use tokio::time::Instant;
+
+/// Receives the events of a metered channel.
+///
+/// Every channel type reports what happens to its values through an observer,
+/// which turns them into metrics for one backend: [`ChannelMetrics`](crate::ChannelMetrics)
+/// for Prometheus, [`MetricsObserver`](crate::MetricsObserver) for the `metrics`
+/// facade, [`OtelObserver`](crate::OtelObserver) for OpenTelemetry, or
+/// [`NoopObserver`] to record nothing. All methods default to doing nothing,
+/// so an implementation only handles the events it cares about.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::sync::atomic::{AtomicI64, Ordering};
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use tokio_prometheus_metered_channel::{mpsc_channel_with_observer, ChannelObserver};
+///
+/// #[derive(Clone, Default)]
+/// struct Depth(Arc<AtomicI64>);
+///
+/// impl ChannelObserver for Depth {
+///     fn on_send(&self) {
+///         self.0.fetch_add(1, Ordering::Relaxed);
+///     }
+///
+///     fn on_recv(&self, _queued: Option<Duration>) {
+///         self.0.fetch_sub(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let depth = Depth::default();
+///     let (tx, mut rx) = mpsc_channel_with_observer(10, depth.clone());
+///
+///     tx.send(1).await.unwrap();
+///     assert_eq!(depth.0.load(Ordering::Relaxed), 1);
+///     rx.recv().await.unwrap();
+///     assert_eq!(depth.0.load(Ordering::Relaxed), 0);
+/// }
+/// ```
+pub trait ChannelObserver: Send + Sync {
+    /// Called after a value is queued
+    #[inline]
+    fn on_send(&self) {}
+
+    /// Called after a value is taken from the queue, with the time it spent
+    /// queued if [`measures_latency`](Self::measures_latency) returned true
+    #[inline]
+    fn on_recv(&self, _queued: Option<Duration>) {}
+
+    /// Called when a broadcast or watch receiver is handed a value by its
+    /// waiting receive method, in addition to [`on_recv`](Self::on_recv)
+    #[inline]
+    fn on_deliver(&self) {}
+
+    /// Called when `count` queued values are discarded without being received
+    #[inline]
+    fn on_drop(&self, _count: usize) {}
+
+    /// Called when a lossy overflow policy discards a value: the oldest queued
+    /// one when `queued` is true, which then no longer counts as queued
+    /// without being reported to [`on_drop`](Self::on_drop), or else the one
+    /// being sent
+    #[inline]
+    fn on_evict(&self, _queued: bool) {}
+
+    /// Called when a broadcast receiver falls behind and misses `skipped` values
+    #[inline]
+    fn on_lag(&self, _skipped: u64) {}
+
+    /// Called after a sender waited `waited` for capacity
+    #[inline]
+    fn on_wait(&self, _waited: Duration) {}
+
+    /// Called when a sender starts waiting for capacity, before the matching
+    /// [`on_wait`](Self::on_wait) or [`on_wait_cancel`](Self::on_wait_cancel)
+    #[inline]
+    fn on_wait_start(&self) {}
+
+    /// Called instead of [`on_wait`](Self::on_wait) when a send waiting for
+    /// capacity is dropped
+    #[inline]
+    fn on_wait_cancel(&self) {}
+
+    /// Called when the capacity of the channel is set or changed
+    #[inline]
+    fn on_capacity(&self, _capacity: usize) {}
+
+    /// Called by [weighted](crate::weighted) channels after a value weighing
+    /// `bytes` is queued, in addition to [`on_send`](Self::on_send)
+    #[inline]
+    fn on_send_bytes(&self, _bytes: usize) {}
+
+    /// Called by [weighted](crate::weighted) channels after a value weighing
+    /// `bytes` leaves the queue
+    #[inline]
+    fn on_recv_bytes(&self, _bytes: usize) {}
+
+    /// Called when a value received through a [`MessageGuard`](crate::MessageGuard)
+    /// is handed to the consumer
+    #[inline]
+    fn on_process_start(&self) {}
+
+    /// Called when the consumer is done with a guarded value, `elapsed` after
+    /// it was handed over
+    #[inline]
+    fn on_processed(&self, _elapsed: Duration) {}
+
+    /// Called when a receiver waited `waited` on an empty channel, before the
+    /// [`on_recv`](Self::on_recv) of the value it was waiting for, if any.
+    /// A wait that is cancelled, or a receiver dropped while waiting, is
+    /// reported too
+    #[inline]
+    fn on_idle(&self, _waited: Duration) {}
+
+    /// Called when an mpsc [`drain`](crate::MpscReceiver::drain) completes, with
+    /// the number of values the consumer processed and the number it
+    /// abandoned. The abandoned values still queued are also reported to
+    /// [`on_drop`](Self::on_drop)
+    #[inline]
+    fn on_drain(&self, _processed: usize, _abandoned: usize) {}
+
+    /// Called when a value the channel could not deliver is sent to its
+    /// [dead-letter channel](crate::DeadLetterSender), with whether the
+    /// dead-letter channel had room for it
+    #[inline]
+    fn on_dead_letter(&self, _captured: bool) {}
+
+    /// Called when a channel of the given kind is created along with its
+    /// first sender and receiver
+    #[inline]
+    fn on_open(&self, _kind: ChannelKind) {}
+
+    /// Called when a sender or receiver handle is added to the channel, by
+    /// cloning or subscribing
+    #[inline]
+    fn on_attach(&self, _handle: HandleKind) {}
+
+    /// Called when a sender or receiver handle is dropped
+    #[inline]
+    fn on_detach(&self, _handle: HandleKind) {}
+
+    /// Called when a receiver closes the channel
+    #[inline]
+    fn on_close(&self) {}
+
+    /// Returns true if values should be timestamped when queued so that
+    /// [`on_recv`](Self::on_recv) gets the time they spent queued
+    #[inline]
+    fn measures_latency(&self) -> bool {
+        false
+    }
+
+    /// Name of the channel, recorded in the `channel` field of its spans
+    #[inline]
+    fn name(&self) -> &str {
+        ""
+    }
+
+    /// Tracing configuration of the channel's spans
+    #[cfg(feature = "tracing")]
+    #[inline]
+    fn trace(&self) -> ChannelTrace {
+        ChannelTrace::default()
+    }
+}
+
+/// The family of a channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChannelKind {
+    /// A bounded mpsc channel
+    Mpsc,
+    /// A broadcast channel
+    Broadcast,
+    /// A watch channel
+    Watch,
+    /// A channel with an overflow policy
+    Overflow,
+    /// A channel with a resizable capacity
+    Resizable,
+    /// A channel bounded by the weight of its values
+    Weighted,
+}
+
+impl ChannelKind {
+    /// Lowercase name of the kind, e.g. `mpsc`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChannelKind::Mpsc => "mpsc",
+            ChannelKind::Broadcast => "broadcast",
+            ChannelKind::Watch => "watch",
+            ChannelKind::Overflow => "overflow",
+            ChannelKind::Resizable => "resizable",
+            ChannelKind::Weighted => "weighted",
+        }
+    }
+}
+
+impl fmt::Display for ChannelKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The side of a channel a handle belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HandleKind {
+    /// A sending handle
+    Sender,
+    /// A receiving handle
+    Receiver,
+}
+
+/// An observer recording nothing.
+///
+/// `NoopObserver` is zero-sized and all its methods are empty, so channels
+/// using it keep the same [`MpscSender`](crate::MpscSender) and
+/// [`MpscReceiver`](crate::MpscReceiver) types as metered channels without
+/// recording anything. Values are still wrapped as they are for metered
+/// channels, so this is not free compared to the underlying tokio channels.
+///
+/// # Examples
+///
+/// ```rust
+/// use tokio_prometheus_metered_channel::{mpsc_channel_with_observer, MpscSender, NoopObserver};
+///
+/// async fn produce(tx: MpscSender<u64, NoopObserver>) {
+///     tx.send(42).await.unwrap();
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let (tx, mut rx) = mpsc_channel_with_observer(10, NoopObserver);
+///     produce(tx).await;
+///     assert_eq!(rx.recv().await, Some(42));
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NoopObserver;
+
+impl ChannelObserver for NoopObserver {}
+
+/// An observer counting channel events in memory, for tests.
+///
+/// Clones share the same counts, so keep a clone to inspect the events of a
+/// channel it was given to.
+///
+/// # Examples
+///
+/// ```rust
+/// use tokio_prometheus_metered_channel::{mpsc_channel_with_observer, CountingObserver};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let observer = CountingObserver::new();
+///     let (tx, mut rx) = mpsc_channel_with_observer(10, observer.clone());
+///
+///     tx.send(1).await.unwrap();
+///     tx.send(2).await.unwrap();
+///     rx.recv().await.unwrap();
+///
+///     assert_eq!(observer.sent(), 2);
+///     assert_eq!(observer.received(), 1);
+///     assert_eq!(observer.len(), 1);
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CountingObserver {
+    counts: Arc<Counts>,
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    sent: AtomicU64,
+    received: AtomicU64,
+    delivered: AtomicU64,
+    dropped: AtomicU64,
+    evicted: AtomicU64,
+    evicted_queued: AtomicU64,
+    lagged: AtomicU64,
+    waits: AtomicU64,
+    waiting: AtomicUsize,
+    capacity: AtomicUsize,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    process_started: AtomicU64,
+    processed: AtomicU64,
+    idle_waits: AtomicU64,
+    drained: AtomicU64,
+    dead_letters: AtomicU64,
+    dead_letters_lost: AtomicU64,
+    senders: AtomicUsize,
+    receivers: AtomicUsize,
+}
+
+impl Counts {
+    fn handles(&self, handle: HandleKind) -> &AtomicUsize {
+        match handle {
+            HandleKind::Sender => &self.senders,
+            HandleKind::Receiver => &self.receivers,
+        }
+    }
+}
+
+impl CountingObserver {
+    /// Create an observer with all counts at zero
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of values queued
+    pub fn sent(&self) -> u64 {
+        self.counts.sent.load(Ordering::Relaxed)
+    }
+
+    /// Number of values taken from the queue
+    pub fn received(&self) -> u64 {
+        self.counts.received.load(Ordering::Relaxed)
+    }
+
+    /// Number of values handed to broadcast or watch receivers
+    pub fn delivered(&self) -> u64 {
+        self.counts.delivered.load(Ordering::Relaxed)
+    }
+
+    /// Number of queued values discarded without being received
+    pub fn dropped(&self) -> u64 {
+        self.counts.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Number of values discarded by a lossy overflow policy
+    pub fn evicted(&self) -> u64 {
+        self.counts.evicted.load(Ordering::Relaxed)
+    }
+
+    /// Number of values missed by lagging broadcast receivers
+    pub fn lagged(&self) -> u64 {
+        self.counts.lagged.load(Ordering::Relaxed)
+    }
+
+    /// Number of sends that had to wait for capacity
+    pub fn waits(&self) -> u64 {
+        self.counts.waits.load(Ordering::Relaxed)
+    }
+
+    /// Number of sends currently waiting for capacity
+    pub fn waiting(&self) -> usize {
+        self.counts.waiting.load(Ordering::Relaxed)
+    }
+
+    /// Last capacity reported by the channel, or 0 if none was
+    pub fn capacity(&self) -> usize {
+        self.counts.capacity.load(Ordering::Relaxed)
+    }
+
+    /// Total weight of the values queued by a weighted channel
+    pub fn bytes_sent(&self) -> u64 {
+        self.counts.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    /// Total weight of the values that left the queue of a weighted channel
+    pub fn bytes_received(&self) -> u64 {
+        self.counts.bytes_received.load(Ordering::Relaxed)
+    }
+
+    /// Total weight of the values currently queued by a weighted channel
+    pub fn queued_bytes(&self) -> u64 {
+        self.bytes_sent().saturating_sub(self.bytes_received())
+    }
+
+    /// Number of guarded values whose processing completed
+    pub fn processed(&self) -> u64 {
+        self.counts.processed.load(Ordering::Relaxed)
+    }
+
+    /// Number of guarded values currently being processed
+    pub fn in_flight(&self) -> u64 {
+        self.counts
+            .process_started
+            .load(Ordering::Relaxed)
+            .saturating_sub(self.processed())
+    }
+
+    /// Number of live sender handles
+    pub fn senders(&self) -> usize {
+        self.counts.senders.load(Ordering::Relaxed)
+    }
+
+    /// Number of live receiver handles
+    pub fn receivers(&self) -> usize {
+        self.counts.receivers.load(Ordering::Relaxed)
+    }
+
+    /// Number of times receivers waited on an empty channel
+    pub fn idle_waits(&self) -> u64 {
+        self.counts.idle_waits.load(Ordering::Relaxed)
+    }
+
+    /// Number of values handed to consumers by drains
+    pub fn drained(&self) -> u64 {
+        self.counts.drained.load(Ordering::Relaxed)
+    }
+
+    /// Number of undelivered values captured by a dead-letter channel
+    pub fn dead_letters(&self) -> u64 {
+        self.counts.dead_letters.load(Ordering::Relaxed)
+    }
+
+    /// Number of undelivered values lost because the dead-letter channel was full
+    pub fn dead_letters_lost(&self) -> u64 {
+        self.counts.dead_letters_lost.load(Ordering::Relaxed)
+    }
+
+    /// Number of values currently queued
+    pub fn len(&self) -> u64 {
+        self.sent()
+            .saturating_sub(self.received())
+            .saturating_sub(self.dropped())
+            .saturating_sub(self.counts.evicted_queued.load(Ordering::Relaxed))
+    }
+
+    /// Returns true if no values are queued
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl ChannelObserver for CountingObserver {
+    fn on_send(&self) {
+        self.counts.sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_recv(&self, _queued: Option<Duration>) {
+        self.counts.received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_deliver(&self) {
+        self.counts.delivered.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_drop(&self, count: usize) {
+        self.counts
+            .dropped
+            .fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    fn on_evict(&self, queued: bool) {
+        self.counts.evicted.fetch_add(1, Ordering::Relaxed);
+        if queued {
+            self.counts.evicted_queued.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn on_lag(&self, skipped: u64) {
+        self.counts.lagged.fetch_add(skipped, Ordering::Relaxed);
+    }
+
+    fn on_wait(&self, _waited: Duration) {
+        self.counts.waits.fetch_add(1, Ordering::Relaxed);
+        self.counts.waiting.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    fn on_wait_start(&self) {
+        self.counts.waiting.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_wait_cancel(&self) {
+        self.counts.waiting.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    fn on_capacity(&self, capacity: usize) {
+        self.counts.capacity.store(capacity, Ordering::Relaxed);
+    }
+
+    fn on_send_bytes(&self, bytes: usize) {
+        self.counts
+            .bytes_sent
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    fn on_recv_bytes(&self, bytes: usize) {
+        self.counts
+            .bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    fn on_process_start(&self) {
+        self.counts.process_started.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_processed(&self, _elapsed: Duration) {
+        self.counts.processed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_idle(&self, _waited: Duration) {
+        self.counts.idle_waits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_drain(&self, processed: usize, _abandoned: usize) {
+        self.counts
+            .drained
+            .fetch_add(processed as u64, Ordering::Relaxed);
+    }
+
+    fn on_dead_letter(&self, captured: bool) {
+        let count = if captured {
+            &self.counts.dead_letters
+        } else {
+            &self.counts.dead_letters_lost
+        };
+        count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_open(&self, _kind: ChannelKind) {
+        self.on_attach(HandleKind::Sender);
+        self.on_attach(HandleKind::Receiver);
+    }
+
+    fn on_attach(&self, handle: HandleKind) {
+        self.counts.handles(handle).fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_detach(&self, handle: HandleKind) {
+        self.counts.handles(handle).fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl<O: ChannelObserver + ?Sized> ChannelObserver for Arc<O> {
+    fn on_send(&self) {
+        (**self).on_send()
+    }
+
+    fn on_recv(&self, queued: Option<Duration>) {
+        (**self).on_recv(queued)
+    }
+
+    fn on_deliver(&self) {
+        (**self).on_deliver()
+    }
+
+    fn on_drop(&self, count: usize) {
+        (**self).on_drop(count)
+    }
+
+    fn on_evict(&self, queued: bool) {
+        (**self).on_evict(queued)
+    }
+
+    fn on_lag(&self, skipped: u64) {
+        (**self).on_lag(skipped)
+    }
+
+    fn on_wait(&self, waited: Duration) {
+        (**self).on_wait(waited)
+    }
+
+    fn on_wait_start(&self) {
+        (**self).on_wait_start()
+    }
+
+    fn on_wait_cancel(&self) {
+        (**self).on_wait_cancel()
+    }
+
+    fn on_capacity(&self, capacity: usize) {
+        (**self).on_capacity(capacity)
+    }
+
+    fn on_send_bytes(&self, bytes: usize) {
+        (**self).on_send_bytes(bytes)
+    }
+
+    fn on_recv_bytes(&self, bytes: usize) {
+        (**self).on_recv_bytes(bytes)
+    }
+
+    fn on_process_start(&self) {
+        (**self).on_process_start()
+    }
+
+    fn on_processed(&self, elapsed: Duration) {
+        (**self).on_processed(elapsed)
+    }
+
+    fn on_idle(&self, waited: Duration) {
+        (**self).on_idle(waited)
+    }
+
+    fn on_drain(&self, processed: usize, abandoned: usize) {
+        (**self).on_drain(processed, abandoned)
+    }
+
+    fn on_dead_letter(&self, captured: bool) {
+        (**self).on_dead_letter(captured)
+    }
+
+    fn on_open(&self, kind: ChannelKind) {
+        (**self).on_open(kind)
+    }
+
+    fn on_attach(&self, handle: HandleKind) {
+        (**self).on_attach(handle)
+    }
+
+    fn on_detach(&self, handle: HandleKind) {
+        (**self).on_detach(handle)
+    }
+
+    fn on_close(&self) {
+        (**self).on_close()
+    }
+
+    fn measures_latency(&self) -> bool {
+        (**self).measures_latency()
+    }
+
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    #[cfg(feature = "tracing")]
+    fn trace(&self) -> ChannelTrace {
+        (**self).trace()
+    }
+}
+
+/// A sender's wait for capacity, reported with [`on_wait_start`](ChannelObserver::on_wait_start)
+/// when created, then [`on_wait`](ChannelObserver::on_wait) once [finished](Self::finish)
+/// or [`on_wait_cancel`](ChannelObserver::on_wait_cancel) if dropped before
+#[cfg(any(
+    feature = "mpsc",
+    feature = "overflow",
+    feature = "resizable",
+    feature = "weighted"
+))]
+pub(crate) struct Waiting<'a, O: ChannelObserver + ?Sized> {
+    observer: &'a O,
+    start: Instant,
+    finished: bool,
+}
+
+#[cfg(any(
+    feature = "mpsc",
+    feature = "overflow",
+    feature = "resizable",
+    feature = "weighted"
+))]
+impl<'a, O: ChannelObserver + ?Sized> Waiting<'a, O> {
+    pub(crate) fn new(observer: &'a O) -> Self {
+        observer.on_wait_start();
+        Self {
+            observer,
+            start: Instant::now(),
+            finished: false,
+        }
+    }
+
+    pub(crate) fn finish(mut self) {
+        self.finished = true;
+        self.observer.on_wait(self.start.elapsed());
+    }
+}
+
+#[cfg(any(
+    feature = "mpsc",
+    feature = "overflow",
+    feature = "resizable",
+    feature = "weighted"
+))]
+impl<O: ChannelObserver + ?Sized> Drop for Waiting<'_, O> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.observer.on_wait_cancel();
+        }
+    }
+}
+
+/// Reports the time a receiver spent waiting on an empty channel when dropped,
+/// so that a wait cancelled before a value arrived still counts as idle
+#[cfg(any(
+    feature = "mpsc",
+    feature = "broadcast",
+    feature = "watch",
+    feature = "overflow",
+    feature = "resizable",
+    feature = "weighted"
+))]
+pub(crate) struct Idle<'a, O: ChannelObserver + ?Sized> {
+    observer: &'a O,
+    start: Instant,
+}
+
+#[cfg(any(
+    feature = "mpsc",
+    feature = "broadcast",
+    feature = "watch",
+    feature = "overflow",
+    feature = "resizable",
+    feature = "weighted"
+))]
+impl<'a, O: ChannelObserver + ?Sized> Idle<'a, O> {
+    #[cfg(any(
+        feature = "broadcast",
+        feature = "watch",
+        feature = "overflow",
+        feature = "resizable",
+        feature = "weighted"
+    ))]
+    pub(crate) fn new(observer: &'a O) -> Self {
+        Self {
+            observer,
+            start: Instant::now(),
+        }
+    }
+
+    /// Continue a wait that started at `start`
+    #[cfg(feature = "mpsc")]
+    pub(crate) fn since(observer: &'a O, start: Instant) -> Self {
+        Self { observer, start }
+    }
+}
+
+#[cfg(any(
+    feature = "mpsc",
+    feature = "broadcast",
+    feature = "watch",
+    feature = "overflow",
+    feature = "resizable",
+    feature = "weighted"
+))]
+impl<O: ChannelObserver + ?Sized> Drop for Idle<'_, O> {
+    fn drop(&mut self) {
+        self.observer.on_idle(self.start.elapsed());
+    }
+}
+
+/// Splits the time of a receiver between waiting on an empty channel and the
+/// time between receives, for backends reporting consumer utilization.
+///
+/// Each receiver holds its own clone of its channel's observer, so a clone
+/// starts a fresh clock instead of sharing the original's.
+#[cfg(any(feature = "prometheus", feature = "metrics", feature = "opentelemetry"))]
+#[derive(Debug, Default)]
+pub(crate) struct RecvClock(Mutex<ClockState>);
+
+#[cfg(any(feature = "prometheus", feature = "metrics", feature = "opentelemetry"))]
+#[derive(Debug, Default)]
+struct ClockState {
+    last_recv: Option<Instant>,
+    idle: Duration,
+}
+
+#[cfg(any(feature = "prometheus", feature = "metrics", feature = "opentelemetry"))]
+impl RecvClock {
+    /// Record that the receiver waited `waited` on an empty channel
+    pub(crate) fn idled(&self, waited: Duration) {
+        self.lock().idle += waited;
+    }
+
+    /// Record a receive, returning the time since the previous one that was
+    /// not spent waiting on an empty channel
+    pub(crate) fn received(&self) -> Option<Duration> {
+        let now = Instant::now();
+        let mut state = self.lock();
+        let idle = std::mem::take(&mut state.idle);
+        state
+            .last_recv
+            .replace(now)
+            .map(|last| now.saturating_duration_since(last).saturating_sub(idle))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ClockState> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(any(feature = "prometheus", feature = "metrics", feature = "opentelemetry"))]
+impl Clone for RecvClock {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
@@ -0,0 +1,268 @@
+use crate::observer::{ChannelObserver, RecvClock};
+#[cfg(feature = "tracing")]
+use crate::trace::ChannelTrace;
+use opentelemetry::metrics::{Counter, Gauge, Histogram, Meter, UpDownCounter};
+use opentelemetry::KeyValue;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A channel observer reporting through OpenTelemetry metrics.
+///
+/// Instrument names follow the same `{name}_{suffix}` scheme as
+/// [`ChannelMetrics`](crate::ChannelMetrics), and every measurement carries
+/// the attributes given at creation.
+///
+/// # Examples
+///
+/// ```rust
+/// use opentelemetry::global;
+/// use tokio_prometheus_metered_channel::{mpsc_channel_with_observer, OtelObserver};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let meter = global::meter("pipeline");
+///     let observer = OtelObserver::new(&meter, "ingest").with_latency(&meter);
+///     let (tx, mut rx) = mpsc_channel_with_observer(10, observer);
+///
+///     tx.send(42).await.unwrap();
+///     assert_eq!(rx.recv().await, Some(42));
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct OtelObserver {
+    name: String,
+    attributes: Arc<[KeyValue]>,
+    queue_size: UpDownCounter<i64>,
+    total_messages: Counter<u64>,
+    delivered_total: Counter<u64>,
+    dropped_total: Counter<u64>,
+    evicted_total: Counter<u64>,
+    lagged_total: Counter<u64>,
+    capacity: Gauge<u64>,
+    send_wait: Histogram<f64>,
+    queue_bytes: UpDownCounter<i64>,
+    bytes_total: Counter<u64>,
+    in_flight: UpDownCounter<i64>,
+    processing_time: Histogram<f64>,
+    idle_time: Counter<f64>,
+    busy_time: Counter<f64>,
+    drained_total: Counter<u64>,
+    dead_letters_total: Counter<u64>,
+    dead_letters_lost_total: Counter<u64>,
+    recv_clock: RecvClock,
+    #[cfg(feature = "tracing")]
+    trace: ChannelTrace,
+    queue_latency: Option<Histogram<f64>>,
+}
+
+impl OtelObserver {
+    /// Create an observer for instruments prefixed with `name`
+    pub fn new(meter: &Meter, name: &str) -> Self {
+        Self::with_attributes(meter, name, Vec::new())
+    }
+
+    /// Create an observer for instruments prefixed with `name`, recording
+    /// `attributes` with every measurement
+    pub fn with_attributes(meter: &Meter, name: &str, attributes: Vec<KeyValue>) -> Self {
+        Self {
+            name: name.to_string(),
+            attributes: attributes.into(),
+            queue_size: meter
+                .i64_up_down_counter(format!("{name}_queue_size"))
+                .with_description("Current number of items in the channel")
+                .build(),
+            total_messages: meter
+                .u64_counter(format!("{name}_total_messages"))
+                .with_description("Total number of items sent through the channel")
+                .build(),
+            delivered_total: meter
+                .u64_counter(format!("{name}_delivered_total"))
+                .with_description("Total number of items handed to broadcast or watch receivers")
+                .build(),
+            dropped_total: meter
+                .u64_counter(format!("{name}_dropped_total"))
+                .with_description("Total number of queued items discarded without being received")
+                .build(),
+            evicted_total: meter
+                .u64_counter(format!("{name}_evicted_total"))
+                .with_description("Total number of items discarded by the overflow policy")
+                .build(),
+            lagged_total: meter
+                .u64_counter(format!("{name}_lagged_total"))
+                .with_description("Total number of items missed by lagging receivers")
+                .build(),
+            capacity: meter
+                .u64_gauge(format!("{name}_capacity"))
+                .with_description("Current maximum number of items the channel can hold")
+                .build(),
+            send_wait: meter
+                .f64_histogram(format!("{name}_send_wait_seconds"))
+                .with_description("Time senders waited for capacity")
+                .with_unit("s")
+                .build(),
+            queue_bytes: meter
+                .i64_up_down_counter(format!("{name}_queue_bytes"))
+                .with_description("Current total weight of the items in the channel")
+                .with_unit("By")
+                .build(),
+            bytes_total: meter
+                .u64_counter(format!("{name}_bytes_total"))
+                .with_description("Total weight of the items sent through the channel")
+                .with_unit("By")
+                .build(),
+            in_flight: meter
+                .i64_up_down_counter(format!("{name}_in_flight"))
+                .with_description("Current number of items being processed")
+                .build(),
+            processing_time: meter
+                .f64_histogram(format!("{name}_processing_seconds"))
+                .with_description("Time consumers spent processing items")
+                .with_unit("s")
+                .build(),
+            idle_time: meter
+                .f64_counter(format!("{name}_idle_seconds_total"))
+                .with_description("Total time receivers spent waiting on the empty channel")
+                .with_unit("s")
+                .build(),
+            busy_time: meter
+                .f64_counter(format!("{name}_busy_seconds_total"))
+                .with_description("Total time receivers spent between receives")
+                .with_unit("s")
+                .build(),
+            drained_total: meter
+                .u64_counter(format!("{name}_drained_total"))
+                .with_description("Total number of items handed to consumers by drains")
+                .build(),
+            dead_letters_total: meter
+                .u64_counter(format!("{name}_dead_letters_total"))
+                .with_description(
+                    "Total number of undelivered items sent to the dead-letter channel",
+                )
+                .build(),
+            dead_letters_lost_total: meter
+                .u64_counter(format!("{name}_dead_letters_lost_total"))
+                .with_description(
+                    "Total number of undelivered items lost to a full dead-letter channel",
+                )
+                .build(),
+            recv_clock: RecvClock::default(),
+            #[cfg(feature = "tracing")]
+            trace: ChannelTrace::default(),
+            queue_latency: None,
+        }
+    }
+
+    /// Record the time values spend queued in a `{name}_queue_latency_seconds` histogram
+    pub fn with_latency(mut self, meter: &Meter) -> Self {
+        self.queue_latency = Some(
+            meter
+                .f64_histogram(format!("{}_queue_latency_seconds", self.name))
+                .with_description("Time items spent queued before being received")
+                .with_unit("s")
+                .build(),
+        );
+        self
+    }
+
+    /// Set the tracing configuration of channels reporting to this observer
+    #[cfg(feature = "tracing")]
+    pub fn with_trace(mut self, trace: ChannelTrace) -> Self {
+        self.trace = trace;
+        self
+    }
+}
+
+impl ChannelObserver for OtelObserver {
+    fn on_send(&self) {
+        self.queue_size.add(1, &self.attributes);
+        self.total_messages.add(1, &self.attributes);
+    }
+
+    fn on_recv(&self, queued: Option<Duration>) {
+        self.queue_size.add(-1, &self.attributes);
+        if let (Some(histogram), Some(queued)) = (&self.queue_latency, queued) {
+            histogram.record(queued.as_secs_f64(), &self.attributes);
+        }
+        if let Some(busy) = self.recv_clock.received() {
+            self.busy_time.add(busy.as_secs_f64(), &self.attributes);
+        }
+    }
+
+    fn on_deliver(&self) {
+        self.delivered_total.add(1, &self.attributes);
+    }
+
+    fn on_drop(&self, count: usize) {
+        self.queue_size.add(-(count as i64), &self.attributes);
+        self.dropped_total.add(count as u64, &self.attributes);
+    }
+
+    fn on_evict(&self, queued: bool) {
+        self.evicted_total.add(1, &self.attributes);
+        if queued {
+            self.queue_size.add(-1, &self.attributes);
+        }
+    }
+
+    fn on_lag(&self, skipped: u64) {
+        self.lagged_total.add(skipped, &self.attributes);
+    }
+
+    fn on_wait(&self, waited: Duration) {
+        self.send_wait
+            .record(waited.as_secs_f64(), &self.attributes);
+    }
+
+    fn on_capacity(&self, capacity: usize) {
+        self.capacity.record(capacity as u64, &self.attributes);
+    }
+
+    fn on_send_bytes(&self, bytes: usize) {
+        self.queue_bytes.add(bytes as i64, &self.attributes);
+        self.bytes_total.add(bytes as u64, &self.attributes);
+    }
+
+    fn on_recv_bytes(&self, bytes: usize) {
+        self.queue_bytes.add(-(bytes as i64), &self.attributes);
+    }
+
+    fn on_process_start(&self) {
+        self.in_flight.add(1, &self.attributes);
+    }
+
+    fn on_processed(&self, elapsed: Duration) {
+        self.in_flight.add(-1, &self.attributes);
+        self.processing_time
+            .record(elapsed.as_secs_f64(), &self.attributes);
+    }
+
+    fn on_idle(&self, waited: Duration) {
+        self.idle_time.add(waited.as_secs_f64(), &self.attributes);
+        self.recv_clock.idled(waited);
+    }
+
+    fn on_drain(&self, processed: usize, _abandoned: usize) {
+        self.drained_total.add(processed as u64, &self.attributes);
+    }
+
+    fn on_dead_letter(&self, captured: bool) {
+        if captured {
+            self.dead_letters_total.add(1, &self.attributes);
+        } else {
+            self.dead_letters_lost_total.add(1, &self.attributes);
+        }
+    }
+
+    fn measures_latency(&self) -> bool {
+        self.queue_latency.is_some()
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[cfg(feature = "tracing")]
+    fn trace(&self) -> ChannelTrace {
+        self.trace
+    }
+}
@@ -0,0 +1,402 @@
+use crate::envelope::Envelope;
+use crate::error::SendError;
+use crate::guard::MessageGuard;
+#[cfg(feature = "prometheus")]
+use crate::metrics::ChannelMetrics;
+use crate::observer::{ChannelKind, ChannelObserver, HandleKind, Idle, Waiting};
+use crate::trace::{self, debug, Op, SpanContext};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::sync::Notify;
+
+/// Policy applied when a value is sent to a full channel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wait for capacity, like a regular bounded channel.
+    #[default]
+    Block,
+    /// Fail immediately with [`SendError::Full`].
+    Reject,
+    /// Evict the oldest queued value to make room for the new one.
+    DropOldest,
+    /// Discard the value being sent, keeping the queue untouched.
+    DropNewest,
+}
+
+impl OverflowPolicy {
+    /// Returns true if this policy discards values instead of failing or waiting
+    pub fn is_lossy(&self) -> bool {
+        matches!(
+            self,
+            OverflowPolicy::DropOldest | OverflowPolicy::DropNewest
+        )
+    }
+}
+
+/// A sender handle to a bounded channel with an overflow policy.
+///
+/// # Examples
+///
+/// ```rust
+/// use tokio_prometheus_metered_channel::{overflow_channel, ChannelMetrics, OverflowPolicy};
+/// use prometheus::Registry;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let registry = Registry::new();
+///     let metrics =
+///         ChannelMetrics::new_with_evicted("telemetry", "telemetry", &registry).unwrap();
+///
+///     let (tx, mut rx) = overflow_channel(2, OverflowPolicy::DropOldest, metrics);
+///
+///     // The third send evicts the oldest value instead of waiting
+///     tx.send(1).await.unwrap();
+///     tx.send(2).await.unwrap();
+///     tx.send(3).await.unwrap();
+///
+///     assert_eq!(rx.recv().await, Some(2));
+///     assert_eq!(rx.evicted_total().unwrap().get(), 1);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Sender<
+    T,
+    #[cfg(feature = "prometheus")] O: ChannelObserver = ChannelMetrics,
+    #[cfg(not(feature = "prometheus"))] O: ChannelObserver,
+> {
+    shared: Arc<Shared<T, O>>,
+}
+
+/// A receiver handle to a bounded channel with an overflow policy
+#[derive(Debug)]
+pub struct Receiver<
+    T,
+    #[cfg(feature = "prometheus")] O: ChannelObserver = ChannelMetrics,
+    #[cfg(not(feature = "prometheus"))] O: ChannelObserver,
+> {
+    shared: Arc<Shared<T, O>>,
+}
+
+#[derive(Debug)]
+struct Shared<T, O> {
+    state: Mutex<State<T>>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    not_empty: Notify,
+    not_full: Notify,
+    observer: O,
+}
+
+#[derive(Debug)]
+struct State<T> {
+    queue: VecDeque<Envelope<T>>,
+    senders: usize,
+    closed: bool,
+}
+
+/// Creates a new bounded channel applying `policy` when `buffer` values are queued
+///
+/// # Panics
+///
+/// Panics if `buffer` is 0.
+#[cfg(feature = "prometheus")]
+pub fn channel<T>(
+    buffer: usize,
+    policy: OverflowPolicy,
+    metrics: ChannelMetrics,
+) -> (Sender<T>, Receiver<T>) {
+    channel_with_observer(buffer, policy, metrics)
+}
+
+/// Creates a new bounded channel applying `policy` when `buffer` values are
+/// queued, reporting to `observer`
+///
+/// # Panics
+///
+/// Panics if `buffer` is 0.
+pub fn channel_with_observer<T, O: ChannelObserver>(
+    buffer: usize,
+    policy: OverflowPolicy,
+    observer: O,
+) -> (Sender<T, O>, Receiver<T, O>) {
+    assert!(
+        buffer > 0,
+        "overflow channel capacity must be greater than 0"
+    );
+    observer.on_open(ChannelKind::Overflow);
+    observer.on_capacity(buffer);
+
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::with_capacity(buffer),
+            senders: 1,
+            closed: false,
+        }),
+        capacity: buffer,
+        policy,
+        not_empty: Notify::new(),
+        not_full: Notify::new(),
+        observer,
+    });
+
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+impl<T, O: ChannelObserver> Shared<T, O> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn push(&self, value: T, context: SpanContext) -> Result<(), SendError<T>> {
+        let mut state = self.lock();
+        if state.closed {
+            return Err(SendError::Closed(value));
+        }
+
+        if state.queue.len() < self.capacity {
+            state
+                .queue
+                .push_back(Envelope::with_context(value, &self.observer, context));
+            drop(state);
+            self.observer.on_send();
+            self.not_empty.notify_one();
+            return Ok(());
+        }
+
+        match self.policy {
+            OverflowPolicy::Block | OverflowPolicy::Reject => Err(SendError::Full(value)),
+            OverflowPolicy::DropOldest => {
+                let evicted = state.queue.pop_front();
+                state
+                    .queue
+                    .push_back(Envelope::with_context(value, &self.observer, context));
+                drop(state);
+                self.observer.on_send();
+                self.record_eviction(true);
+                self.not_empty.notify_one();
+                drop(evicted);
+                Ok(())
+            }
+            OverflowPolicy::DropNewest => {
+                drop(state);
+                self.record_eviction(false);
+                Ok(())
+            }
+        }
+    }
+
+    fn pop(&self) -> Result<Envelope<T>, TryRecvError> {
+        let mut state = self.lock();
+        match state.queue.pop_front() {
+            Some(envelope) => {
+                drop(state);
+                self.not_full.notify_one();
+                Ok(envelope)
+            }
+            None if state.closed || state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    fn close(&self) {
+        self.lock().closed = true;
+        self.not_full.notify_waiters();
+    }
+
+    fn record_eviction(&self, queued: bool) {
+        debug!(policy = ?self.policy, "channel full, value evicted");
+        self.observer.on_evict(queued);
+    }
+}
+
+impl<T, O: ChannelObserver> Sender<T, O> {
+    /// Try to send a value without waiting for capacity.
+    ///
+    /// With a lossy policy this never fails with [`SendError::Full`].
+    pub fn try_send(&self, value: T) -> Result<(), SendError<T>> {
+        self.shared
+            .push(value, SpanContext::capture(&self.shared.observer))
+    }
+
+    /// Send a value, applying the channel's overflow policy if it is full
+    pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let (context, span) = trace::send_span(self.observer());
+        span.instrument(self.send_inner(value, context)).await
+    }
+
+    async fn send_inner(&self, mut value: T, context: SpanContext) -> Result<(), SendError<T>> {
+        if self.shared.policy != OverflowPolicy::Block {
+            return self.shared.push(value, context);
+        }
+
+        debug!("attempting to send value");
+        let mut waiting = None;
+        loop {
+            let notified = self.shared.not_full.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            match self.shared.push(value, context.clone()) {
+                Err(SendError::Full(returned)) => value = returned,
+                result => {
+                    if let Some(waiting) = waiting {
+                        Waiting::finish(waiting);
+                    }
+                    return result;
+                }
+            }
+
+            debug!("channel full, waiting for capacity");
+            waiting.get_or_insert_with(|| Waiting::new(&self.shared.observer));
+            notified.await;
+        }
+    }
+
+    /// Returns true if the channel has been closed
+    pub fn is_closed(&self) -> bool {
+        self.shared.lock().closed
+    }
+
+    /// Get the overflow policy of this channel
+    pub fn policy(&self) -> OverflowPolicy {
+        self.shared.policy
+    }
+
+    /// Get the maximum number of values the channel can hold
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity
+    }
+
+    /// Get the observer receiving the channel's events
+    pub fn observer(&self) -> &O {
+        &self.shared.observer
+    }
+}
+
+impl<T, O: ChannelObserver> Clone for Sender<T, O> {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+        self.shared.observer.on_attach(HandleKind::Sender);
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T, O: ChannelObserver> Drop for Sender<T, O> {
+    fn drop(&mut self) {
+        self.shared.observer.on_detach(HandleKind::Sender);
+        let mut state = self.shared.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            drop(state);
+            self.shared.not_empty.notify_one();
+        }
+    }
+}
+
+impl<T, O: ChannelObserver> Receiver<T, O> {
+    /// Receive the next value, or `None` once the channel is closed and empty
+    pub async fn recv(&mut self) -> Option<T> {
+        let span = trace::span(self.observer(), Op::Recv);
+        let msg = span
+            .instrument(async {
+                debug!("waiting to receive value");
+                let mut idle = None;
+                loop {
+                    let notified = self.shared.not_empty.notified();
+                    tokio::pin!(notified);
+                    notified.as_mut().enable();
+
+                    match self.shared.pop() {
+                        Ok(envelope) => {
+                            debug!("value received successfully");
+                            return Some(envelope);
+                        }
+                        Err(TryRecvError::Disconnected) => {
+                            debug!("channel closed, no more values");
+                            return None;
+                        }
+                        Err(TryRecvError::Empty) => {
+                            idle.get_or_insert_with(|| Idle::new(self.observer()));
+                            notified.await;
+                        }
+                    }
+                }
+            })
+            .await;
+        msg.map(|envelope| envelope.open(self.observer()))
+    }
+
+    /// Receive the next value in a guard recording how long the consumer
+    /// spends processing it
+    pub async fn recv_guarded(&mut self) -> Option<MessageGuard<T, O>>
+    where
+        O: Clone,
+    {
+        let value = self.recv().await?;
+        Some(MessageGuard::new(value, self.observer().clone()))
+    }
+
+    /// Try to receive a value without waiting
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.shared
+            .pop()
+            .map(|envelope| envelope.open(self.observer()))
+    }
+
+    /// Close the channel, letting buffered values still be received
+    pub fn close(&mut self) {
+        self.shared.close();
+        self.shared.observer.on_close();
+    }
+
+    /// Get the number of values currently queued
+    pub fn len(&self) -> usize {
+        self.shared.lock().queue.len()
+    }
+
+    /// Returns true if no values are queued
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the observer receiving the channel's events
+    pub fn observer(&self) -> &O {
+        &self.shared.observer
+    }
+}
+
+#[cfg(feature = "prometheus")]
+impl<T> Receiver<T, ChannelMetrics> {
+    /// Get the total messages counter if enabled
+    pub fn total_messages(&self) -> Option<&prometheus::IntCounter> {
+        self.shared.observer.total_messages.as_ref()
+    }
+
+    /// Get the evicted messages counter if enabled
+    pub fn evicted_total(&self) -> Option<&prometheus::IntCounter> {
+        self.shared.observer.evicted_total.as_ref()
+    }
+}
+
+impl<T, O: ChannelObserver> Drop for Receiver<T, O> {
+    fn drop(&mut self) {
+        let remaining = {
+            let mut state = self.shared.lock();
+            state.closed = true;
+            std::mem::take(&mut state.queue)
+        };
+        if !remaining.is_empty() {
+            self.shared.observer.on_drop(remaining.len());
+        }
+        self.shared.observer.on_detach(HandleKind::Receiver);
+        self.shared.not_full.notify_waiters();
+    }
+}
@@ -0,0 +1,223 @@
+use crate::channel::Sender;
+use crate::error::SendError;
+use crate::metrics::{ChannelMetrics, ThrottleMetrics};
+use crate::observer::ChannelObserver;
+use crate::trace::debug;
+use futures::future::{self, Either};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+/// An mpsc sender throttled by a token bucket.
+///
+/// Each send first waits for a rate token, then for channel capacity. Both
+/// waits are recorded in separate histograms so throttling can be told apart
+/// from backpressure. Clones share the same bucket, and the rate can be
+/// changed at runtime with [`set_rate`](Self::set_rate), which also wakes the
+/// sends waiting for a token. A token is given back if the send then fails.
+///
+/// # Examples
+///
+/// ```rust
+/// use tokio_prometheus_metered_channel::{mpsc_channel, ChannelMetrics, RateLimitedSender, ThrottleMetrics};
+/// use prometheus::Registry;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let registry = Registry::new();
+///     let metrics = ChannelMetrics::new_basic("example", "example channel", &registry).unwrap();
+///     let throttle = ThrottleMetrics::new("example", "example", &registry).unwrap();
+///
+///     let (tx, mut rx) = mpsc_channel(10, metrics);
+///     // 100 messages per second, with bursts of up to 5
+///     let tx = RateLimitedSender::new(tx, 100.0, 5, throttle);
+///
+///     tx.send(42).await.unwrap();
+///     assert_eq!(rx.recv().await, Some(42));
+/// }
+/// ```
+#[derive(Debug)]
+pub struct RateLimitedSender<T, O: ChannelObserver = ChannelMetrics> {
+    inner: Sender<T, O>,
+    bucket: Arc<Mutex<TokenBucket>>,
+    rate_changed: Arc<Notify>,
+    metrics: ThrottleMetrics,
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64, burst: u32) -> Self {
+        Self {
+            rate,
+            burst: f64::from(burst),
+            tokens: f64::from(burst),
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.last_refill = now;
+    }
+
+    /// Take a token, or return how long to wait until one is available
+    fn try_acquire(&mut self) -> Result<(), Duration> {
+        self.refill();
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+        }
+    }
+
+    /// Give back a token taken for a send that failed
+    fn release(&mut self) {
+        self.tokens = (self.tokens + 1.0).min(self.burst);
+    }
+}
+
+fn validate(rate: f64, burst: u32) {
+    assert!(
+        rate.is_finite() && rate > 0.0,
+        "rate must be a positive number of messages per second"
+    );
+    assert!(burst > 0, "burst must be greater than 0");
+}
+
+impl<T, O: ChannelObserver> RateLimitedSender<T, O> {
+    /// Wrap `sender` so that it sends at most `rate` messages per second,
+    /// allowing bursts of up to `burst` messages
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is not a positive finite number or `burst` is 0.
+    pub fn new(sender: Sender<T, O>, rate: f64, burst: u32, metrics: ThrottleMetrics) -> Self {
+        validate(rate, burst);
+        Self {
+            inner: sender,
+            bucket: Arc::new(Mutex::new(TokenBucket::new(rate, burst))),
+            rate_changed: Arc::new(Notify::new()),
+            metrics,
+        }
+    }
+
+    fn bucket(&self) -> MutexGuard<'_, TokenBucket> {
+        self.bucket.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Send a value, waiting for a rate token and then for capacity.
+    ///
+    /// Fails without waiting for a token once the channel is closed.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip(self, value), level = "debug")
+    )]
+    pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        if self.inner.is_closed() {
+            return Err(SendError::Closed(value));
+        }
+
+        let start = Instant::now();
+        let mut throttled = false;
+        loop {
+            let rate_changed = self.rate_changed.notified();
+            tokio::pin!(rate_changed);
+            rate_changed.as_mut().enable();
+
+            let wait = match self.bucket().try_acquire() {
+                Ok(()) => break,
+                Err(wait) => wait,
+            };
+            if !throttled {
+                throttled = true;
+                self.metrics.throttled_total.inc();
+                debug!(?wait, "send throttled, waiting for rate token");
+            }
+
+            // Wake up early if the rate changes or the channel closes
+            let sleep = tokio::time::sleep(wait);
+            let closed = self.inner.closed();
+            tokio::pin!(sleep, closed);
+            let woken = future::select(sleep, future::select(rate_changed, closed)).await;
+            if let Either::Right((Either::Right(_), _)) = woken {
+                return Err(SendError::Closed(value));
+            }
+        }
+        self.metrics
+            .throttle_wait
+            .observe(start.elapsed().as_secs_f64());
+
+        if self.inner.is_closed() {
+            self.bucket().release();
+            return Err(SendError::Closed(value));
+        }
+
+        let start = Instant::now();
+        let result = self.inner.send(value).await;
+        self.metrics
+            .capacity_wait
+            .observe(start.elapsed().as_secs_f64());
+        if result.is_err() {
+            self.bucket().release();
+        }
+        result
+    }
+
+    /// Change the rate and burst size, effective for subsequent sends
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is not a positive finite number or `burst` is 0.
+    pub fn set_rate(&self, rate: f64, burst: u32) {
+        validate(rate, burst);
+        let mut bucket = self.bucket();
+        bucket.refill();
+        bucket.rate = rate;
+        bucket.burst = f64::from(burst);
+        bucket.tokens = bucket.tokens.min(bucket.burst);
+        drop(bucket);
+        self.rate_changed.notify_waiters();
+    }
+
+    /// Get the current rate, in messages per second
+    pub fn rate(&self) -> f64 {
+        self.bucket().rate
+    }
+
+    /// Get the current burst size
+    pub fn burst(&self) -> u32 {
+        self.bucket().burst as u32
+    }
+
+    /// Returns true if the channel has been closed
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    /// Get the wrapped sender, bypassing the rate limit
+    pub fn inner(&self) -> &Sender<T, O> {
+        &self.inner
+    }
+}
+
+impl<T, O: ChannelObserver + Clone> Clone for RateLimitedSender<T, O> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            bucket: self.bucket.clone(),
+            rate_changed: self.rate_changed.clone(),
+            metrics: self.metrics.clone(),
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::sync::{Mutex, MutexGuard};
+use tokio::time::Instant;
+
+/// Averaging windows of the rates, in seconds
+const WINDOWS: [f64; 3] = [1.0, 10.0, 60.0];
+
+/// Exponentially weighted moving averages of an event rate, in events per second
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rates {
+    /// Rate averaged over about the last second
+    pub one_second: f64,
+    /// Rate averaged over about the last ten seconds
+    pub ten_seconds: f64,
+    /// Rate averaged over about the last minute
+    pub one_minute: f64,
+}
+
+/// In-process throughput readings of a channel, read with
+/// [`ChannelMetrics::throughput`](crate::ChannelMetrics::throughput)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Throughput {
+    /// Rate of values sent through the channel
+    pub send: Rates,
+    /// Rate of values received from the channel
+    pub recv: Rates,
+    /// Current number of items in the channel
+    pub depth: i64,
+}
+
+/// Send and receive rates shared by all the handles of a channel
+#[derive(Debug)]
+pub(crate) struct RateMeter {
+    send: Mutex<Ewma>,
+    recv: Mutex<Ewma>,
+}
+
+impl RateMeter {
+    pub(crate) fn new() -> Self {
+        Self {
+            send: Mutex::new(Ewma::new()),
+            recv: Mutex::new(Ewma::new()),
+        }
+    }
+
+    pub(crate) fn sent(&self) {
+        lock(&self.send).record(Instant::now());
+    }
+
+    pub(crate) fn received(&self) {
+        lock(&self.recv).record(Instant::now());
+    }
+
+    /// Read the current rates, with the given channel depth
+    pub(crate) fn throughput(&self, depth: i64) -> Throughput {
+        let now = Instant::now();
+        Throughput {
+            send: lock(&self.send).read(now),
+            recv: lock(&self.recv).read(now),
+            depth,
+        }
+    }
+}
+
+fn lock(ewma: &Mutex<Ewma>) -> MutexGuard<'_, Ewma> {
+    ewma.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Continuously decaying averages: each event adds `1 / window` to a rate
+/// that decays by `e^(-elapsed / window)`, converging on the event rate
+#[derive(Debug)]
+struct Ewma {
+    rates: [f64; 3],
+    updated: Instant,
+}
+
+impl Ewma {
+    fn new() -> Self {
+        Self {
+            rates: [0.0; 3],
+            updated: Instant::now(),
+        }
+    }
+
+    fn decay(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        for (rate, window) in self.rates.iter_mut().zip(WINDOWS) {
+            *rate *= (-elapsed / window).exp();
+        }
+        self.updated = now;
+    }
+
+    fn record(&mut self, now: Instant) {
+        self.decay(now);
+        for (rate, window) in self.rates.iter_mut().zip(WINDOWS) {
+            *rate += 1.0 / window;
+        }
+    }
+
+    fn read(&mut self, now: Instant) -> Rates {
+        self.decay(now);
+        let [one_second, ten_seconds, one_minute] = self.rates;
+        Rates {
+            one_second,
+            ten_seconds,
+            one_minute,
+        }
+    }
+}
@@ -0,0 +1,451 @@
+use crate::error::SendError;
+use crate::guard::MessageGuard;
+#[cfg(feature = "prometheus")]
+use crate::metrics::ChannelMetrics;
+use crate::observer::{ChannelKind, ChannelObserver, HandleKind, Idle, Waiting};
+use crate::trace::{self, debug, error, Op, SpanContext};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::sync::{mpsc, Semaphore, TryAcquireError};
+use tokio::time::Instant;
+
+/// A sender handle to a resizable channel.
+///
+/// # Examples
+///
+/// ```rust
+/// use tokio_prometheus_metered_channel::{resizable_channel, ChannelMetrics};
+/// use prometheus::Registry;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let registry = Registry::new();
+///     let metrics = ChannelMetrics::new_with_capacity("example", "example", &registry).unwrap();
+///
+///     let (tx, mut rx) = resizable_channel(1, metrics);
+///     tx.send(1).await.unwrap();
+///     assert!(tx.try_send(2).is_err());
+///
+///     // Growing the channel makes room without restarting anything
+///     tx.resize_handle().set_capacity(2);
+///     tx.try_send(2).unwrap();
+///
+///     assert_eq!(rx.recv().await, Some(1));
+///     assert_eq!(rx.recv().await, Some(2));
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Sender<
+    T,
+    #[cfg(feature = "prometheus")] O: ChannelObserver = ChannelMetrics,
+    #[cfg(not(feature = "prometheus"))] O: ChannelObserver,
+> {
+    inner: mpsc::UnboundedSender<(T, Instant, SpanContext)>,
+    capacity: Arc<Capacity>,
+    observer: O,
+}
+
+/// A receiver handle to a resizable channel
+#[derive(Debug)]
+pub struct Receiver<
+    T,
+    #[cfg(feature = "prometheus")] O: ChannelObserver = ChannelMetrics,
+    #[cfg(not(feature = "prometheus"))] O: ChannelObserver,
+> {
+    inner: mpsc::UnboundedReceiver<(T, Instant, SpanContext)>,
+    capacity: Arc<Capacity>,
+    observer: O,
+}
+
+/// A handle for inspecting and changing the capacity of a resizable channel.
+///
+/// Unlike a [`Sender`], holding a handle does not keep the channel open.
+#[derive(Debug, Clone)]
+pub struct ResizeHandle {
+    capacity: Arc<Capacity>,
+}
+
+struct Capacity {
+    semaphore: Semaphore,
+    state: Mutex<CapacityState>,
+    observer: Arc<dyn ChannelObserver>,
+}
+
+impl fmt::Debug for Capacity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Capacity")
+            .field("semaphore", &self.semaphore)
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+struct CapacityState {
+    capacity: usize,
+    /// Permits still to be withheld after a shrink below the current queue length
+    debt: usize,
+    /// Queue latency accumulated since the last [`ResizeHandle::take_latency`]
+    latency_sum: Duration,
+    latency_count: u64,
+}
+
+/// Creates a new resizable channel with an initial capacity of `buffer`
+///
+/// # Panics
+///
+/// Panics if `buffer` is 0 or larger than [`Semaphore::MAX_PERMITS`].
+#[cfg(feature = "prometheus")]
+pub fn channel<T>(buffer: usize, metrics: ChannelMetrics) -> (Sender<T>, Receiver<T>) {
+    channel_with_observer(buffer, metrics)
+}
+
+/// Creates a new resizable channel with an initial capacity of `buffer`, reporting to `observer`
+///
+/// # Panics
+///
+/// Panics if `buffer` is 0 or larger than [`Semaphore::MAX_PERMITS`].
+pub fn channel_with_observer<T, O: ChannelObserver + Clone + 'static>(
+    buffer: usize,
+    observer: O,
+) -> (Sender<T, O>, Receiver<T, O>) {
+    validate(buffer);
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    observer.on_open(ChannelKind::Resizable);
+    observer.on_capacity(buffer);
+    let capacity = Arc::new(Capacity {
+        semaphore: Semaphore::new(buffer),
+        state: Mutex::new(CapacityState {
+            capacity: buffer,
+            debt: 0,
+            latency_sum: Duration::ZERO,
+            latency_count: 0,
+        }),
+        observer: Arc::new(observer.clone()),
+    });
+
+    (
+        Sender {
+            inner: tx,
+            capacity: capacity.clone(),
+            observer: observer.clone(),
+        },
+        Receiver {
+            inner: rx,
+            capacity,
+            observer,
+        },
+    )
+}
+
+fn validate(capacity: usize) {
+    assert!(capacity > 0, "channel capacity must be greater than 0");
+    assert!(
+        capacity <= Semaphore::MAX_PERMITS,
+        "channel capacity cannot exceed {}",
+        Semaphore::MAX_PERMITS
+    );
+}
+
+impl Capacity {
+    fn lock(&self) -> MutexGuard<'_, CapacityState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Return a slot freed by a value that spent `latency` in the queue
+    fn dequeued(&self, latency: Duration) {
+        let mut state = self.lock();
+        state.latency_sum += latency;
+        state.latency_count += 1;
+        self.release_locked(&mut state);
+    }
+
+    /// Return a slot freed by an unsent value
+    fn release(&self) {
+        self.release_locked(&mut self.lock());
+    }
+
+    fn release_locked(&self, state: &mut CapacityState) {
+        if state.debt > 0 {
+            state.debt -= 1;
+        } else {
+            self.semaphore.add_permits(1);
+        }
+    }
+
+    fn resize(&self, new_capacity: usize) {
+        validate(new_capacity);
+        let mut state = self.lock();
+        let old_capacity = state.capacity;
+
+        if new_capacity > old_capacity {
+            let growth = new_capacity - old_capacity;
+            let repaid = growth.min(state.debt);
+            state.debt -= repaid;
+            // Adding permits wakes senders waiting for capacity
+            self.semaphore.add_permits(growth - repaid);
+        } else {
+            let shrink = old_capacity - new_capacity;
+            let forgotten = self.semaphore.forget_permits(shrink);
+            state.debt += shrink - forgotten;
+        }
+
+        state.capacity = new_capacity;
+        self.observer.on_capacity(new_capacity);
+        debug!(old_capacity, new_capacity, "channel resized");
+    }
+}
+
+impl<T, O: ChannelObserver> Sender<T, O> {
+    /// Try to send a value without waiting for capacity
+    pub fn try_send(&self, value: T) -> Result<(), SendError<T>> {
+        match self.capacity.semaphore.try_acquire() {
+            Ok(permit) => permit.forget(),
+            Err(TryAcquireError::NoPermits) => return Err(SendError::Full(value)),
+            Err(TryAcquireError::Closed) => return Err(SendError::Closed(value)),
+        }
+        self.enqueue(value, SpanContext::capture(&self.observer))
+    }
+
+    /// Send a value, waiting for capacity if needed
+    pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let (context, span) = trace::send_span(self.observer());
+        span.instrument(self.send_inner(value, context)).await
+    }
+
+    async fn send_inner(&self, value: T, context: SpanContext) -> Result<(), SendError<T>> {
+        debug!("attempting to send value");
+        let acquired = match self.capacity.semaphore.try_acquire() {
+            Ok(permit) => Ok(permit),
+            Err(TryAcquireError::NoPermits) => {
+                debug!("channel full, waiting for capacity");
+                let waiting = Waiting::new(&self.observer);
+                let permit = self.capacity.semaphore.acquire().await;
+                waiting.finish();
+                permit.map_err(|_| ())
+            }
+            Err(TryAcquireError::Closed) => Err(()),
+        };
+        match acquired {
+            Ok(permit) => permit.forget(),
+            Err(()) => {
+                error!("failed to send value, channel closed");
+                return Err(SendError::Closed(value));
+            }
+        }
+        let result = self.enqueue(value, context);
+        if result.is_ok() {
+            debug!("value sent successfully");
+        }
+        result
+    }
+
+    fn enqueue(&self, value: T, context: SpanContext) -> Result<(), SendError<T>> {
+        match self.inner.send((value, Instant::now(), context)) {
+            Ok(()) => {
+                self.observer.on_send();
+                Ok(())
+            }
+            Err(err) => {
+                self.capacity.release();
+                Err(SendError::Closed((err.0).0))
+            }
+        }
+    }
+
+    /// Returns true if the channel has been closed
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    /// Get the current capacity of the channel
+    pub fn capacity(&self) -> usize {
+        self.capacity.lock().capacity
+    }
+
+    /// Get a handle for resizing the channel
+    pub fn resize_handle(&self) -> ResizeHandle {
+        ResizeHandle {
+            capacity: self.capacity.clone(),
+        }
+    }
+
+    /// Get the observer receiving the channel's events
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+}
+
+impl<T, O: ChannelObserver + Clone> Clone for Sender<T, O> {
+    fn clone(&self) -> Self {
+        self.observer.on_attach(HandleKind::Sender);
+        Self {
+            inner: self.inner.clone(),
+            capacity: self.capacity.clone(),
+            observer: self.observer.clone(),
+        }
+    }
+}
+
+impl<T, O: ChannelObserver> Receiver<T, O> {
+    /// Receive the next value
+    pub async fn recv(&mut self) -> Option<T> {
+        let span = trace::span(self.observer(), Op::Recv);
+        let msg = span
+            .instrument(async {
+                debug!("waiting to receive value");
+                let msg = match self.inner.try_recv() {
+                    Ok(msg) => Some(msg),
+                    Err(TryRecvError::Disconnected) => None,
+                    Err(TryRecvError::Empty) => {
+                        let _idle = Idle::new(&self.observer);
+                        self.inner.recv().await
+                    }
+                };
+                if msg.is_some() {
+                    debug!("value received successfully");
+                } else {
+                    debug!("channel closed, no more values");
+                }
+                msg
+            })
+            .await;
+        let (msg, enqueued, context) = msg?;
+        self.dequeued(enqueued, context);
+        Some(msg)
+    }
+
+    /// Receive the next value in a guard recording how long the consumer
+    /// spends processing it
+    pub async fn recv_guarded(&mut self) -> Option<MessageGuard<T, O>>
+    where
+        O: Clone,
+    {
+        let value = self.recv().await?;
+        Some(MessageGuard::new(value, self.observer().clone()))
+    }
+
+    /// Try to receive a value without waiting
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let (msg, enqueued, context) = self.inner.try_recv()?;
+        self.dequeued(enqueued, context);
+        Ok(msg)
+    }
+
+    fn dequeued(&self, enqueued: Instant, context: SpanContext) {
+        let latency = enqueued.elapsed();
+        self.observer.on_recv(Some(latency));
+        context.link();
+        self.capacity.dequeued(latency);
+    }
+
+    /// Close the channel, letting buffered values still be received
+    pub fn close(&mut self) {
+        self.capacity.semaphore.close();
+        self.inner.close();
+        self.observer.on_close();
+    }
+
+    /// Close the channel and discard the values still queued, reporting them
+    /// as dropped
+    fn discard(&mut self) -> usize {
+        self.capacity.semaphore.close();
+        self.inner.close();
+        let mut count = 0;
+        while self.inner.try_recv().is_ok() {
+            count += 1;
+            self.capacity.release();
+        }
+        if count > 0 {
+            self.observer.on_drop(count);
+        }
+        count
+    }
+
+    /// Get the current capacity of the channel
+    pub fn capacity(&self) -> usize {
+        self.capacity.lock().capacity
+    }
+
+    /// Get a handle for resizing the channel
+    pub fn resize_handle(&self) -> ResizeHandle {
+        ResizeHandle {
+            capacity: self.capacity.clone(),
+        }
+    }
+
+    /// Get the observer receiving the channel's events
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+}
+
+#[cfg(feature = "prometheus")]
+impl<T> Receiver<T, ChannelMetrics> {
+    /// Get the total messages counter if enabled
+    pub fn total_messages(&self) -> Option<&prometheus::IntCounter> {
+        self.observer.total_messages.as_ref()
+    }
+}
+
+impl<T, O: ChannelObserver> Drop for Sender<T, O> {
+    fn drop(&mut self) {
+        self.observer.on_detach(HandleKind::Sender);
+    }
+}
+
+impl<T, O: ChannelObserver> Drop for Receiver<T, O> {
+    fn drop(&mut self) {
+        // Report the values still queued instead of letting them vanish
+        self.discard();
+        self.observer.on_detach(HandleKind::Receiver);
+    }
+}
+
+impl ResizeHandle {
+    /// Get the current capacity of the channel
+    pub fn capacity(&self) -> usize {
+        self.capacity.lock().capacity
+    }
+
+    /// Set the capacity of the channel.
+    ///
+    /// Growing the channel immediately wakes senders waiting for capacity.
+    /// Shrinking below the number of queued values never drops them; new
+    /// sends wait until the queue has drained below the new capacity.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0 or larger than [`Semaphore::MAX_PERMITS`].
+    pub fn set_capacity(&self, capacity: usize) {
+        self.capacity.resize(capacity)
+    }
+
+    /// Get the number of values currently queued, including sends in progress
+    pub fn len(&self) -> usize {
+        let state = self.capacity.lock();
+        (state.capacity + state.debt).saturating_sub(self.capacity.semaphore.available_permits())
+    }
+
+    /// Returns true if no values are queued
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns true if the receiver has been closed or dropped
+    pub fn is_closed(&self) -> bool {
+        self.capacity.semaphore.is_closed()
+    }
+
+    /// Take the mean time values spent queued since the previous call
+    #[cfg(feature = "controller")]
+    pub(crate) fn take_latency(&self) -> Option<Duration> {
+        let mut state = self.capacity.lock();
+        let count = std::mem::take(&mut state.latency_count);
+        let sum = std::mem::take(&mut state.latency_sum);
+        (count > 0).then(|| sum.div_f64(count as f64))
+    }
+}
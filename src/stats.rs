@@ -0,0 +1,266 @@
+use crate::observer::{ChannelKind, HandleKind};
+#[cfg(feature = "tasks")]
+use crate::rates::{RateMeter, Throughput};
+#[cfg(feature = "tasks")]
+use crate::tasks::TaskLinks;
+use prometheus::IntGauge;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::Duration;
+
+/// A snapshot of the state of a live channel.
+///
+/// Each [`ChannelMetrics`](crate::ChannelMetrics) created with stats tracks its
+/// own channel, so several live channels can share a name. Channels sharing
+/// their metrics, like those created with
+/// [`ChannelMetrics::get_or_register`](crate::ChannelMetrics::get_or_register),
+/// share their stats.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelStats {
+    /// Fully qualified prefix of the channel's metric names
+    pub name: String,
+    /// Constant labels of the channel's metrics
+    pub labels: BTreeMap<String, String>,
+    /// Family of the channel, unknown until a channel is created with the metrics
+    pub kind: Option<ChannelKind>,
+    /// Current maximum number of items the channel can hold, if bounded
+    pub capacity: Option<usize>,
+    /// Current number of items in the channel
+    pub len: usize,
+    /// Number of live sender handles
+    pub senders: usize,
+    /// Number of live receiver handles
+    pub receivers: usize,
+    /// Total number of items sent through the channel
+    pub sent: u64,
+    /// Total number of items received from the channel
+    pub received: u64,
+    /// Total number of queued items discarded without being received
+    pub dropped: u64,
+    /// Whether the channel was closed or lost all its senders or receivers
+    pub closed: bool,
+}
+
+/// List the live channels created with [stats](crate::ChannelMetricsOpts::with_stats),
+/// sorted by name and labels
+///
+/// # Examples
+///
+/// ```rust
+/// use tokio_prometheus_metered_channel::{live_channels, MeteredChannelBuilder};
+/// use prometheus::Registry;
+///
+/// let (tx, _rx) = MeteredChannelBuilder::new()
+///     .name("jobs")
+///     .capacity(10)
+///     .registry(&Registry::new())
+///     .with_stats()
+///     .build_mpsc::<u64>()
+///     .unwrap();
+/// tx.try_send(1).unwrap();
+///
+/// let jobs = live_channels().into_iter().find(|stats| stats.name == "jobs").unwrap();
+/// assert_eq!(jobs.capacity, Some(10));
+/// assert_eq!((jobs.len, jobs.senders, jobs.receivers), (1, 1, 1));
+/// ```
+pub fn live_channels() -> Vec<ChannelStats> {
+    let mut stats: Vec<_> = trackers()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .map(|tracker| tracker.stats())
+        .collect();
+    stats.sort_by(|a, b| (&a.name, &a.labels).cmp(&(&b.name, &b.labels)));
+    stats
+}
+
+/// Get the stats of the live channels named `name` created with
+/// [stats](crate::ChannelMetricsOpts::with_stats), sorted by labels.
+///
+/// Channels can share a name when their metrics differ in constant labels or
+/// are registered with different registries.
+pub fn live_channel(name: &str) -> Vec<ChannelStats> {
+    let mut stats: Vec<_> = trackers()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .filter(|tracker| &*tracker.name == name)
+        .map(|tracker| tracker.stats())
+        .collect();
+    stats.sort_by(|a, b| a.labels.cmp(&b.labels));
+    stats
+}
+
+/// Trackers of the channels listed by [`live_channels`], dropped with the
+/// last metrics referencing them
+static TRACKERS: Mutex<Vec<Weak<Tracker>>> = Mutex::new(Vec::new());
+
+fn trackers() -> MutexGuard<'static, Vec<Weak<Tracker>>> {
+    TRACKERS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Get the trackers of every live channel
+#[cfg(any(feature = "watchdog", feature = "tasks"))]
+pub(crate) fn live_trackers() -> Vec<Arc<Tracker>> {
+    trackers().iter().filter_map(Weak::upgrade).collect()
+}
+
+/// Live state of a channel, fed by the events of its metrics
+#[derive(Debug)]
+pub(crate) struct Tracker {
+    name: Arc<str>,
+    labels: BTreeMap<String, String>,
+    kind: Mutex<Option<ChannelKind>>,
+    capacity: AtomicUsize,
+    len: AtomicI64,
+    senders: AtomicUsize,
+    receivers: AtomicUsize,
+    sent: AtomicU64,
+    received: AtomicU64,
+    dropped: AtomicU64,
+    closed: AtomicBool,
+    stall: Mutex<Option<StallCheck>>,
+    #[cfg(feature = "tasks")]
+    tasks: TaskLinks,
+    #[cfg(feature = "tasks")]
+    rates: Mutex<Option<Arc<RateMeter>>>,
+}
+
+/// How long a channel may hold values without receive progress before the
+/// [`Watchdog`](crate::Watchdog) flags it, and the gauge it sets
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "watchdog"), allow(dead_code))]
+pub(crate) struct StallCheck {
+    pub(crate) threshold: Duration,
+    pub(crate) gauge: IntGauge,
+}
+
+impl Tracker {
+    /// List a new channel named `name` with the constant labels `labels`
+    pub(crate) fn register(name: Arc<str>, labels: BTreeMap<String, String>) -> Arc<Self> {
+        let mut trackers = trackers();
+        trackers.retain(|tracker| tracker.strong_count() > 0);
+
+        let tracker = Arc::new(Self {
+            name,
+            labels,
+            kind: Mutex::new(None),
+            capacity: AtomicUsize::new(0),
+            len: AtomicI64::new(0),
+            senders: AtomicUsize::new(0),
+            receivers: AtomicUsize::new(0),
+            sent: AtomicU64::new(0),
+            received: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            closed: AtomicBool::new(false),
+            stall: Mutex::new(None),
+            #[cfg(feature = "tasks")]
+            tasks: TaskLinks::default(),
+            #[cfg(feature = "tasks")]
+            rates: Mutex::new(None),
+        });
+        trackers.push(Arc::downgrade(&tracker));
+        tracker
+    }
+
+    pub(crate) fn opened(&self, kind: ChannelKind) {
+        *self.kind.lock().unwrap_or_else(|e| e.into_inner()) = Some(kind);
+        self.attached(HandleKind::Sender);
+        self.attached(HandleKind::Receiver);
+    }
+
+    pub(crate) fn attached(&self, handle: HandleKind) {
+        self.handles(handle).fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn detached(&self, handle: HandleKind) {
+        self.handles(handle).fetch_sub(1, Ordering::Relaxed);
+    }
+
+    fn handles(&self, handle: HandleKind) -> &AtomicUsize {
+        match handle {
+            HandleKind::Sender => &self.senders,
+            HandleKind::Receiver => &self.receivers,
+        }
+    }
+
+    pub(crate) fn sent(&self) {
+        self.len.fetch_add(1, Ordering::Relaxed);
+        self.sent.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "tasks")]
+        self.tasks.sent();
+    }
+
+    pub(crate) fn received(&self) {
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        self.received.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "tasks")]
+        self.tasks.received();
+    }
+
+    pub(crate) fn dropped(&self, count: usize) {
+        self.len.fetch_sub(count as i64, Ordering::Relaxed);
+        self.dropped.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn evicted(&self) {
+        self.len.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn set_capacity(&self, capacity: usize) {
+        self.capacity.store(capacity, Ordering::Relaxed);
+    }
+
+    pub(crate) fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+
+    /// Have the watchdog flag the channel in `gauge` once it stalls for `threshold`
+    pub(crate) fn check_stalls(&self, threshold: Duration, gauge: IntGauge) {
+        *self.stall.lock().unwrap_or_else(|e| e.into_inner()) =
+            Some(StallCheck { threshold, gauge });
+    }
+
+    #[cfg(feature = "watchdog")]
+    pub(crate) fn stall_check(&self) -> Option<StallCheck> {
+        self.stall.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Named tasks using the channel
+    #[cfg(feature = "tasks")]
+    pub(crate) fn tasks(&self) -> &TaskLinks {
+        &self.tasks
+    }
+
+    /// Report the rates of the channel along with its topology
+    #[cfg(feature = "tasks")]
+    pub(crate) fn measure_rates(&self, rates: Arc<RateMeter>) {
+        *self.rates.lock().unwrap_or_else(|e| e.into_inner()) = Some(rates);
+    }
+
+    #[cfg(feature = "tasks")]
+    pub(crate) fn throughput(&self, depth: i64) -> Option<Throughput> {
+        let rates = self.rates.lock().unwrap_or_else(|e| e.into_inner());
+        rates.as_ref().map(|rates| rates.throughput(depth))
+    }
+
+    pub(crate) fn stats(&self) -> ChannelStats {
+        let kind = *self.kind.lock().unwrap_or_else(|e| e.into_inner());
+        let senders = self.senders.load(Ordering::Relaxed);
+        let receivers = self.receivers.load(Ordering::Relaxed);
+        let capacity = self.capacity.load(Ordering::Relaxed);
+        ChannelStats {
+            name: self.name.to_string(),
+            labels: self.labels.clone(),
+            kind,
+            capacity: (capacity > 0).then_some(capacity),
+            len: self.len.load(Ordering::Relaxed).max(0) as usize,
+            senders,
+            receivers,
+            sent: self.sent.load(Ordering::Relaxed),
+            received: self.received.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            closed: self.closed.load(Ordering::Relaxed)
+                || (kind.is_some() && (senders == 0 || receivers == 0)),
+        }
+    }
+}
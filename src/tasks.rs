@@ -0,0 +1,109 @@
+use std::future::Future;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+tokio::task_local! {
+    static TASK: Arc<str>;
+}
+
+/// Run `future` as the task named `name`.
+///
+/// Channels created with [stats](crate::ChannelMetricsOpts::with_stats) note
+/// which named tasks send to and receive from them, and which are waiting for
+/// capacity, so that [`find_deadlocks`](crate::find_deadlocks) can follow
+/// blocked sends from task to task. Names should be unique: tasks sharing a
+/// name are tracked as one.
+///
+/// # Examples
+///
+/// ```rust
+/// use tokio_prometheus_metered_channel::{track_task, MeteredChannelBuilder};
+/// use prometheus::Registry;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let (tx, mut rx) = MeteredChannelBuilder::new()
+///         .name("events")
+///         .capacity(10)
+///         .registry(&Registry::new())
+///         .with_stats()
+///         .build_mpsc()
+///         .unwrap();
+///
+///     let producer = tokio::spawn(track_task("producer", async move {
+///         tx.send(1).await.unwrap();
+///     }));
+///     producer.await.unwrap();
+///     track_task("consumer", async move { rx.recv().await }).await;
+/// }
+/// ```
+pub fn track_task<F: Future>(name: &str, future: F) -> impl Future<Output = F::Output> {
+    TASK.scope(Arc::from(name), future)
+}
+
+/// Name of the tracked task currently running, if any
+fn current_task() -> Option<Arc<str>> {
+    TASK.try_with(Arc::clone).ok()
+}
+
+/// Named tasks sending to, receiving from and waiting on a channel
+#[derive(Debug, Default)]
+pub(crate) struct TaskLinks(Mutex<Links>);
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Links {
+    pub(crate) producers: Vec<Arc<str>>,
+    pub(crate) consumers: Vec<Arc<str>>,
+    /// Tasks currently waiting for capacity, once per waiting send
+    pub(crate) waiting: Vec<Arc<str>>,
+}
+
+impl TaskLinks {
+    fn lock(&self) -> MutexGuard<'_, Links> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn sent(&self) {
+        if let Some(task) = current_task() {
+            add(&mut self.lock().producers, task);
+        }
+    }
+
+    pub(crate) fn received(&self) {
+        if let Some(task) = current_task() {
+            add(&mut self.lock().consumers, task);
+        }
+    }
+
+    pub(crate) fn produced_by(&self, task: &str) {
+        add(&mut self.lock().producers, Arc::from(task));
+    }
+
+    pub(crate) fn consumed_by(&self, task: &str) {
+        add(&mut self.lock().consumers, Arc::from(task));
+    }
+
+    pub(crate) fn wait_started(&self) {
+        if let Some(task) = current_task() {
+            self.lock().waiting.push(task);
+        }
+    }
+
+    pub(crate) fn wait_ended(&self) {
+        if let Some(task) = current_task() {
+            let mut links = self.lock();
+            if let Some(index) = links.waiting.iter().position(|waiting| *waiting == task) {
+                links.waiting.swap_remove(index);
+            }
+        }
+    }
+
+    pub(crate) fn get(&self) -> Links {
+        self.lock().clone()
+    }
+}
+
+fn add(tasks: &mut Vec<Arc<str>>, task: Arc<str>) {
+    if !tasks.contains(&task) {
+        tasks.push(task);
+    }
+}
@@ -0,0 +1,158 @@
+use crate::{MeteredChannelBuilder, OverflowPolicy};
+use prometheus::Registry;
+
+fn gathered_names(registry: &Registry) -> Vec<String> {
+    registry
+        .gather()
+        .iter()
+        .map(|family| family.get_name().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_builder_mpsc() {
+    let registry = Registry::new();
+    let (tx, mut rx) = MeteredChannelBuilder::new()
+        .name("test_builder_mpsc")
+        .registry(&registry)
+        .capacity(4)
+        .with_total()
+        .with_latency_histogram(vec![0.01, 0.1, 1.0])
+        .build_mpsc()
+        .unwrap();
+
+    tx.send(1).await.unwrap();
+    assert_eq!(rx.recv().await, Some(1));
+
+    assert_eq!(
+        gathered_names(&registry),
+        vec![
+            "test_builder_mpsc_capacity",
+            "test_builder_mpsc_queue_latency_seconds",
+            "test_builder_mpsc_queue_size",
+            "test_builder_mpsc_total_messages",
+        ]
+    );
+
+    let families = registry.gather();
+    let capacity = families
+        .iter()
+        .find(|family| family.get_name() == "test_builder_mpsc_capacity")
+        .unwrap();
+    assert_eq!(capacity.get_metric()[0].get_gauge().get_value(), 4.0);
+
+    let latency = families
+        .iter()
+        .find(|family| family.get_name() == "test_builder_mpsc_queue_latency_seconds")
+        .unwrap();
+    assert_eq!(
+        latency.get_metric()[0].get_histogram().get_sample_count(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_builder_const_labels() {
+    let registry = Registry::new();
+    let (tx, _rx) = MeteredChannelBuilder::new()
+        .name("test_builder_labels")
+        .registry(&registry)
+        .capacity(4)
+        .const_label("pipeline", "ingest")
+        .build_broadcast::<i32>()
+        .unwrap();
+
+    tx.send(1).unwrap();
+
+    for family in registry.gather() {
+        let labels = family.get_metric()[0].get_label();
+        assert_eq!(labels.len(), 1);
+        assert_eq!(labels[0].get_name(), "pipeline");
+        assert_eq!(labels[0].get_value(), "ingest");
+    }
+}
+
+#[tokio::test]
+async fn test_builder_watch() {
+    let registry = Registry::new();
+    let (tx, rx) = MeteredChannelBuilder::new()
+        .name("test_builder_watch")
+        .registry(&registry)
+        .with_total()
+        .build_watch(0)
+        .unwrap();
+
+    tx.send(1).unwrap();
+    assert_eq!(*rx.borrow(), 1);
+    assert_eq!(rx.total_messages().unwrap().get(), 1);
+}
+
+#[tokio::test]
+async fn test_builder_overflow() {
+    let registry = Registry::new();
+    let (tx, mut rx) = MeteredChannelBuilder::new()
+        .name("test_builder_overflow")
+        .registry(&registry)
+        .capacity(1)
+        .overflow_policy(OverflowPolicy::DropNewest)
+        .build_overflow()
+        .unwrap();
+
+    tx.send(1).await.unwrap();
+    tx.send(2).await.unwrap();
+
+    assert_eq!(rx.recv().await, Some(1));
+    assert_eq!(rx.evicted_total().unwrap().get(), 1);
+}
+
+#[test]
+fn test_builder_missing_options() {
+    let registry = Registry::new();
+
+    let missing_name = MeteredChannelBuilder::new()
+        .registry(&registry)
+        .capacity(1)
+        .build_mpsc::<i32>();
+    assert!(missing_name.is_err());
+
+    let missing_capacity = MeteredChannelBuilder::new()
+        .name("test_builder_missing")
+        .registry(&registry)
+        .build_mpsc::<i32>();
+    assert!(missing_capacity.is_err());
+
+    #[cfg(feature = "overflow")]
+    {
+        let ignored_policy = MeteredChannelBuilder::new()
+            .name("test_builder_ignored_policy")
+            .capacity(1)
+            .registry(&registry)
+            .overflow_policy(crate::OverflowPolicy::DropOldest)
+            .build_mpsc::<i32>();
+        assert!(ignored_policy.is_err());
+    }
+
+    // Nothing was registered by the failed builds
+    assert!(registry.gather().is_empty());
+}
+
+#[tokio::test]
+async fn test_builder_shared() {
+    let registry = Registry::new();
+    let builder = MeteredChannelBuilder::new()
+        .name("test_builder_shared")
+        .registry(&registry)
+        .capacity(4)
+        .with_total()
+        .shared();
+
+    let (tx1, rx1) = builder.build_mpsc::<i32>().unwrap();
+    let (tx2, rx2) = builder.build_mpsc::<i32>().unwrap();
+
+    tx1.send(1).await.unwrap();
+    tx2.send(2).await.unwrap();
+    assert_eq!(rx1.total_messages().unwrap().get(), 2);
+
+    drop((tx1, rx1, tx2, rx2));
+    assert!(registry.gather().is_empty());
+}
@@ -0,0 +1,129 @@
+use crate::{
+    resizable_channel, CapacityController, ChannelMetrics, ControllerConfig, ControllerMetrics,
+    ControllerTarget,
+};
+use prometheus::Registry;
+use std::time::Duration;
+
+fn controlled<T>(
+    name: &str,
+    buffer: usize,
+    config: ControllerConfig,
+    registry: &Registry,
+) -> (
+    crate::resizable::Sender<T>,
+    crate::resizable::Receiver<T>,
+    CapacityController,
+    ControllerMetrics,
+) {
+    let metrics = ChannelMetrics::new_with_capacity(name, name, registry).unwrap();
+    let controller_metrics = ControllerMetrics::new(name, name, registry).unwrap();
+    let (tx, rx) = resizable_channel(buffer, metrics);
+    let controller =
+        CapacityController::new(tx.resize_handle(), config, controller_metrics.clone());
+    (tx, rx, controller, controller_metrics)
+}
+
+#[tokio::test]
+async fn test_controller_utilization_grow_and_shrink() {
+    let registry = Registry::new();
+    let config = ControllerConfig::new(2, 16, ControllerTarget::Utilization(0.5));
+    let (tx, mut rx, mut controller, metrics) = controlled("test_util", 4, config, &registry);
+
+    for i in 0..4 {
+        tx.send(i).await.unwrap();
+    }
+    assert_eq!(controller.tick(), Some(8));
+    assert_eq!(tx.capacity(), 8);
+    assert_eq!(metrics.observed.get(), 1.0);
+
+    // Draining the queue shrinks the channel, bounded by the maximum step
+    while rx.try_recv().is_ok() {}
+    assert_eq!(controller.tick(), Some(4));
+    assert_eq!(controller.tick(), Some(2));
+    assert_eq!(controller.tick(), None);
+
+    assert_eq!(metrics.adjustments.with_label_values(&["grow"]).get(), 1);
+    assert_eq!(metrics.adjustments.with_label_values(&["shrink"]).get(), 2);
+}
+
+#[tokio::test]
+async fn test_controller_respects_bounds() {
+    let registry = Registry::new();
+    let mut config = ControllerConfig::new(2, 6, ControllerTarget::Utilization(0.1));
+    config.max_step = 10.0;
+    let (tx, _rx, mut controller, _) = controlled("test_bounds", 4, config, &registry);
+
+    for i in 0..4 {
+        tx.send(i).await.unwrap();
+    }
+    assert_eq!(controller.tick(), Some(6));
+    assert_eq!(controller.tick(), None);
+}
+
+#[tokio::test]
+async fn test_controller_latency_shrinks() {
+    let registry = Registry::new();
+    let config = ControllerConfig::new(1, 64, ControllerTarget::Latency(Duration::from_millis(5)));
+    let (tx, mut rx, mut controller, metrics) = controlled("test_latency", 8, config, &registry);
+
+    // Without received values there is nothing to act on
+    assert_eq!(controller.tick(), None);
+
+    for i in 0..4 {
+        tx.send(i).await.unwrap();
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    while rx.try_recv().is_ok() {}
+
+    assert_eq!(controller.tick(), Some(4));
+    assert!(metrics.observed.get() >= 0.05);
+}
+
+#[tokio::test]
+async fn test_controller_latency_holds_when_not_saturated() {
+    let registry = Registry::new();
+    let config = ControllerConfig::new(1, 64, ControllerTarget::Latency(Duration::from_secs(1)));
+    let (tx, mut rx, mut controller, _) = controlled("test_latency_hold", 8, config, &registry);
+
+    tx.send(1).await.unwrap();
+    rx.recv().await.unwrap();
+
+    // Values wait far less than the target, but growing an idle channel is pointless
+    assert_eq!(controller.tick(), None);
+    assert_eq!(tx.capacity(), 8);
+}
+
+#[tokio::test]
+async fn test_controller_spawn_stops_on_close() {
+    let registry = Registry::new();
+    let mut config = ControllerConfig::new(1, 64, ControllerTarget::Utilization(0.5));
+    config.interval = Duration::from_millis(10);
+    let (tx, rx, controller, _) = controlled::<i32>("test_spawn", 8, config, &registry);
+
+    let task = controller.spawn();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(tx.capacity() < 8);
+
+    drop(rx);
+    tokio::time::timeout(Duration::from_secs(1), task)
+        .await
+        .expect("controller should stop once the channel is closed")
+        .unwrap();
+}
+
+#[test]
+fn test_controller_metrics_failure_leaves_nothing_registered() {
+    let registry = Registry::new();
+    let foreign = prometheus::IntGauge::new(
+        "test_ctl_partial_controller_observed",
+        "Last value observed by the capacity controller of test partial channel",
+    )
+    .unwrap();
+    registry.register(Box::new(foreign.clone())).unwrap();
+
+    assert!(ControllerMetrics::new("test_ctl_partial", "test partial", &registry).is_err());
+
+    registry.unregister(Box::new(foreign)).unwrap();
+    assert!(ControllerMetrics::new("test_ctl_partial", "test partial", &registry).is_ok());
+}
@@ -0,0 +1,117 @@
+use crate::{
+    mpsc_channel_with_dead_letters, mpsc_channel_with_observer, ChannelMetrics, ChannelMetricsOpts,
+    CountingObserver, DeadLetterReason, MeteredChannelBuilder, SendError,
+};
+use prometheus::Registry;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_dead_letters_capture_full_and_closed_sends() {
+    let (dead_tx, mut dead_rx) = mpsc_channel_with_observer(8, CountingObserver::new());
+    let observer = CountingObserver::new();
+    let (tx, rx) = mpsc_channel_with_dead_letters(1, observer.clone(), dead_tx);
+
+    tx.try_send(1).unwrap();
+    assert!(matches!(tx.try_send(2), Err(SendError::Full(()))));
+    drop(rx);
+    assert!(matches!(tx.send(3).await, Err(SendError::Closed(()))));
+
+    let reasons: Vec<_> = (0..3)
+        .map(|_| {
+            let letter = dead_rx.try_recv().unwrap();
+            (letter.value, letter.reason)
+        })
+        .collect();
+    assert_eq!(
+        reasons,
+        vec![
+            (2, DeadLetterReason::Full),
+            (1, DeadLetterReason::Discarded),
+            (3, DeadLetterReason::Closed),
+        ]
+    );
+    assert_eq!(observer.dead_letters(), 3);
+    assert_eq!(observer.dead_letters_lost(), 0);
+}
+
+#[tokio::test]
+async fn test_dead_letters_capture_values_abandoned_by_drain() {
+    let (dead_tx, mut dead_rx) = mpsc_channel_with_observer(8, CountingObserver::new());
+    let (tx, mut rx) = mpsc_channel_with_dead_letters(8, CountingObserver::new(), dead_tx);
+    for i in 0..3 {
+        tx.send(i).await.unwrap();
+    }
+
+    // The second value is interrupted, and only the third is still queued
+    let report = rx
+        .drain(Duration::from_millis(50), |_| async {
+            tokio::time::sleep(Duration::from_millis(30)).await
+        })
+        .await;
+    assert_eq!((report.processed, report.abandoned), (1, 2));
+
+    let letter = dead_rx.recv().await.unwrap();
+    assert_eq!(letter.value, 2);
+    assert_eq!(letter.reason, DeadLetterReason::Discarded);
+    // Nothing is left for the receiver to discard on drop
+    drop(rx);
+    assert!(dead_rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_dead_letters_lost_when_sink_full() {
+    let dead_observer = CountingObserver::new();
+    let (dead_tx, mut dead_rx) = mpsc_channel_with_observer(1, dead_observer.clone());
+    let observer = CountingObserver::new();
+    let (tx, _rx) = mpsc_channel_with_dead_letters(1, observer.clone(), dead_tx);
+
+    tx.try_send(1).unwrap();
+    assert!(tx.try_send(2).is_err());
+    assert!(tx.try_send(3).is_err());
+
+    assert_eq!(observer.dead_letters(), 1);
+    assert_eq!(observer.dead_letters_lost(), 1);
+    assert_eq!(dead_observer.sent(), 1);
+    assert_eq!(dead_rx.recv().await.unwrap().value, 2);
+}
+
+#[tokio::test]
+async fn test_dead_letters_carry_channel_name() {
+    let registry = Registry::new();
+    let dead_letters =
+        ChannelMetrics::new_basic("dead_letters", "dead letters", &registry).unwrap();
+    let (dead_tx, mut dead_rx) = crate::mpsc_channel(8, dead_letters);
+    let metrics = MeteredChannelBuilder::new()
+        .name("test_dlq_orders")
+        .registry(&registry)
+        .with_dead_letters()
+        .build_metrics()
+        .unwrap();
+    let (tx, rx) = mpsc_channel_with_dead_letters(1, metrics, dead_tx);
+
+    drop(rx);
+    assert!(tx.try_send("order").is_err());
+    // The dead-letter channel is metered like any other
+    assert_eq!(dead_rx.observer().queue_size.get(), 1);
+
+    let letter = dead_rx.recv().await.unwrap();
+    assert_eq!(&*letter.channel, "test_dlq_orders");
+    assert_eq!(letter.reason, DeadLetterReason::Closed);
+    let counter = tx.inner().observer().dead_letters_total.as_ref().unwrap();
+    assert_eq!(counter.get(), 1);
+}
+
+#[test]
+fn test_dead_letter_metrics_registered() {
+    let registry = Registry::new();
+    let opts = ChannelMetricsOpts::new("test_dlq_registered").with_dead_letters();
+    ChannelMetrics::with_opts(&opts, &registry).unwrap();
+
+    let names: Vec<_> = registry
+        .gather()
+        .iter()
+        .map(|family| family.get_name().to_string())
+        .collect();
+    assert!(names.contains(&"test_dlq_registered_dead_letters_total".to_string()));
+    assert!(names.contains(&"test_dlq_registered_dead_letters_lost_total".to_string()));
+}
@@ -0,0 +1,173 @@
+use crate::{
+    find_deadlocks, mpsc_channel, track_task, ChannelMetrics, ChannelMetricsOpts, Deadlock,
+    MpscReceiver, MpscSender, WithPermit,
+};
+use prometheus::Registry;
+use std::time::Duration;
+
+fn channel(name: &str, capacity: usize) -> (MpscSender<u32>, MpscReceiver<u32>) {
+    let opts = ChannelMetricsOpts::new(name).with_stats();
+    mpsc_channel(
+        capacity,
+        ChannelMetrics::with_opts(&opts, &Registry::new()).unwrap(),
+    )
+}
+
+/// Deadlocks involving the task named `task`, leaving out those of other tests
+fn deadlocks_of(task: &str) -> Vec<Deadlock> {
+    find_deadlocks()
+        .into_iter()
+        .filter(|deadlock| deadlock.cycle.iter().any(|send| send.task == task))
+        .collect()
+}
+
+async fn settle() {
+    tokio::time::sleep(Duration::from_millis(50)).await;
+}
+
+#[tokio::test]
+async fn test_two_task_cycle() {
+    let (to_b, mut from_a) = channel("test_deadlock_cycle_ab", 1);
+    let (to_a, mut from_b) = channel("test_deadlock_cycle_ba", 1);
+    to_b.observer().consumed_by("cycle_b");
+    to_a.observer().consumed_by("cycle_a");
+
+    let a = tokio::spawn(track_task("cycle_a", async move {
+        to_b.send(1).await.unwrap();
+        to_b.send(2).await.unwrap();
+        from_b.recv().await
+    }));
+    let b = tokio::spawn(track_task("cycle_b", async move {
+        to_a.send(1).await.unwrap();
+        to_a.send(2).await.unwrap();
+        from_a.recv().await
+    }));
+    settle().await;
+
+    let deadlocks = deadlocks_of("cycle_a");
+    assert_eq!(deadlocks.len(), 1);
+    let cycle = &deadlocks[0].cycle;
+    assert_eq!(
+        (cycle[0].task.as_str(), cycle[1].task.as_str()),
+        ("cycle_a", "cycle_b")
+    );
+    assert_eq!(cycle[0].channel, "test_deadlock_cycle_ab");
+    assert_eq!(cycle[0].depth, 1);
+
+    // Cancelled sends no longer wait
+    a.abort();
+    b.abort();
+    let _ = (a.await, b.await);
+    assert!(deadlocks_of("cycle_a").is_empty());
+}
+
+#[tokio::test]
+async fn test_cycle_through_reserve() {
+    let (to_b, mut from_a) = channel("test_deadlock_reserve_ab", 1);
+    let (to_a, mut from_b) = channel("test_deadlock_reserve_ba", 1);
+    to_b.observer().consumed_by("reserve_b");
+    to_a.observer().consumed_by("reserve_a");
+
+    let a = tokio::spawn(track_task("reserve_a", async move {
+        to_b.send(1).await.unwrap();
+        to_b.reserve().await.unwrap().send(2);
+        from_b.recv().await
+    }));
+    let b = tokio::spawn(track_task("reserve_b", async move {
+        to_a.send(1).await.unwrap();
+        to_a.reserve().await.unwrap().send(2);
+        from_a.recv().await
+    }));
+    settle().await;
+
+    // Tasks waiting for a permit are seen like those waiting to send
+    assert_eq!(deadlocks_of("reserve_a").len(), 1);
+
+    a.abort();
+    b.abort();
+    let _ = (a.await, b.await);
+    assert!(deadlocks_of("reserve_a").is_empty());
+}
+
+#[tokio::test]
+async fn test_consumers_inferred_from_receives() {
+    let (tx, mut rx) = channel("test_deadlock_self", 1);
+    let task = tokio::spawn(track_task("self_loop", async move {
+        tx.send(1).await.unwrap();
+        rx.recv().await.unwrap();
+        // The task is the only consumer of the channel it now fills
+        tx.send(2).await.unwrap();
+        tx.send(3).await.unwrap();
+        rx.recv().await
+    }));
+    settle().await;
+
+    let deadlocks = deadlocks_of("self_loop");
+    assert_eq!(deadlocks.len(), 1);
+    assert_eq!(deadlocks[0].cycle.len(), 1);
+    assert_eq!(
+        deadlocks[0].to_string(),
+        "self_loop -> test_deadlock_self (1 queued) -> self_loop"
+    );
+    task.abort();
+}
+
+#[tokio::test]
+async fn test_free_consumer_is_no_deadlock() {
+    let (to_b, mut from_a) = channel("test_deadlock_free_ab", 1);
+    let (to_a, from_b) = channel("test_deadlock_free_ba", 1);
+    to_b.observer().consumed_by("free_b");
+    to_a.observer().consumed_by("free_a");
+
+    // A waits on B, which is busy elsewhere rather than waiting on A
+    let a = tokio::spawn(track_task("free_a", async move {
+        to_b.send(1).await.unwrap();
+        to_b.send(2).await.unwrap();
+        drop(from_b);
+    }));
+    let b = tokio::spawn(track_task("free_b", async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        drop(to_a);
+        from_a.recv().await
+    }));
+    settle().await;
+    assert!(deadlocks_of("free_a").is_empty());
+
+    a.abort();
+    b.abort();
+}
+
+#[tokio::test]
+async fn test_tasks_waiting_behind_a_cycle() {
+    let (to_b, mut from_a) = channel("test_deadlock_behind_ab", 1);
+    let (to_a, mut from_b) = channel("test_deadlock_behind_ba", 1);
+    let to_a_too = to_a.clone();
+    to_b.observer().consumed_by("behind_b");
+    to_a.observer().consumed_by("behind_a");
+
+    let a = tokio::spawn(track_task("behind_a", async move {
+        to_b.send(1).await.unwrap();
+        to_b.send(2).await.unwrap();
+        from_b.recv().await
+    }));
+    let b = tokio::spawn(track_task("behind_b", async move {
+        to_a.send(1).await.unwrap();
+        to_a.send(2).await.unwrap();
+        from_a.recv().await
+    }));
+    settle().await;
+    // A third task stuck on the cycle is left out of it
+    let c = tokio::spawn(track_task("behind_c", async move {
+        to_a_too.send(3).await.unwrap();
+    }));
+    settle().await;
+
+    let deadlocks = deadlocks_of("behind_a");
+    assert_eq!(deadlocks.len(), 1);
+    assert_eq!(deadlocks[0].cycle.len(), 2);
+    assert!(deadlocks_of("behind_c").is_empty());
+
+    a.abort();
+    b.abort();
+    c.abort();
+}
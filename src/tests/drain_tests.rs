@@ -0,0 +1,198 @@
+use crate::{
+    mpsc_channel, mpsc_channel_with_observer, ChannelMetrics, ChannelMetricsOpts, CountingObserver,
+    DrainReport, MeteredChannelBuilder, SendError, WithPermit,
+};
+use prometheus::Registry;
+use std::time::Duration;
+
+fn metrics(name: &str) -> ChannelMetrics {
+    let opts = ChannelMetricsOpts::new(name).with_drain();
+    ChannelMetrics::with_opts(&opts, &Registry::new()).unwrap()
+}
+
+#[tokio::test]
+async fn test_drain_processes_queued_values() {
+    let (tx, mut rx) = mpsc_channel(8, metrics("test_drain_all"));
+    for i in 0..3 {
+        tx.send(i).await.unwrap();
+    }
+
+    let mut seen = Vec::new();
+    let report = rx
+        .drain(Duration::from_secs(1), |value| {
+            seen.push(value);
+            async {}
+        })
+        .await;
+    assert_eq!(
+        report,
+        DrainReport {
+            processed: 3,
+            abandoned: 0
+        }
+    );
+    assert_eq!(seen, vec![0, 1, 2]);
+    assert!(matches!(tx.send(3).await, Err(SendError::Closed(3))));
+
+    let metrics = rx.observer();
+    assert_eq!(metrics.drained_total.as_ref().unwrap().get(), 3);
+    assert_eq!(metrics.dropped_total.as_ref().unwrap().get(), 0);
+    assert_eq!(metrics.queue_size.get(), 0);
+}
+
+#[tokio::test]
+async fn test_drain_abandons_values_after_timeout() {
+    let (tx, mut rx) = mpsc_channel(8, metrics("test_drain_timeout"));
+    for i in 0..5 {
+        tx.send(i).await.unwrap();
+    }
+
+    // The second value is still being processed at the deadline
+    let report = rx
+        .drain(Duration::from_millis(50), |_| {
+            tokio::time::sleep(Duration::from_millis(30))
+        })
+        .await;
+    assert_eq!(
+        report,
+        DrainReport {
+            processed: 1,
+            abandoned: 4
+        }
+    );
+
+    let metrics = rx.observer();
+    assert_eq!(metrics.drained_total.as_ref().unwrap().get(), 1);
+    assert_eq!(metrics.dropped_total.as_ref().unwrap().get(), 3);
+    assert_eq!(metrics.queue_size.get(), 0);
+}
+
+#[tokio::test]
+async fn test_drain_interrupts_hung_processing() {
+    let (tx, mut rx) = mpsc_channel_with_observer(4, CountingObserver::new());
+    tx.send(1).await.unwrap();
+    tx.send(2).await.unwrap();
+
+    let report = tokio::time::timeout(
+        Duration::from_secs(1),
+        rx.drain(Duration::from_millis(20), |_| std::future::pending()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        report,
+        DrainReport {
+            processed: 0,
+            abandoned: 2
+        }
+    );
+    assert_eq!(rx.observer().dropped(), 1);
+}
+
+#[tokio::test]
+async fn test_dropped_receiver_reports_queued_values() {
+    let observer = CountingObserver::new();
+    let (tx, rx) = mpsc_channel_with_observer(4, observer.clone());
+    tx.send(1).await.unwrap();
+    tx.send(2).await.unwrap();
+
+    drop(rx);
+    assert_eq!(observer.dropped(), 2);
+    assert!(observer.is_empty());
+    assert_eq!(observer.drained(), 0);
+}
+
+#[tokio::test]
+async fn test_close_and_drain() {
+    let observer = CountingObserver::new();
+    let (tx, mut rx) = mpsc_channel_with_observer(4, observer.clone());
+    let other = tx.clone();
+    tx.send(1).await.unwrap();
+    other.send(2).await.unwrap();
+
+    let consumer = tokio::spawn(async move {
+        let mut values = Vec::new();
+        while let Some(value) = rx.recv().await {
+            values.push(value);
+        }
+        values
+    });
+    tx.close_and_drain().await;
+    assert!(tx.is_closed());
+    assert!(matches!(other.try_send(3), Err(SendError::Closed(3))));
+
+    drop((tx, other));
+    assert_eq!(consumer.await.unwrap(), vec![1, 2]);
+    assert_eq!(observer.received(), 2);
+}
+
+#[tokio::test]
+async fn test_close_and_drain_stops_sends_at_once() {
+    let (tx, mut rx) = mpsc_channel_with_observer(4, CountingObserver::new());
+    let other = tx.clone();
+    tx.send(1).await.unwrap();
+
+    let closing = tokio::spawn(async move { tx.close_and_drain().await });
+    tokio::task::yield_now().await;
+
+    // The receiver has not reached the close yet
+    assert!(other.is_closed());
+    assert!(matches!(other.try_send(2), Err(SendError::Closed(2))));
+    assert!(matches!(other.send(3).await, Err(SendError::Closed(3))));
+    assert!(other.reserve().await.is_err());
+
+    assert_eq!(rx.recv().await, Some(1));
+    assert_eq!(rx.recv().await, None);
+    closing.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_permit_send_after_close_and_drain() {
+    let observer = CountingObserver::new();
+    let (tx, mut rx) = mpsc_channel_with_observer(2, observer.clone());
+    let permit = tx.reserve().await.unwrap();
+
+    let other = tx.clone();
+    let closing = tokio::spawn(async move { other.close_and_drain().await });
+    tokio::task::yield_now().await;
+
+    // The close is queued ahead of the permit's value, which is dropped
+    permit.send(1);
+    assert_eq!(rx.recv().await, None);
+    closing.await.unwrap();
+    assert_eq!(observer.sent(), 0);
+}
+
+#[tokio::test]
+async fn test_close_and_drain_with_dropped_receiver() {
+    let observer = CountingObserver::new();
+    let (tx, rx) = mpsc_channel_with_observer(4, observer.clone());
+    tx.send(1).await.unwrap();
+
+    let dropper = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        drop(rx);
+    });
+    tx.close_and_drain().await;
+    dropper.await.unwrap();
+    assert_eq!(observer.dropped(), 1);
+}
+
+#[tokio::test]
+async fn test_builder_drain_metrics() {
+    let registry = Registry::new();
+    let (tx, rx) = MeteredChannelBuilder::new()
+        .name("test_builder_drain")
+        .capacity(4)
+        .registry(&registry)
+        .with_drain()
+        .build_mpsc()
+        .unwrap();
+    tx.send(1).await.unwrap();
+    drop(rx);
+
+    let families = registry.gather();
+    let names: Vec<_> = families.iter().map(|family| family.get_name()).collect();
+    assert!(names.contains(&"test_builder_drain_drained_total"));
+    assert_eq!(tx.observer().dropped_total.as_ref().unwrap().get(), 1);
+}
@@ -0,0 +1,123 @@
+use crate::{mpsc_channel, ChannelMetrics, ChannelMetricsOpts, MetricsServer};
+use prometheus::Registry;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+async fn request(addr: SocketAddr, request: &str) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+async fn get(addr: SocketAddr, path: &str) -> String {
+    request(
+        addr,
+        &format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path),
+    )
+    .await
+}
+
+async fn serve(registry: &Registry) -> SocketAddr {
+    let server = MetricsServer::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .registry(registry);
+    let addr = server.local_addr().unwrap();
+    server.spawn();
+    addr
+}
+
+#[tokio::test]
+async fn test_serves_metrics() {
+    let registry = Registry::new();
+    let opts = ChannelMetricsOpts::new("test_exporter_metrics").with_total();
+    let (tx, _rx) = mpsc_channel(4, ChannelMetrics::with_opts(&opts, &registry).unwrap());
+    tx.send(1).await.unwrap();
+
+    let addr = serve(&registry).await;
+    let response = get(addr, "/metrics").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("Content-Type: text/plain; version=0.0.4"));
+    assert!(response.contains("\ntest_exporter_metrics_queue_size 1\n"));
+    assert!(response.contains("\ntest_exporter_metrics_total_messages 1\n"));
+}
+
+#[tokio::test]
+async fn test_serves_channels() {
+    let registry = Registry::new();
+    let opts = ChannelMetricsOpts::new("test_exporter_channels").with_stats();
+    let (tx, _rx) = mpsc_channel(4, ChannelMetrics::with_opts(&opts, &registry).unwrap());
+    tx.send(1).await.unwrap();
+
+    let addr = serve(&registry).await;
+    let response = get(addr, "/channels?pretty").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("Content-Type: application/json\r\n"));
+    let body = response.split("\r\n\r\n").nth(1).unwrap();
+    assert!(body.starts_with('[') && body.ends_with(']'));
+    assert!(body.contains(
+        "{\"name\":\"test_exporter_channels\",\"labels\":{},\"kind\":\"mpsc\",\"capacity\":4,\"len\":1,\
+         \"senders\":1,\"receivers\":1,\"sent\":1,\"received\":0,\"dropped\":0,\"closed\":false}"
+    ));
+}
+
+#[tokio::test]
+async fn test_unknown_requests() {
+    let addr = serve(&Registry::new()).await;
+
+    let response = get(addr, "/").await;
+    assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+    let response = request(addr, "POST /metrics HTTP/1.1\r\nContent-Length: 0\r\n\r\n").await;
+    assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+    assert!(response.contains("\r\nAllow: GET\r\n"));
+
+    let oversized = format!("GET /metrics HTTP/1.1\r\nX-Padding: {}", "a".repeat(10_000));
+    let response = request(addr, &oversized).await;
+    assert!(response.starts_with("HTTP/1.1 431 "));
+}
+
+#[tokio::test]
+async fn test_silent_connections_time_out() {
+    let server = MetricsServer::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .registry(&Registry::new())
+        .timeout(Duration::from_millis(50))
+        .max_connections(1);
+    let addr = server.local_addr().unwrap();
+    server.spawn();
+
+    // Holds the only connection slot without sending a request
+    let mut silent = TcpStream::connect(addr).await.unwrap();
+    let response = get(addr, "/").await;
+    assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+    let mut buf = Vec::new();
+    assert_eq!(silent.read_to_end(&mut buf).await.unwrap(), 0);
+}
+
+#[cfg(feature = "tasks")]
+#[tokio::test]
+async fn test_serves_topology() {
+    let opts = ChannelMetricsOpts::new("test_exporter_topology").with_stats();
+    let (tx, _rx) = mpsc_channel(
+        4,
+        ChannelMetrics::with_opts(&opts, &Registry::new()).unwrap(),
+    );
+    crate::track_task("exporter_producer", async { tx.send(1).await.unwrap() }).await;
+
+    let addr = serve(&Registry::new()).await;
+    let response = get(addr, "/topology.dot").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("Content-Type: text/vnd.graphviz\r\n"));
+    assert!(response.contains("\"exporter_producer\" -> \"test_exporter_topology/out\""));
+
+    let response = get(addr, "/topology").await;
+    assert!(response.contains("Content-Type: application/json\r\n"));
+    assert!(response.contains("\"producers\":[\"exporter_producer\"]"));
+}
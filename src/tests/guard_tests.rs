@@ -0,0 +1,120 @@
+use crate::weighted::ByteLen;
+use crate::{
+    mpsc_channel, mpsc_channel_with_observer, overflow_channel_with_observer,
+    resizable_channel_with_observer, weighted::Limit, weighted_channel_with_observer,
+    ChannelMetrics, ChannelMetricsOpts, CountingObserver, MeteredChannelBuilder, OverflowPolicy,
+};
+use prometheus::Registry;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_guard_records_processing_time() {
+    let registry = Registry::new();
+    let opts = ChannelMetricsOpts::new("test_guard_processing")
+        .with_latency_histogram(vec![1.0])
+        .with_processing_histogram(vec![0.1, 1.0, 10.0]);
+    let metrics = ChannelMetrics::with_opts(&opts, &registry).unwrap();
+    let in_flight = metrics.in_flight.clone().unwrap();
+    let processing_time = metrics.processing_time.clone().unwrap();
+
+    let (tx, mut rx) = mpsc_channel(4, metrics);
+    tx.send(1).await.unwrap();
+    tx.send(2).await.unwrap();
+
+    let first = rx.recv_guarded().await.unwrap();
+    let second = rx.recv_guarded().await.unwrap();
+    assert_eq!(*first + *second, 3);
+    assert_eq!(in_flight.get(), 2);
+
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    drop(first);
+    assert_eq!(in_flight.get(), 1);
+    assert_eq!(processing_time.get_sample_count(), 1);
+    assert!(processing_time.get_sample_sum() >= 0.02);
+
+    assert_eq!(second.into_inner(), 2);
+    assert_eq!(in_flight.get(), 0);
+    assert_eq!(processing_time.get_sample_count(), 2);
+}
+
+#[tokio::test]
+async fn test_guard_deref_mut() {
+    let observer = CountingObserver::new();
+    let (tx, mut rx) = mpsc_channel_with_observer(4, observer.clone());
+    tx.send(vec![1, 2]).await.unwrap();
+
+    let mut guard = rx.recv_guarded().await.unwrap();
+    guard.push(3);
+    assert_eq!(*guard, vec![1, 2, 3]);
+    assert_eq!(observer.in_flight(), 1);
+    assert_eq!(observer.processed(), 0);
+
+    drop(guard);
+    assert_eq!(observer.in_flight(), 0);
+    assert_eq!(observer.processed(), 1);
+}
+
+#[tokio::test]
+async fn test_guard_closed_channel() {
+    let observer = CountingObserver::new();
+    let (tx, mut rx) = mpsc_channel_with_observer::<i32, _>(4, observer.clone());
+    drop(tx);
+
+    assert!(rx.recv_guarded().await.is_none());
+    assert_eq!(observer.in_flight(), 0);
+    assert_eq!(observer.processed(), 0);
+}
+
+#[tokio::test]
+async fn test_guard_other_channels() {
+    let observer = CountingObserver::new();
+
+    let (tx, mut rx) =
+        overflow_channel_with_observer(2, OverflowPolicy::DropOldest, observer.clone());
+    tx.send(1).await.unwrap();
+    let overflow_guard = rx.recv_guarded().await.unwrap();
+
+    let (tx, mut rx) = resizable_channel_with_observer(2, observer.clone());
+    tx.send(2).await.unwrap();
+    let resizable_guard = rx.recv_guarded().await.unwrap();
+
+    let (tx, mut rx) = weighted_channel_with_observer(Limit::Bytes(8), ByteLen, observer.clone());
+    tx.send("abc").await.unwrap();
+    let weighted_guard = rx.recv_guarded().await.unwrap();
+
+    assert_eq!(*overflow_guard + *resizable_guard, 3);
+    assert_eq!(weighted_guard.len(), 3);
+    assert_eq!(observer.in_flight(), 3);
+
+    drop((overflow_guard, resizable_guard, weighted_guard));
+    assert_eq!(observer.in_flight(), 0);
+    assert_eq!(observer.processed(), 3);
+}
+
+#[tokio::test]
+async fn test_builder_processing_histogram() {
+    let registry = Registry::new();
+    let (tx, mut rx) = MeteredChannelBuilder::new()
+        .name("test_builder_processing")
+        .capacity(4)
+        .registry(&registry)
+        .with_processing_histogram(vec![0.1, 1.0])
+        .build_mpsc()
+        .unwrap();
+
+    tx.send(1).await.unwrap();
+    rx.recv_guarded().await.unwrap();
+
+    let families = registry.gather();
+    let names: Vec<_> = families.iter().map(|family| family.get_name()).collect();
+    assert!(names.contains(&"test_builder_processing_in_flight"));
+    assert!(names.contains(&"test_builder_processing_processing_seconds"));
+    assert_eq!(
+        rx.observer()
+            .processing_time
+            .as_ref()
+            .unwrap()
+            .get_sample_count(),
+        1
+    );
+}
@@ -0,0 +1,101 @@
+use crate::{mpsc_channel, ChannelMetrics, MergeMetrics, MergedReceiver};
+use futures::StreamExt;
+use prometheus::Registry;
+
+fn metered<T>(name: &str, registry: &Registry) -> (crate::MpscSender<T>, crate::MpscReceiver<T>) {
+    let metrics = ChannelMetrics::new_basic(name, name, registry).unwrap();
+    mpsc_channel(16, metrics)
+}
+
+#[tokio::test]
+async fn test_merge_round_robin() {
+    let registry = Registry::new();
+    let (tx_a, rx_a) = metered("test_rr_a", &registry);
+    let (tx_b, rx_b) = metered("test_rr_b", &registry);
+
+    let mut merged =
+        MergedReceiver::new(MergeMetrics::new("test_rr", "test rr", &registry).unwrap());
+    merged.push("a", rx_a);
+    merged.push("b", rx_b);
+
+    for i in 0..3 {
+        tx_a.send(i).await.unwrap();
+        tx_b.send(i + 10).await.unwrap();
+    }
+
+    let mut received = vec![];
+    for _ in 0..6 {
+        received.push(merged.recv().await.unwrap());
+    }
+
+    assert_eq!(
+        received,
+        vec![(0, 0), (1, 10), (0, 1), (1, 11), (0, 2), (1, 12)]
+    );
+}
+
+#[tokio::test]
+async fn test_merge_weighted() {
+    let registry = Registry::new();
+    let (tx_a, rx_a) = metered("test_weighted_a", &registry);
+    let (tx_b, rx_b) = metered("test_weighted_b", &registry);
+
+    let metrics = MergeMetrics::new("test_weighted", "test weighted", &registry).unwrap();
+    let mut merged = MergedReceiver::new(metrics.clone());
+    merged.push_weighted("a", rx_a, 3);
+    merged.push("b", rx_b);
+
+    for i in 0..6 {
+        tx_a.send(i).await.unwrap();
+        tx_b.send(i).await.unwrap();
+    }
+
+    let mut sources = vec![];
+    for _ in 0..8 {
+        sources.push(merged.recv().await.unwrap().0);
+    }
+
+    assert_eq!(sources, vec![0, 0, 0, 1, 0, 0, 0, 1]);
+    assert_eq!(metrics.received.with_label_values(&["a"]).get(), 6);
+    assert_eq!(metrics.received.with_label_values(&["b"]).get(), 2);
+}
+
+#[tokio::test]
+async fn test_merge_skips_empty_sources() {
+    let registry = Registry::new();
+    let (_tx_a, rx_a) = metered::<i32>("test_skip_a", &registry);
+    let (tx_b, rx_b) = metered("test_skip_b", &registry);
+
+    let mut merged =
+        MergedReceiver::new(MergeMetrics::new("test_skip", "test skip", &registry).unwrap());
+    merged.push("idle", rx_a);
+    let busy = merged.push("busy", rx_b);
+
+    tx_b.send(1).await.unwrap();
+    tx_b.send(2).await.unwrap();
+
+    assert_eq!(merged.recv().await, Some((busy, 1)));
+    assert_eq!(merged.recv().await, Some((busy, 2)));
+    assert_eq!(merged.source_name(busy), Some("busy"));
+}
+
+#[tokio::test]
+async fn test_merge_closed() {
+    let registry = Registry::new();
+    let (tx_a, rx_a) = metered("test_merge_closed_a", &registry);
+    let (tx_b, rx_b) = metered("test_merge_closed_b", &registry);
+
+    let mut merged = MergedReceiver::new(
+        MergeMetrics::new("test_merge_closed", "test merge closed", &registry).unwrap(),
+    );
+    merged.push("a", rx_a);
+    merged.push("b", rx_b);
+
+    tx_a.send(1).await.unwrap();
+    drop(tx_a);
+    drop(tx_b);
+
+    // Buffered values are drained before the merged stream ends
+    let items: Vec<_> = merged.collect().await;
+    assert_eq!(items, vec![(0, 1)]);
+}
@@ -1,5 +1,5 @@
-use crate::ChannelMetrics;
-use prometheus::Registry;
+use crate::{mpsc_channel, ChannelMetrics, ChannelMetricsOpts};
+use prometheus::{IntGauge, Registry};
 
 #[test]
 fn test_metrics_creation() {
@@ -42,3 +42,134 @@ fn test_metrics_registration() {
 
     assert!(metrics2.is_err());
 }
+
+#[test]
+fn test_metrics_opts_naming() {
+    let registry = Registry::new();
+    let opts = ChannelMetricsOpts::new("ingest")
+        .namespace("svc")
+        .subsystem("channels")
+        .help("ingest pipeline")
+        .const_label("pipeline", "ingest")
+        .with_total();
+    assert_eq!(opts.prefix(), "svc_channels_ingest");
+
+    let metrics = ChannelMetrics::with_opts(&opts, &registry).unwrap();
+    metrics.queue_size.inc();
+
+    let families = registry.gather();
+    let names: Vec<_> = families.iter().map(|family| family.get_name()).collect();
+    assert_eq!(
+        names,
+        vec![
+            "svc_channels_ingest_queue_size",
+            "svc_channels_ingest_total_messages"
+        ]
+    );
+    assert_eq!(
+        families[0].get_help(),
+        "Current number of items in ingest pipeline channel"
+    );
+
+    let labels = families[0].get_metric()[0].get_label();
+    assert_eq!(labels[0].get_name(), "pipeline");
+    assert_eq!(labels[0].get_value(), "ingest");
+}
+
+#[test]
+fn test_metrics_opts_help_override() {
+    let registry = Registry::new();
+    let opts = ChannelMetricsOpts::new("")
+        .namespace("svc")
+        .metric_help("queue_size", "Items waiting to be ingested")
+        .with_capacity();
+
+    ChannelMetrics::with_opts(&opts, &registry).unwrap();
+
+    let families = registry.gather();
+    assert_eq!(families[0].get_name(), "svc_capacity");
+    assert_eq!(families[0].get_help(), "Current capacity of svc channel");
+    assert_eq!(families[1].get_name(), "svc_queue_size");
+    assert_eq!(families[1].get_help(), "Items waiting to be ingested");
+}
+
+#[test]
+fn test_get_or_register_shares_metrics() {
+    let registry = Registry::new();
+    let opts = ChannelMetricsOpts::new("test_shared").with_total();
+
+    let metrics1 = ChannelMetrics::get_or_register(&opts, &registry).unwrap();
+    let metrics2 = ChannelMetrics::get_or_register(&opts, &registry).unwrap();
+
+    metrics1.queue_size.inc();
+    assert_eq!(metrics2.queue_size.get(), 1);
+    assert_eq!(registry.gather().len(), 2);
+
+    // Collectors created outside get_or_register are not shared
+    let foreign = ChannelMetrics::new_basic("test_foreign", "test foreign", &registry).unwrap();
+    let opts = ChannelMetricsOpts::new("test_foreign");
+    assert!(ChannelMetrics::get_or_register(&opts, &registry).is_err());
+    drop(foreign);
+}
+
+#[test]
+fn test_get_or_register_adds_missing_collectors() {
+    let registry = Registry::new();
+    let basic = ChannelMetricsOpts::new("test_missing");
+
+    let metrics1 = ChannelMetrics::get_or_register(&basic, &registry).unwrap();
+    assert!(metrics1.total_messages.is_none());
+
+    let metrics2 = ChannelMetrics::get_or_register(&basic.clone().with_total(), &registry).unwrap();
+    assert!(metrics2.total_messages.is_some());
+    assert_eq!(registry.gather().len(), 2);
+}
+
+#[test]
+fn test_get_or_register_failure_leaves_nothing_registered() {
+    let registry = Registry::new();
+    let foreign = IntGauge::new(
+        "test_partial_capacity",
+        "Current capacity of test_partial channel",
+    )
+    .unwrap();
+    registry.register(Box::new(foreign.clone())).unwrap();
+
+    // The queue size gauge and total counter register before the capacity gauge collides
+    let opts = ChannelMetricsOpts::new("test_partial")
+        .with_total()
+        .with_capacity();
+    assert!(ChannelMetrics::get_or_register(&opts, &registry).is_err());
+    assert_eq!(registry.gather().len(), 1);
+
+    // The same happens when adding collectors to a shared set
+    let basic = ChannelMetricsOpts::new("test_partial");
+    let _metrics = ChannelMetrics::get_or_register(&basic, &registry).unwrap();
+    assert!(ChannelMetrics::get_or_register(&opts, &registry).is_err());
+    assert_eq!(registry.gather().len(), 2);
+
+    registry.unregister(Box::new(foreign)).unwrap();
+    let metrics = ChannelMetrics::get_or_register(&opts, &registry).unwrap();
+    assert!(metrics.capacity.is_some());
+}
+
+#[tokio::test]
+async fn test_get_or_register_unregisters_on_last_drop() {
+    let registry = Registry::new();
+    let opts = ChannelMetricsOpts::new("test_refcount").with_total();
+
+    let metrics = ChannelMetrics::get_or_register(&opts, &registry).unwrap();
+    let (tx, rx) = mpsc_channel::<i32>(1, metrics.clone());
+    drop(metrics);
+
+    // The channel keeps the metrics registered
+    tx.send(1).await.unwrap();
+    assert_eq!(registry.gather().len(), 2);
+
+    drop(tx);
+    drop(rx);
+    assert!(registry.gather().is_empty());
+
+    // The names can be registered again, even outside get_or_register
+    ChannelMetrics::new("test_refcount", "test_refcount", &registry).unwrap();
+}
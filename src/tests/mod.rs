@@ -1,4 +1,94 @@
+#[cfg(all(feature = "prometheus", feature = "broadcast"))]
 mod broadcast_tests;
+#[cfg(all(
+    feature = "prometheus",
+    feature = "mpsc",
+    feature = "broadcast",
+    feature = "watch",
+    feature = "overflow"
+))]
+mod builder_tests;
+#[cfg(all(feature = "prometheus", feature = "mpsc"))]
 mod channel_tests;
+#[cfg(feature = "controller")]
+mod controller_tests;
+#[cfg(all(feature = "prometheus", feature = "mpsc"))]
+mod dead_letter_tests;
+#[cfg(all(feature = "tasks", feature = "mpsc"))]
+mod deadlock_tests;
+#[cfg(all(feature = "prometheus", feature = "mpsc"))]
+mod drain_tests;
+#[cfg(all(feature = "exporter", feature = "mpsc"))]
+mod exporter_tests;
+#[cfg(all(
+    feature = "prometheus",
+    feature = "mpsc",
+    feature = "overflow",
+    feature = "resizable",
+    feature = "weighted"
+))]
+mod guard_tests;
+#[cfg(all(feature = "prometheus", feature = "mpsc"))]
+mod merge_tests;
+#[cfg(all(feature = "prometheus", feature = "mpsc"))]
 mod metrics_tests;
+#[cfg(all(
+    feature = "prometheus",
+    feature = "mpsc",
+    feature = "broadcast",
+    feature = "watch"
+))]
+mod named_tests;
+#[cfg(all(
+    feature = "prometheus",
+    feature = "mpsc",
+    feature = "broadcast",
+    feature = "watch",
+    feature = "overflow",
+    feature = "resizable"
+))]
+mod observer_tests;
+#[cfg(all(feature = "prometheus", feature = "overflow"))]
+mod overflow_tests;
+#[cfg(all(feature = "prometheus", feature = "mpsc"))]
+mod rate_limit_tests;
+#[cfg(all(feature = "prometheus", feature = "mpsc", feature = "broadcast"))]
+mod rates_tests;
+#[cfg(all(feature = "prometheus", feature = "resizable"))]
+mod resizable_tests;
+#[cfg(all(
+    feature = "prometheus",
+    feature = "mpsc",
+    feature = "broadcast",
+    feature = "watch",
+    feature = "overflow",
+    feature = "resizable",
+    feature = "weighted"
+))]
+mod stats_tests;
+#[cfg(all(feature = "tasks", feature = "mpsc"))]
+mod topology_tests;
+#[cfg(all(
+    feature = "prometheus",
+    feature = "tracing",
+    feature = "mpsc",
+    feature = "broadcast",
+    feature = "overflow",
+    feature = "resizable",
+    feature = "weighted"
+))]
+mod trace_tests;
+#[cfg(all(
+    feature = "prometheus",
+    feature = "mpsc",
+    feature = "broadcast",
+    feature = "watch",
+    feature = "overflow"
+))]
+mod utilization_tests;
+#[cfg(all(feature = "prometheus", feature = "watch"))]
 mod watch_tests;
+#[cfg(all(feature = "watchdog", feature = "mpsc"))]
+mod watchdog_tests;
+#[cfg(all(feature = "prometheus", feature = "weighted"))]
+mod weighted_tests;
@@ -0,0 +1,57 @@
+use crate::{
+    broadcast_channel_named, default_registry, mpsc_channel_named, set_default_registry,
+    watch_channel_named,
+};
+use prometheus::Registry;
+
+fn gathered_names(registry: &Registry) -> Vec<String> {
+    registry
+        .gather()
+        .iter()
+        .map(|family| family.get_name().to_string())
+        .collect()
+}
+
+// The default registry is process-wide, so everything touching it lives in one test
+#[tokio::test]
+async fn test_named_channels_use_default_registry() {
+    let (tx, mut rx) = mpsc_channel_named("test_named_prometheus_default", 4).unwrap();
+    tx.send(1).await.unwrap();
+    assert_eq!(rx.recv().await, Some(1));
+    assert!(gathered_names(prometheus::default_registry())
+        .contains(&"test_named_prometheus_default_queue_size".to_string()));
+    drop((tx, rx));
+
+    let registry = Registry::new();
+    set_default_registry(&registry);
+    assert!(default_registry().gather().is_empty());
+
+    let (tx1, rx1) = mpsc_channel_named::<i32>("test_named_mpsc", 4).unwrap();
+    // Creating a channel with the same name again shares its metrics
+    let (tx2, _rx2) = mpsc_channel_named::<i32>("test_named_mpsc", 4).unwrap();
+    tx1.send(1).await.unwrap();
+    tx2.send(2).await.unwrap();
+    assert_eq!(rx1.total_messages().unwrap().get(), 2);
+
+    let (btx, _brx) = broadcast_channel_named::<i32>("test_named_broadcast", 4).unwrap();
+    btx.send(1).unwrap();
+    let (wtx, wrx) = watch_channel_named("test_named_watch", 0).unwrap();
+    wtx.send(1).unwrap();
+    assert_eq!(*wrx.borrow(), 1);
+
+    assert_eq!(
+        gathered_names(&registry),
+        vec![
+            "test_named_broadcast_capacity",
+            "test_named_broadcast_queue_size",
+            "test_named_broadcast_total_messages",
+            "test_named_mpsc_capacity",
+            "test_named_mpsc_queue_size",
+            "test_named_mpsc_total_messages",
+            "test_named_watch_queue_size",
+            "test_named_watch_total_messages",
+        ]
+    );
+
+    set_default_registry(prometheus::default_registry());
+}
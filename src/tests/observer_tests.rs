@@ -0,0 +1,274 @@
+use crate::{
+    broadcast_channel_with_observer, mpsc_channel_with_observer, overflow_channel_with_observer,
+    resizable_channel_with_observer, ChannelMetrics, ChannelMetricsOpts, ChannelObserver,
+    CountingObserver, OverflowPolicy,
+};
+use prometheus::Registry;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Event {
+    Send,
+    Recv,
+    Deliver,
+    Drop(usize),
+    Evict(bool),
+    Lag(u64),
+    Wait,
+    Capacity(usize),
+    Close,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<Event>>>);
+
+impl Recorder {
+    fn take(&self) -> Vec<Event> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+
+    fn push(&self, event: Event) {
+        self.0.lock().unwrap().push(event);
+    }
+}
+
+impl ChannelObserver for Recorder {
+    fn on_send(&self) {
+        self.push(Event::Send);
+    }
+
+    fn on_recv(&self, _queued: Option<Duration>) {
+        self.push(Event::Recv);
+    }
+
+    fn on_deliver(&self) {
+        self.push(Event::Deliver);
+    }
+
+    fn on_drop(&self, count: usize) {
+        self.push(Event::Drop(count));
+    }
+
+    fn on_evict(&self, queued: bool) {
+        self.push(Event::Evict(queued));
+    }
+
+    fn on_lag(&self, skipped: u64) {
+        self.push(Event::Lag(skipped));
+    }
+
+    fn on_wait(&self, _waited: Duration) {
+        self.push(Event::Wait);
+    }
+
+    fn on_capacity(&self, capacity: usize) {
+        self.push(Event::Capacity(capacity));
+    }
+
+    fn on_close(&self) {
+        self.push(Event::Close);
+    }
+}
+
+#[tokio::test]
+async fn test_observer_mpsc_events() {
+    let recorder = Recorder::default();
+    let (tx, mut rx) = mpsc_channel_with_observer(1, recorder.clone());
+    assert_eq!(recorder.take(), vec![Event::Capacity(1)]);
+
+    tx.send(1).await.unwrap();
+    let blocked = tokio::spawn({
+        let tx = tx.clone();
+        async move { tx.send(2).await }
+    });
+    tokio::task::yield_now().await;
+
+    assert_eq!(rx.recv().await, Some(1));
+    blocked.await.unwrap().unwrap();
+    assert_eq!(rx.recv().await, Some(2));
+
+    assert_eq!(
+        recorder.take(),
+        vec![
+            Event::Send,
+            Event::Recv,
+            Event::Wait,
+            Event::Send,
+            Event::Recv
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_observer_broadcast_lag() {
+    let recorder = Recorder::default();
+    let (tx, mut rx) = broadcast_channel_with_observer(2, recorder.clone());
+
+    for i in 0..3 {
+        tx.send(i).unwrap();
+    }
+    recorder.take();
+
+    assert!(matches!(rx.recv().await, Err(RecvError::Lagged(1))));
+    assert_eq!(rx.recv().await.unwrap(), 1);
+    assert_eq!(
+        recorder.take(),
+        vec![Event::Lag(1), Event::Recv, Event::Deliver]
+    );
+}
+
+#[tokio::test]
+async fn test_observer_overflow_drops() {
+    let recorder = Recorder::default();
+    let (tx, rx) = overflow_channel_with_observer(1, OverflowPolicy::DropOldest, recorder.clone());
+
+    tx.send(1).await.unwrap();
+    tx.send(2).await.unwrap();
+    drop(rx);
+
+    assert_eq!(
+        recorder.take(),
+        vec![
+            Event::Capacity(1),
+            Event::Send,
+            Event::Send,
+            Event::Evict(true),
+            Event::Drop(1)
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_observer_resize() {
+    let recorder = Recorder::default();
+    let (tx, _rx) = resizable_channel_with_observer::<i32, _>(2, recorder.clone());
+
+    tx.resize_handle().set_capacity(8);
+    assert_eq!(
+        recorder.take(),
+        vec![Event::Capacity(2), Event::Capacity(8)]
+    );
+}
+
+#[tokio::test]
+async fn test_observer_shared_prometheus_backend() {
+    let registry = Registry::new();
+    let opts = ChannelMetricsOpts::new("test_observer_arc")
+        .with_total()
+        .with_latency_histogram(vec![0.1, 1.0]);
+    let metrics = ChannelMetrics::with_opts(&opts, &registry).unwrap();
+
+    let observer: Arc<dyn ChannelObserver> = Arc::new(metrics.clone());
+    let (tx, mut rx) = mpsc_channel_with_observer(4, observer);
+
+    tx.send(1).await.unwrap();
+    tx.send(2).await.unwrap();
+    rx.recv().await.unwrap();
+
+    assert_eq!(metrics.queue_size.get(), 1);
+    assert_eq!(metrics.total_messages.unwrap().get(), 2);
+    assert_eq!(metrics.queue_latency.unwrap().get_sample_count(), 1);
+}
+
+#[tokio::test]
+async fn test_observer_drop_oldest_reports_evictions_once() {
+    let recorder = Recorder::default();
+    let (tx, mut rx) =
+        overflow_channel_with_observer(1, OverflowPolicy::DropOldest, recorder.clone());
+
+    tx.send(1).await.unwrap();
+    tx.send(2).await.unwrap();
+    assert_eq!(rx.recv().await, Some(2));
+    drop(rx);
+
+    // Nothing is left to report as dropped
+    assert_eq!(
+        recorder.take(),
+        vec![
+            Event::Capacity(1),
+            Event::Send,
+            Event::Send,
+            Event::Evict(true),
+            Event::Recv
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_observer_drain_reports_close_once() {
+    let recorder = Recorder::default();
+    let (tx, mut rx) = mpsc_channel_with_observer(2, recorder.clone());
+
+    tx.send(1).await.unwrap();
+    let closing = tokio::spawn(async move { tx.close_and_drain().await });
+    tokio::task::yield_now().await;
+
+    // The receiver closes before it reaches the queued close
+    let report = rx.drain(Duration::from_secs(1), |_| async {}).await;
+    assert_eq!(report.processed, 1);
+    closing.await.unwrap();
+
+    let closes = recorder
+        .take()
+        .into_iter()
+        .filter(|event| *event == Event::Close);
+    assert_eq!(closes.count(), 1);
+}
+
+#[tokio::test]
+async fn test_counting_observer() {
+    let observer = CountingObserver::new();
+    let (tx, mut rx) =
+        overflow_channel_with_observer(2, OverflowPolicy::DropNewest, observer.clone());
+
+    for i in 0..4 {
+        tx.send(i).await.unwrap();
+    }
+    assert_eq!(rx.recv().await, Some(0));
+    assert_eq!(observer.capacity(), 2);
+    assert_eq!(observer.sent(), 2);
+    assert_eq!(observer.received(), 1);
+    assert_eq!(observer.evicted(), 2);
+    assert_eq!(observer.len(), 1);
+
+    drop(rx);
+    assert_eq!(observer.dropped(), 1);
+    assert!(observer.is_empty());
+
+    let observer = CountingObserver::new();
+    let (tx, mut rx) = broadcast_channel_with_observer(1, observer.clone());
+    tx.send(1).unwrap();
+    tx.send(2).unwrap();
+    assert!(rx.recv().await.is_err());
+    assert_eq!(rx.recv().await.unwrap(), 2);
+    assert_eq!(observer.lagged(), 1);
+    assert_eq!(observer.delivered(), 1);
+}
+
+#[tokio::test]
+async fn test_counting_observer_waiting_sends() {
+    let observer = CountingObserver::new();
+    let (tx, mut rx) = mpsc_channel_with_observer(1, observer.clone());
+    tx.send(0).await.unwrap();
+
+    // A cancelled wait is not counted as a completed one
+    let timeout = tokio::time::timeout(Duration::from_millis(10), tx.send(1)).await;
+    assert!(timeout.is_err());
+    assert_eq!((observer.waiting(), observer.waits()), (0, 0));
+
+    let sender = tokio::spawn(async move { tx.send(2).await });
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert_eq!(observer.waiting(), 1);
+    assert_eq!(rx.recv().await, Some(0));
+    sender.await.unwrap().unwrap();
+    assert_eq!((observer.waiting(), observer.waits()), (0, 1));
+
+    let observer = CountingObserver::new();
+    let (tx, _rx) = overflow_channel_with_observer(1, OverflowPolicy::Block, observer.clone());
+    tx.send(0).await.unwrap();
+    let timeout = tokio::time::timeout(Duration::from_millis(10), tx.send(1)).await;
+    assert!(timeout.is_err());
+    assert_eq!((observer.waiting(), observer.waits()), (0, 0));
+}
@@ -0,0 +1,115 @@
+use crate::{overflow_channel, ChannelMetrics, OverflowPolicy, SendError};
+use futures::task::{noop_waker, Context, Poll};
+use futures::FutureExt;
+use prometheus::Registry;
+use tokio::sync::mpsc::error::TryRecvError;
+
+#[tokio::test]
+async fn test_overflow_block() {
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    let registry = Registry::new();
+    let metrics =
+        ChannelMetrics::new_with_evicted("test_block", "test block policy", &registry).unwrap();
+
+    let (tx, mut rx) = overflow_channel::<i32>(1, OverflowPolicy::Block, metrics);
+
+    tx.send(1).await.unwrap();
+    assert!(matches!(tx.try_send(2), Err(SendError::Full(2))));
+
+    let mut send_fut = Box::pin(tx.send(2));
+    assert!(matches!(send_fut.poll_unpin(&mut cx), Poll::Pending));
+
+    assert_eq!(rx.recv().await, Some(1));
+    assert!(send_fut.now_or_never().is_some());
+    assert_eq!(rx.recv().await, Some(2));
+    assert_eq!(rx.evicted_total().unwrap().get(), 0);
+}
+
+#[tokio::test]
+async fn test_overflow_reject() {
+    let registry = Registry::new();
+    let metrics =
+        ChannelMetrics::new_with_evicted("test_reject", "test reject policy", &registry).unwrap();
+
+    let (tx, mut rx) = overflow_channel::<i32>(1, OverflowPolicy::Reject, metrics);
+
+    tx.send(1).await.unwrap();
+    assert!(matches!(tx.send(2).await, Err(SendError::Full(2))));
+
+    assert_eq!(rx.recv().await, Some(1));
+    assert_eq!(rx.total_messages().unwrap().get(), 1);
+    assert_eq!(rx.evicted_total().unwrap().get(), 0);
+}
+
+#[tokio::test]
+async fn test_overflow_drop_oldest() {
+    let registry = Registry::new();
+    let metrics =
+        ChannelMetrics::new_with_evicted("test_oldest", "test drop oldest", &registry).unwrap();
+    let queue_size = metrics.queue_size.clone();
+
+    let (tx, mut rx) = overflow_channel::<i32>(2, OverflowPolicy::DropOldest, metrics);
+
+    for i in 0..5 {
+        tx.send(i).await.unwrap();
+    }
+
+    assert_eq!(queue_size.get(), 2);
+    assert_eq!(rx.recv().await, Some(3));
+    assert_eq!(rx.recv().await, Some(4));
+    assert_eq!(queue_size.get(), 0);
+    assert_eq!(rx.total_messages().unwrap().get(), 5);
+    assert_eq!(rx.evicted_total().unwrap().get(), 3);
+}
+
+#[tokio::test]
+async fn test_overflow_drop_newest() {
+    let registry = Registry::new();
+    let metrics =
+        ChannelMetrics::new_with_evicted("test_newest", "test drop newest", &registry).unwrap();
+
+    let (tx, mut rx) = overflow_channel::<i32>(2, OverflowPolicy::DropNewest, metrics);
+
+    for i in 0..5 {
+        tx.try_send(i).unwrap();
+    }
+
+    assert_eq!(rx.recv().await, Some(0));
+    assert_eq!(rx.recv().await, Some(1));
+    assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+    assert_eq!(rx.total_messages().unwrap().get(), 2);
+    assert_eq!(rx.evicted_total().unwrap().get(), 3);
+}
+
+#[tokio::test]
+async fn test_overflow_senders_dropped() {
+    let registry = Registry::new();
+    let metrics = ChannelMetrics::new_basic("test_senders", "test senders", &registry).unwrap();
+
+    let (tx, mut rx) = overflow_channel::<i32>(4, OverflowPolicy::DropOldest, metrics);
+
+    tx.send(1).await.unwrap();
+    drop(tx);
+
+    // Buffered values are still delivered after all senders are gone
+    assert_eq!(rx.recv().await, Some(1));
+    assert_eq!(rx.recv().await, None);
+}
+
+#[tokio::test]
+async fn test_overflow_receiver_dropped() {
+    let registry = Registry::new();
+    let metrics = ChannelMetrics::new_basic("test_receiver", "test receiver", &registry).unwrap();
+    let queue_size = metrics.queue_size.clone();
+
+    let (tx, rx) = overflow_channel::<i32>(4, OverflowPolicy::Block, metrics);
+
+    tx.send(1).await.unwrap();
+    drop(rx);
+
+    assert!(tx.is_closed());
+    assert!(matches!(tx.send(2).await, Err(SendError::Closed(2))));
+    assert_eq!(queue_size.get(), 0);
+}
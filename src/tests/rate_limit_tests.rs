@@ -0,0 +1,144 @@
+use crate::{mpsc_channel, ChannelMetrics, RateLimitedSender, SendError, ThrottleMetrics};
+use prometheus::Registry;
+use std::time::{Duration, Instant};
+
+#[tokio::test]
+async fn test_rate_limited_burst() {
+    let registry = Registry::new();
+    let metrics = ChannelMetrics::new_basic("test_burst", "test burst", &registry).unwrap();
+    let throttle = ThrottleMetrics::new("test_burst", "test burst", &registry).unwrap();
+
+    let (tx, mut rx) = mpsc_channel(10, metrics);
+    let tx = RateLimitedSender::new(tx, 1.0, 3, throttle.clone());
+
+    // The initial burst goes through without throttling
+    for i in 0..3 {
+        tx.send(i).await.unwrap();
+    }
+
+    assert_eq!(throttle.throttled_total.get(), 0);
+    assert_eq!(throttle.throttle_wait.get_sample_count(), 3);
+    assert_eq!(throttle.capacity_wait.get_sample_count(), 3);
+    assert_eq!(rx.recv().await, Some(0));
+}
+
+#[tokio::test]
+async fn test_rate_limited_throttles() {
+    let registry = Registry::new();
+    let metrics = ChannelMetrics::new_basic("test_throttle", "test throttle", &registry).unwrap();
+    let throttle = ThrottleMetrics::new("test_throttle", "test throttle", &registry).unwrap();
+
+    let (tx, _rx) = mpsc_channel(10, metrics);
+    let tx = RateLimitedSender::new(tx, 100.0, 1, throttle.clone());
+
+    let start = Instant::now();
+    for i in 0..5 {
+        tx.send(i).await.unwrap();
+    }
+
+    assert!(start.elapsed() >= Duration::from_millis(35));
+    assert_eq!(throttle.throttled_total.get(), 4);
+    assert!(throttle.throttle_wait.get_sample_sum() > 0.03);
+}
+
+#[tokio::test]
+async fn test_rate_limited_set_rate() {
+    let registry = Registry::new();
+    let metrics = ChannelMetrics::new_basic("test_set_rate", "test set rate", &registry).unwrap();
+    let throttle = ThrottleMetrics::new("test_set_rate", "test set rate", &registry).unwrap();
+
+    let (tx, _rx) = mpsc_channel(10, metrics);
+    let tx = RateLimitedSender::new(tx, 0.1, 1, throttle.clone());
+    let clone = tx.clone();
+
+    tx.send(1).await.unwrap();
+
+    // At the initial rate the next token would take ten seconds
+    clone.set_rate(1000.0, 1);
+    assert_eq!(tx.rate(), 1000.0);
+
+    tokio::time::timeout(Duration::from_secs(1), tx.send(2))
+        .await
+        .expect("send should not wait for the old rate")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_rate_limited_closed() {
+    let registry = Registry::new();
+    let metrics = ChannelMetrics::new_basic("test_rl_closed", "test closed", &registry).unwrap();
+    let throttle = ThrottleMetrics::new("test_rl_closed", "test closed", &registry).unwrap();
+
+    let (tx, rx) = mpsc_channel::<i32>(10, metrics);
+    let tx = RateLimitedSender::new(tx, 1.0, 1, throttle);
+    drop(rx);
+
+    assert!(tx.is_closed());
+    assert!(matches!(tx.send(1).await, Err(SendError::Closed(1))));
+}
+
+#[tokio::test]
+async fn test_rate_limited_set_rate_wakes_waiting_send() {
+    let registry = Registry::new();
+    let metrics = ChannelMetrics::new_basic("test_rl_wake", "test wake", &registry).unwrap();
+    let throttle = ThrottleMetrics::new("test_rl_wake", "test wake", &registry).unwrap();
+
+    let (tx, _rx) = mpsc_channel(10, metrics);
+    let tx = RateLimitedSender::new(tx, 0.1, 1, throttle);
+    tx.send(1).await.unwrap();
+
+    // This send computed a ten second wait before the rate changes
+    let waiting = tokio::spawn({
+        let tx = tx.clone();
+        async move { tx.send(2).await }
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    tx.set_rate(1000.0, 1);
+
+    tokio::time::timeout(Duration::from_secs(1), waiting)
+        .await
+        .expect("waiting send should see the new rate")
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_rate_limited_closed_while_throttled() {
+    let registry = Registry::new();
+    let metrics = ChannelMetrics::new_basic("test_rl_close_wait", "test", &registry).unwrap();
+    let throttle = ThrottleMetrics::new("test_rl_close_wait", "test", &registry).unwrap();
+
+    let (tx, rx) = mpsc_channel(10, metrics);
+    let tx = RateLimitedSender::new(tx, 0.1, 1, throttle);
+    tx.send(1).await.unwrap();
+
+    let waiting = tokio::spawn({
+        let tx = tx.clone();
+        async move { tx.send(2).await }
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    drop(rx);
+
+    let result = tokio::time::timeout(Duration::from_secs(1), waiting)
+        .await
+        .expect("closing should fail the throttled send")
+        .unwrap();
+    assert!(matches!(result, Err(SendError::Closed(2))));
+}
+
+#[test]
+fn test_throttle_metrics_failure_leaves_nothing_registered() {
+    let registry = Registry::new();
+    let foreign = prometheus::IntGauge::new(
+        "test_rl_partial_capacity_wait_seconds",
+        "Time spent waiting for capacity by test partial sender",
+    )
+    .unwrap();
+    registry.register(Box::new(foreign.clone())).unwrap();
+
+    assert!(ThrottleMetrics::new("test_rl_partial", "test partial", &registry).is_err());
+    assert_eq!(registry.gather().len(), 1);
+
+    registry.unregister(Box::new(foreign)).unwrap();
+    assert!(ThrottleMetrics::new("test_rl_partial", "test partial", &registry).is_ok());
+}
@@ -0,0 +1,84 @@
+use crate::{
+    broadcast_channel, mpsc_channel, ChannelMetrics, ChannelMetricsOpts, MeteredChannelBuilder,
+};
+use prometheus::Registry;
+use std::time::Duration;
+
+fn metrics(name: &str) -> ChannelMetrics {
+    let opts = ChannelMetricsOpts::new(name).with_rates();
+    ChannelMetrics::with_opts(&opts, &Registry::new()).unwrap()
+}
+
+#[tokio::test]
+async fn test_rates_follow_bursts() {
+    let (tx, mut rx) = mpsc_channel(200, metrics("test_rates_burst"));
+    for i in 0..100 {
+        tx.send(i).await.unwrap();
+    }
+    for _ in 0..40 {
+        rx.recv().await.unwrap();
+    }
+
+    let throughput = rx.observer().throughput().unwrap();
+    assert!(throughput.send.one_second > 90.0 && throughput.send.one_second <= 100.0);
+    assert!(throughput.send.ten_seconds > 9.0 && throughput.send.ten_seconds <= 10.0);
+    assert!(throughput.send.one_minute > 1.5 && throughput.send.one_minute <= 100.0 / 60.0);
+    assert!(throughput.recv.one_second > 36.0 && throughput.recv.one_second <= 40.0);
+    assert_eq!(throughput.depth, 60);
+}
+
+#[tokio::test]
+async fn test_rates_decay() {
+    let (tx, _rx) = mpsc_channel(200, metrics("test_rates_decay"));
+    for i in 0..100 {
+        tx.send(i).await.unwrap();
+    }
+    let before = tx.observer().throughput().unwrap();
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let after = tx.observer().throughput().unwrap();
+    assert!(after.send.one_second < before.send.one_second * (-0.1f64).exp());
+    assert!(after.send.ten_seconds < before.send.ten_seconds);
+    assert!(
+        after.send.one_second / before.send.one_second
+            < after.send.one_minute / before.send.one_minute
+    );
+    assert_eq!(after.recv.one_second, 0.0);
+}
+
+#[tokio::test]
+async fn test_rates_disabled_by_default() {
+    let opts = ChannelMetricsOpts::new("test_rates_disabled");
+    let metrics = ChannelMetrics::with_opts(&opts, &Registry::new()).unwrap();
+    assert!(metrics.throughput().is_none());
+}
+
+#[tokio::test]
+async fn test_rates_count_each_broadcast_delivery() {
+    let (tx, mut rx1) = broadcast_channel(16, metrics("test_rates_broadcast"));
+    let mut rx2 = tx.subscribe();
+    tx.send(1).unwrap();
+    rx1.recv().await.unwrap();
+    rx2.recv().await.unwrap();
+
+    let throughput = tx.observer().throughput().unwrap();
+    assert!(throughput.send.one_second > 0.9 && throughput.send.one_second <= 1.0);
+    assert!(throughput.recv.one_second > 1.8 && throughput.recv.one_second <= 2.0);
+}
+
+#[tokio::test]
+async fn test_builder_rates() {
+    let registry = Registry::new();
+    let (tx, _rx) = MeteredChannelBuilder::new()
+        .name("test_builder_rates")
+        .capacity(4)
+        .registry(&registry)
+        .with_rates()
+        .build_mpsc()
+        .unwrap();
+
+    tx.send(1).await.unwrap();
+    let throughput = tx.observer().throughput().unwrap();
+    assert!(throughput.send.one_second > 0.0);
+    assert_eq!(throughput.depth, 1);
+}
@@ -0,0 +1,119 @@
+use crate::{resizable_channel, ChannelMetrics, SendError};
+use futures::task::{noop_waker, Context, Poll};
+use futures::FutureExt;
+use prometheus::Registry;
+
+#[tokio::test]
+async fn test_resizable_basic() {
+    let registry = Registry::new();
+    let metrics =
+        ChannelMetrics::new_with_capacity("test_resize", "test resizable", &registry).unwrap();
+    let queue_size = metrics.queue_size.clone();
+
+    let (tx, mut rx) = resizable_channel(2, metrics);
+
+    tx.send(1).await.unwrap();
+    tx.send(2).await.unwrap();
+    assert!(matches!(tx.try_send(3), Err(SendError::Full(3))));
+    assert_eq!(queue_size.get(), 2);
+
+    assert_eq!(rx.recv().await, Some(1));
+    assert_eq!(rx.recv().await, Some(2));
+    assert_eq!(queue_size.get(), 0);
+    assert_eq!(rx.total_messages().unwrap().get(), 2);
+}
+
+#[tokio::test]
+async fn test_resizable_grow_wakes_senders() {
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    let registry = Registry::new();
+    let metrics = ChannelMetrics::new_with_capacity("test_grow", "test grow", &registry).unwrap();
+    let capacity = metrics.capacity.clone().unwrap();
+
+    let (tx, _rx) = resizable_channel(1, metrics);
+    assert_eq!(capacity.get(), 1);
+
+    tx.send(1).await.unwrap();
+    let mut send_fut = Box::pin(tx.send(2));
+    assert!(matches!(send_fut.poll_unpin(&mut cx), Poll::Pending));
+
+    tx.resize_handle().set_capacity(3);
+    assert_eq!(capacity.get(), 3);
+    assert_eq!(tx.capacity(), 3);
+
+    // The waiting sender completes without any value being received
+    assert!(send_fut.now_or_never().is_some());
+    tx.try_send(3).unwrap();
+    assert!(tx.try_send(4).is_err());
+}
+
+#[tokio::test]
+async fn test_resizable_shrink() {
+    let registry = Registry::new();
+    let metrics =
+        ChannelMetrics::new_with_capacity("test_shrink", "test shrink", &registry).unwrap();
+
+    let (tx, mut rx) = resizable_channel(4, metrics);
+    let handle = rx.resize_handle();
+
+    for i in 0..4 {
+        tx.send(i).await.unwrap();
+    }
+
+    // Queued values are kept, but sends wait until the queue drains below the new capacity
+    handle.set_capacity(2);
+    assert_eq!(rx.recv().await, Some(0));
+    assert!(tx.try_send(10).is_err());
+    assert_eq!(rx.recv().await, Some(1));
+    assert!(tx.try_send(10).is_err());
+    assert_eq!(rx.recv().await, Some(2));
+    tx.try_send(10).unwrap();
+    assert!(tx.try_send(11).is_err());
+
+    // Growing again repays the outstanding shrink first
+    handle.set_capacity(3);
+    tx.try_send(11).unwrap();
+    assert!(tx.try_send(12).is_err());
+}
+
+#[tokio::test]
+async fn test_resizable_closed() {
+    let registry = Registry::new();
+    let metrics =
+        ChannelMetrics::new_with_capacity("test_rs_closed", "test closed", &registry).unwrap();
+
+    let (tx, rx) = resizable_channel::<i32>(1, metrics);
+    let handle = tx.resize_handle();
+    tx.send(1).await.unwrap();
+
+    let waiting = tokio::spawn({
+        let tx = tx.clone();
+        async move { tx.send(2).await }
+    });
+    tokio::task::yield_now().await;
+    drop(rx);
+
+    assert!(matches!(waiting.await.unwrap(), Err(SendError::Closed(2))));
+    assert!(tx.is_closed());
+    assert_eq!(handle.capacity(), 1);
+}
+
+#[tokio::test]
+async fn test_resizable_drop_receiver_discards_queued() {
+    let registry = Registry::new();
+    let metrics =
+        ChannelMetrics::new_with_capacity("test_rs_discard", "test discard", &registry).unwrap();
+    let queue_size = metrics.queue_size.clone();
+
+    let (tx, rx) = resizable_channel(2, metrics);
+    let handle = tx.resize_handle();
+    tx.send(1).await.unwrap();
+    tx.send(2).await.unwrap();
+    drop(rx);
+
+    assert_eq!(queue_size.get(), 0);
+    assert_eq!(handle.len(), 0);
+    assert!(matches!(tx.try_send(3), Err(SendError::Closed(3))));
+}
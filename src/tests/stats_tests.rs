@@ -0,0 +1,170 @@
+use crate::weighted::{ByteLen, Limit};
+use crate::{
+    broadcast_channel, live_channel, live_channels, mpsc_channel, overflow_channel,
+    resizable_channel_with_observer, watch_channel, weighted_channel_with_observer, ChannelKind,
+    ChannelMetrics, ChannelMetricsOpts, CountingObserver, MeteredChannelBuilder, OverflowPolicy,
+};
+use prometheus::Registry;
+
+fn metrics(name: &str) -> ChannelMetrics {
+    let opts = ChannelMetricsOpts::new(name).with_stats();
+    ChannelMetrics::with_opts(&opts, &Registry::new()).unwrap()
+}
+
+#[tokio::test]
+async fn test_stats_track_mpsc_handles() {
+    let (tx, mut rx) = mpsc_channel(8, metrics("test_stats_mpsc"));
+    let tx2 = tx.clone();
+    tx.send(1).await.unwrap();
+    tx2.send(2).await.unwrap();
+    rx.recv().await.unwrap();
+
+    let stats = live_channel("test_stats_mpsc").pop().unwrap();
+    assert_eq!(stats.kind, Some(ChannelKind::Mpsc));
+    assert_eq!(stats.capacity, Some(8));
+    assert_eq!((stats.len, stats.sent, stats.received), (1, 2, 1));
+    assert_eq!((stats.senders, stats.receivers), (2, 1));
+    assert!(!stats.closed);
+
+    drop(tx2);
+    assert_eq!(tx.observer().stats().unwrap().senders, 1);
+    drop(rx);
+    let stats = tx.observer().stats().unwrap();
+    assert_eq!(stats.receivers, 0);
+    assert!(stats.closed);
+}
+
+#[tokio::test]
+async fn test_live_channels_lists_live_channels() {
+    let (tx, rx) = mpsc_channel::<i32>(4, metrics("test_stats_listed_b"));
+    let listed = mpsc_channel::<i32>(4, metrics("test_stats_listed_a"));
+    let unlisted = mpsc_channel::<i32>(
+        4,
+        ChannelMetrics::with_opts(
+            &ChannelMetricsOpts::new("test_stats_unlisted"),
+            &Registry::new(),
+        )
+        .unwrap(),
+    );
+
+    let names: Vec<_> = live_channels()
+        .into_iter()
+        .map(|stats| stats.name)
+        .filter(|name| name.starts_with("test_stats_"))
+        .collect();
+    assert!(names.contains(&"test_stats_listed_a".to_string()));
+    assert!(names.contains(&"test_stats_listed_b".to_string()));
+    assert!(!names.contains(&"test_stats_unlisted".to_string()));
+    let a = names.iter().position(|name| name == "test_stats_listed_a");
+    let b = names.iter().position(|name| name == "test_stats_listed_b");
+    assert!(a < b);
+    assert!(unlisted.0.observer().stats().is_none());
+
+    drop((tx, rx));
+    assert!(live_channel("test_stats_listed_b").is_empty());
+    drop(listed);
+    assert!(live_channel("test_stats_listed_a").is_empty());
+}
+
+#[tokio::test]
+async fn test_stats_other_kinds() {
+    let (tx, _rx) = broadcast_channel::<i32>(4, metrics("test_stats_broadcast"));
+    let _rx2 = tx.subscribe();
+    let stats = live_channel("test_stats_broadcast").pop().unwrap();
+    assert_eq!(stats.kind, Some(ChannelKind::Broadcast));
+    assert_eq!((stats.senders, stats.receivers), (1, 2));
+
+    let (tx, rx) = watch_channel(0, metrics("test_stats_watch"));
+    let _rx2 = rx.clone();
+    drop(tx);
+    let stats = live_channel("test_stats_watch").pop().unwrap();
+    assert_eq!(stats.kind, Some(ChannelKind::Watch));
+    assert_eq!(stats.capacity, None);
+    assert_eq!((stats.senders, stats.receivers), (0, 2));
+    assert!(stats.closed);
+
+    let (tx, mut rx) = overflow_channel(2, OverflowPolicy::DropOldest, metrics("test_stats_ovf"));
+    for i in 0..3 {
+        tx.send(i).await.unwrap();
+    }
+    rx.close();
+    let stats = live_channel("test_stats_ovf").pop().unwrap();
+    assert_eq!(stats.kind, Some(ChannelKind::Overflow));
+    assert_eq!((stats.len, stats.sent, stats.dropped), (2, 3, 0));
+    assert_eq!((stats.senders, stats.receivers), (1, 1));
+    assert!(stats.closed);
+}
+
+#[tokio::test]
+async fn test_counting_observer_handles() {
+    let observer = CountingObserver::new();
+    let (tx, rx) = resizable_channel_with_observer::<i32, _>(4, observer.clone());
+    let tx2 = tx.clone();
+    assert_eq!((observer.senders(), observer.receivers()), (2, 1));
+    drop((tx, tx2, rx));
+    assert_eq!((observer.senders(), observer.receivers()), (0, 0));
+
+    let (tx, rx) = weighted_channel_with_observer(Limit::Bytes(8), ByteLen, observer.clone());
+    tx.try_send(vec![1u8]).unwrap();
+    assert_eq!((observer.senders(), observer.receivers()), (1, 1));
+    drop(rx);
+    assert_eq!(observer.receivers(), 0);
+}
+
+#[tokio::test]
+async fn test_shared_channels_share_stats() {
+    let registry = Registry::new();
+    let build = || {
+        MeteredChannelBuilder::new()
+            .name("test_stats_shared")
+            .capacity(4)
+            .registry(&registry)
+            .with_stats()
+            .shared()
+            .build_mpsc::<i32>()
+            .unwrap()
+    };
+    let (tx1, _rx1) = build();
+    let (tx2, _rx2) = build();
+    tx1.try_send(1).unwrap();
+    tx2.try_send(2).unwrap();
+
+    let stats = live_channel("test_stats_shared").pop().unwrap();
+    assert_eq!((stats.sent, stats.senders, stats.receivers), (2, 2, 2));
+}
+
+#[tokio::test]
+async fn test_channels_sharing_a_name_are_tracked_apart() {
+    let registry = Registry::new();
+    let build = |pipeline: &str| {
+        MeteredChannelBuilder::new()
+            .name("test_stats_same_name")
+            .capacity(4)
+            .registry(&registry)
+            .const_label("pipeline", pipeline)
+            .with_stats()
+            .build_mpsc::<i32>()
+            .unwrap()
+    };
+    let (ingest, ingest_rx) = build("ingest");
+    let (egress, _egress_rx) = build("egress");
+    ingest.try_send(1).unwrap();
+    drop(ingest_rx);
+    egress.try_send(2).unwrap();
+    egress.try_send(3).unwrap();
+
+    let stats = live_channel("test_stats_same_name");
+    let summary: Vec<_> = stats
+        .iter()
+        .map(|stats| (stats.labels["pipeline"].as_str(), stats.sent, stats.closed))
+        .collect();
+    assert_eq!(summary, vec![("egress", 2, false), ("ingest", 1, true)]);
+
+    // A channel reusing the name of a closed one starts open
+    let (_tx, _rx) = mpsc_channel::<i32>(4, metrics("test_stats_same_name"));
+    let fresh = live_channel("test_stats_same_name")
+        .into_iter()
+        .find(|stats| stats.labels.is_empty())
+        .unwrap();
+    assert_eq!((fresh.sent, fresh.closed), (0, false));
+}
@@ -0,0 +1,89 @@
+use crate::{
+    mpsc_channel, topology, track_task, ChannelMetrics, ChannelMetricsOpts, MpscReceiver,
+    MpscSender, Topology,
+};
+use prometheus::Registry;
+
+fn channel(opts: ChannelMetricsOpts, capacity: usize) -> (MpscSender<u32>, MpscReceiver<u32>) {
+    let metrics = ChannelMetrics::with_opts(&opts.with_stats(), &Registry::new()).unwrap();
+    mpsc_channel(capacity, metrics)
+}
+
+/// The part of the topology made of channels named with `prefix`
+fn topology_of(prefix: &str) -> Topology {
+    let mut topology = topology();
+    topology
+        .channels
+        .retain(|channel| channel.stats.name.starts_with(prefix));
+    topology
+}
+
+#[tokio::test]
+async fn test_topology_inferred_from_tasks() {
+    let (to_parse, mut parse_in) = channel(ChannelMetricsOpts::new("test_topology_raw"), 4);
+    let (to_index, mut index_in) = channel(ChannelMetricsOpts::new("test_topology_parsed"), 4);
+
+    track_task("reader", async { to_parse.send(1).await.unwrap() }).await;
+    track_task("parser", async {
+        let value = parse_in.recv().await.unwrap();
+        to_index.send(value).await.unwrap();
+    })
+    .await;
+    track_task("indexer", async { index_in.recv().await.unwrap() }).await;
+
+    let topology = topology_of("test_topology_");
+    assert_eq!(topology.stages(), ["indexer", "parser", "reader"]);
+    let parsed = &topology.channels[0];
+    assert_eq!(parsed.stats.name, "test_topology_parsed");
+    assert_eq!(
+        (parsed.producers.as_slice(), parsed.consumers.as_slice()),
+        (&["parser".to_string()][..], &["indexer".to_string()][..])
+    );
+
+    let dot = topology.to_dot();
+    assert!(dot.starts_with("digraph channels {\n"));
+    assert!(
+        dot.contains("    \"reader\" -> \"parser\" [label=\"test_topology_raw\\n0/4 queued\"];\n")
+    );
+    assert!(dot.contains(
+        "    \"parser\" -> \"indexer\" [label=\"test_topology_parsed\\n0/4 queued\"];\n"
+    ));
+}
+
+#[tokio::test]
+async fn test_topology_declared_and_unknown_stages() {
+    let (tx, _rx) = channel(ChannelMetricsOpts::new("test_topology_declared"), 8);
+    let (_orphan_tx, _orphan_rx) =
+        channel(ChannelMetricsOpts::new("test_topology_declared_orphan"), 8);
+    tx.observer().produced_by("ingest");
+    tx.observer().consumed_by("sink_a");
+    tx.observer().consumed_by("sink_b");
+    tx.send(1).await.unwrap();
+
+    let dot = topology_of("test_topology_declared").to_dot();
+    assert!(
+        dot.contains("\"ingest\" -> \"sink_a\" [label=\"test_topology_declared\\n1/8 queued\"]")
+    );
+    assert!(dot.contains("\"ingest\" -> \"sink_b\""));
+    // Channels without known tasks start and end at points
+    assert!(dot.contains("    \"test_topology_declared_orphan/in\" [shape=point];\n"));
+    assert!(dot.contains(
+        "    \"test_topology_declared_orphan/in\" -> \"test_topology_declared_orphan/out\""
+    ));
+}
+
+#[tokio::test]
+async fn test_topology_json() {
+    let opts = ChannelMetricsOpts::new("test_topology_json").with_rates();
+    let (tx, _rx) = channel(opts, 2);
+    track_task("json \"writer\"", async { tx.send(1).await.unwrap() }).await;
+    let (_untracked_tx, _untracked_rx) =
+        channel(ChannelMetricsOpts::new("test_topology_jsonless"), 2);
+
+    let topology = topology_of("test_topology_json");
+    assert!(topology.channels[0].throughput.unwrap().send.one_second > 0.0);
+    let json = topology.to_json();
+    assert!(json.starts_with("{\"stages\":[\"json \\\"writer\\\"\"],\"channels\":[{\"name\":\"test_topology_json\",\"labels\":{},\"kind\":\"mpsc\",\"capacity\":2,\"len\":1,"));
+    assert!(json.contains(",\"producers\":[\"json \\\"writer\\\"\"],\"consumers\":[],\"throughput\":{\"send\":{\"one_second\":"));
+    assert!(json.ends_with(",\"producers\":[],\"consumers\":[],\"throughput\":null}]}"));
+}
@@ -0,0 +1,266 @@
+use crate::{
+    broadcast_channel_with_observer, mpsc_channel_with_observer, mpsc_channel_with_total,
+    overflow_channel_with_observer, resizable_channel_with_observer, weighted::Limit,
+    weighted_channel_with_observer, ChannelObserver, ChannelTrace, MeteredChannelBuilder,
+    OverflowPolicy,
+};
+use prometheus::{IntCounter, IntGauge, Registry};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Instrument, Level, Subscriber};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+#[derive(Debug, Clone, PartialEq)]
+struct SpanRecord {
+    name: &'static str,
+    level: Level,
+    channel: String,
+}
+
+#[derive(Debug, Default)]
+struct Log {
+    spans: Vec<SpanRecord>,
+    follows: Vec<(&'static str, &'static str)>,
+}
+
+#[derive(Clone, Default)]
+struct SpanLog(Arc<Mutex<Log>>);
+
+impl SpanLog {
+    fn spans(&self) -> Vec<SpanRecord> {
+        std::mem::take(&mut self.0.lock().unwrap().spans)
+    }
+
+    fn follows(&self) -> Vec<(&'static str, &'static str)> {
+        std::mem::take(&mut self.0.lock().unwrap().follows)
+    }
+}
+
+struct ChannelField(String);
+
+impl Visit for ChannelField {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "channel" {
+            self.0 = value.to_string();
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn fmt::Debug) {}
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for SpanLog {
+    fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+        let mut channel = ChannelField(String::new());
+        attrs.record(&mut channel);
+        self.0.lock().unwrap().spans.push(SpanRecord {
+            name: attrs.metadata().name(),
+            level: *attrs.metadata().level(),
+            channel: channel.0,
+        });
+    }
+
+    fn on_follows_from(&self, span: &Id, follows: &Id, ctx: Context<'_, S>) {
+        let (Some(span), Some(follows)) = (ctx.span(span), ctx.span(follows)) else {
+            return;
+        };
+        self.0
+            .lock()
+            .unwrap()
+            .follows
+            .push((span.name(), follows.name()));
+    }
+}
+
+fn subscribe() -> (SpanLog, tracing::subscriber::DefaultGuard) {
+    let log = SpanLog::default();
+    let subscriber = tracing_subscriber::registry().with(log.clone());
+    (log, tracing::subscriber::set_default(subscriber))
+}
+
+#[derive(Clone)]
+struct Named(&'static str, ChannelTrace);
+
+impl ChannelObserver for Named {
+    fn name(&self) -> &str {
+        self.0
+    }
+
+    fn trace(&self) -> ChannelTrace {
+        self.1
+    }
+}
+
+fn span(name: &'static str, level: Level, channel: &str) -> SpanRecord {
+    SpanRecord {
+        name,
+        level,
+        channel: channel.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_spans_record_channel_name() {
+    let (log, _guard) = subscribe();
+    let (tx, mut rx) = mpsc_channel_with_observer(4, Named("ingest", ChannelTrace::new()));
+
+    tx.send(1).await.unwrap();
+    rx.recv().await.unwrap();
+
+    assert_eq!(
+        log.spans(),
+        vec![
+            span("send", Level::DEBUG, "ingest"),
+            span("recv", Level::DEBUG, "ingest")
+        ]
+    );
+    assert!(log.follows().is_empty());
+}
+
+#[tokio::test]
+async fn test_span_level_per_channel() {
+    let (log, _guard) = subscribe();
+    let (tx, mut rx) =
+        mpsc_channel_with_observer(4, Named("hot", ChannelTrace::new().level(Level::TRACE)));
+    let (btx, mut brx) =
+        broadcast_channel_with_observer(4, Named("events", ChannelTrace::new().level(Level::INFO)));
+
+    tx.send(1).await.unwrap();
+    rx.recv().await.unwrap();
+    btx.send(2).unwrap();
+    brx.recv().await.unwrap();
+
+    assert_eq!(
+        log.spans(),
+        vec![
+            span("send", Level::TRACE, "hot"),
+            span("recv", Level::TRACE, "hot"),
+            span("send", Level::INFO, "events"),
+            span("recv", Level::INFO, "events")
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_propagation_links_receiver_to_sender() {
+    let (log, _guard) = subscribe();
+    let trace = ChannelTrace::new().propagate(true);
+    let (tx, mut rx) = mpsc_channel_with_observer(4, Named("ingest", trace));
+
+    async {
+        tx.send(1).await.unwrap();
+        tx.try_send(2).unwrap();
+    }
+    .instrument(tracing::info_span!("produce"))
+    .await;
+    async {
+        rx.recv().await.unwrap();
+        rx.try_recv().unwrap();
+    }
+    .instrument(tracing::info_span!("consume"))
+    .await;
+
+    // The caller's spans are linked, not the channel's own send and recv spans
+    assert_eq!(log.follows(), vec![("consume", "produce"); 2]);
+}
+
+#[tokio::test]
+async fn test_propagation_across_channel_kinds() {
+    let (log, _guard) = subscribe();
+    let trace = ChannelTrace::new().propagate(true);
+
+    let produce = || tracing::info_span!("produce");
+    let consume = || tracing::info_span!("consume");
+
+    let (tx, mut rx) =
+        overflow_channel_with_observer(2, OverflowPolicy::DropOldest, Named("overflow", trace));
+    tx.send(1).instrument(produce()).await.unwrap();
+    rx.recv().instrument(consume()).await.unwrap();
+
+    let (tx, mut rx) = resizable_channel_with_observer(2, Named("resizable", trace));
+    tx.send(1).instrument(produce()).await.unwrap();
+    rx.recv().instrument(consume()).await.unwrap();
+
+    let (tx, mut rx) =
+        weighted_channel_with_observer(Limit::Items(2), |_: &i32| 1, Named("weighted", trace));
+    tx.send(1).instrument(produce()).await.unwrap();
+    rx.recv().instrument(consume()).await.unwrap();
+
+    let (tx, mut rx) = broadcast_channel_with_observer(2, Named("broadcast", trace));
+    produce().in_scope(|| tx.send(1)).unwrap();
+    rx.recv().instrument(consume()).await.unwrap();
+
+    assert_eq!(log.follows(), vec![("consume", "produce"); 4]);
+}
+
+#[tokio::test]
+async fn test_metrics_name_channel_spans() {
+    let (log, _guard) = subscribe();
+    let registry = Registry::new();
+    let (tx, mut rx) = MeteredChannelBuilder::new()
+        .namespace("svc")
+        .name("traced")
+        .capacity(4)
+        .registry(&registry)
+        .trace(ChannelTrace::new().level(Level::INFO))
+        .build_mpsc::<i32>()
+        .unwrap();
+
+    tx.send(1).await.unwrap();
+    rx.recv().await.unwrap();
+    assert_eq!(
+        log.spans(),
+        vec![
+            span("send", Level::INFO, "svc_traced"),
+            span("recv", Level::INFO, "svc_traced")
+        ]
+    );
+
+    let gauge = IntGauge::new("collected_queue_size", "queue size").unwrap();
+    let total = IntCounter::new("collected_total_messages", "total").unwrap();
+    let (tx, _rx) = mpsc_channel_with_total::<i32>(4, &gauge, &total);
+    assert_eq!(tx.observer().name(), "collected");
+}
+
+#[tokio::test]
+async fn test_recv_traced_returns_sender_span() {
+    let (log, _guard) = subscribe();
+    let trace = ChannelTrace::new().propagate(true);
+    let (tx, mut rx) = mpsc_channel_with_observer(4, Named("ingest", trace));
+
+    async {
+        tx.send(1).await.unwrap();
+        tx.try_send(2).unwrap();
+    }
+    .instrument(tracing::info_span!("produce"))
+    .await;
+
+    let (value, span) = rx.recv_traced().await.unwrap();
+    assert_eq!(value, 1);
+    assert_eq!(span.metadata().unwrap().name(), "produce");
+
+    let (value, span) = rx.try_recv_traced().unwrap();
+    assert_eq!(value, 2);
+    assert_eq!(span.metadata().unwrap().name(), "produce");
+
+    // Without a consumer span there is nothing to link
+    assert!(log.follows().is_empty());
+}
+
+#[tokio::test]
+async fn test_recv_traced_without_propagation() {
+    let (_log, _guard) = subscribe();
+    let (tx, mut rx) = mpsc_channel_with_observer(4, Named("ingest", ChannelTrace::new()));
+
+    tx.send(1)
+        .instrument(tracing::info_span!("produce"))
+        .await
+        .unwrap();
+
+    let (value, span) = rx.recv_traced().await.unwrap();
+    assert_eq!(value, 1);
+    assert!(span.is_none());
+}
@@ -0,0 +1,166 @@
+use crate::{
+    broadcast_channel_with_observer, mpsc_channel, mpsc_channel_with_observer,
+    overflow_channel_with_observer, watch_channel_with_observer, ChannelMetrics,
+    ChannelMetricsOpts, CountingObserver, MeteredChannelBuilder, OverflowPolicy,
+};
+use prometheus::Registry;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_idle_time_while_waiting() {
+    let registry = Registry::new();
+    let opts = ChannelMetricsOpts::new("test_idle_wait").with_utilization();
+    let metrics = ChannelMetrics::with_opts(&opts, &registry).unwrap();
+    let idle_time = metrics.idle_time.clone().unwrap();
+
+    let (tx, mut rx) = mpsc_channel(4, metrics);
+    let sender = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        tx.send(1).await.unwrap();
+    });
+
+    assert_eq!(rx.recv().await, Some(1));
+    assert!(idle_time.get() >= 0.02);
+    sender.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_busy_time_between_receives() {
+    let registry = Registry::new();
+    let opts = ChannelMetricsOpts::new("test_busy_time").with_utilization();
+    let metrics = ChannelMetrics::with_opts(&opts, &registry).unwrap();
+    let idle_time = metrics.idle_time.clone().unwrap();
+    let busy_time = metrics.busy_time.clone().unwrap();
+
+    let (tx, mut rx) = mpsc_channel(4, metrics);
+    tx.send(1).await.unwrap();
+    tx.send(2).await.unwrap();
+
+    rx.recv().await.unwrap();
+    assert_eq!(busy_time.get(), 0.0);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    rx.recv().await.unwrap();
+
+    assert_eq!(idle_time.get(), 0.0);
+    assert!(busy_time.get() >= 0.02);
+}
+
+#[tokio::test]
+async fn test_busy_time_excludes_idle_time() {
+    let registry = Registry::new();
+    let opts = ChannelMetricsOpts::new("test_busy_idle").with_utilization();
+    let metrics = ChannelMetrics::with_opts(&opts, &registry).unwrap();
+    let idle_time = metrics.idle_time.clone().unwrap();
+    let busy_time = metrics.busy_time.clone().unwrap();
+
+    let (tx, mut rx) = mpsc_channel(4, metrics);
+    tx.send(1).await.unwrap();
+    rx.recv().await.unwrap();
+
+    let sender = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        tx.send(2).await.unwrap();
+    });
+    rx.recv().await.unwrap();
+    sender.await.unwrap();
+
+    assert!(idle_time.get() >= 0.05);
+    assert!(busy_time.get() < 0.05);
+}
+
+#[tokio::test]
+async fn test_idle_waits_across_channel_kinds() {
+    let observer = CountingObserver::new();
+
+    let (tx, mut rx) = mpsc_channel_with_observer(4, observer.clone());
+    tx.send(1).await.unwrap();
+    rx.recv().await.unwrap();
+    assert_eq!(observer.idle_waits(), 0);
+
+    let waiting = tokio::spawn(async move { rx.recv().await });
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    tx.send(2).await.unwrap();
+    assert_eq!(waiting.await.unwrap(), Some(2));
+    assert_eq!(observer.idle_waits(), 1);
+
+    let (tx, mut rx) =
+        overflow_channel_with_observer(4, OverflowPolicy::DropOldest, observer.clone());
+    let waiting = tokio::spawn(async move { rx.recv().await });
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    tx.send(3).await.unwrap();
+    assert_eq!(waiting.await.unwrap(), Some(3));
+    assert_eq!(observer.idle_waits(), 2);
+
+    let (tx, mut rx) = broadcast_channel_with_observer(4, observer.clone());
+    tx.send(4).unwrap();
+    rx.recv().await.unwrap();
+    let waiting = tokio::spawn(async move { rx.recv().await });
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    tx.send(5).unwrap();
+    assert_eq!(waiting.await.unwrap().unwrap(), 5);
+    assert_eq!(observer.idle_waits(), 3);
+
+    let (tx, mut rx) = watch_channel_with_observer(0, observer.clone());
+    tx.send(6).unwrap();
+    rx.changed().await.unwrap();
+    let waiting = tokio::spawn(async move { rx.changed().await });
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    tx.send(7).unwrap();
+    waiting.await.unwrap().unwrap();
+    assert_eq!(observer.idle_waits(), 4);
+}
+
+#[tokio::test]
+async fn test_cancelled_recv_counts_as_idle() {
+    let registry = Registry::new();
+    let opts = ChannelMetricsOpts::new("test_idle_cancel").with_utilization();
+    let metrics = ChannelMetrics::with_opts(&opts, &registry).unwrap();
+    let idle_time = metrics.idle_time.clone().unwrap();
+    let busy_time = metrics.busy_time.clone().unwrap();
+
+    let (tx, mut rx) = mpsc_channel(4, metrics);
+    tx.send(1).await.unwrap();
+    rx.recv().await.unwrap();
+
+    let timed_out = tokio::time::timeout(Duration::from_millis(50), rx.recv()).await;
+    assert!(timed_out.is_err());
+    assert!(idle_time.get() >= 0.05);
+
+    tx.send(2).await.unwrap();
+    rx.recv().await.unwrap();
+    assert!(busy_time.get() < 0.05);
+}
+
+#[tokio::test]
+async fn test_poll_recv_counts_idle() {
+    let observer = CountingObserver::new();
+    let (tx, mut rx) = mpsc_channel_with_observer(4, observer.clone());
+
+    let sender = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        tx.send(1).await.unwrap();
+    });
+    assert_eq!(std::future::poll_fn(|cx| rx.poll_recv(cx)).await, Some(1));
+    assert_eq!(observer.idle_waits(), 1);
+    sender.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_builder_utilization() {
+    let registry = Registry::new();
+    let (tx, mut rx) = MeteredChannelBuilder::new()
+        .name("test_builder_utilization")
+        .capacity(4)
+        .registry(&registry)
+        .with_utilization()
+        .build_mpsc()
+        .unwrap();
+
+    tx.send(1).await.unwrap();
+    rx.recv().await.unwrap();
+
+    let families = registry.gather();
+    let names: Vec<_> = families.iter().map(|family| family.get_name()).collect();
+    assert!(names.contains(&"test_builder_utilization_idle_seconds_total"));
+    assert!(names.contains(&"test_builder_utilization_busy_seconds_total"));
+}
@@ -0,0 +1,119 @@
+use crate::{mpsc_channel, ChannelMetrics, ChannelMetricsOpts, MeteredChannelBuilder, Watchdog};
+use prometheus::Registry;
+use std::time::Duration;
+
+const THRESHOLD: Duration = Duration::from_millis(20);
+
+fn metrics(name: &str) -> ChannelMetrics {
+    let opts = ChannelMetricsOpts::new(name).with_stall_threshold(THRESHOLD);
+    ChannelMetrics::with_opts(&opts, &Registry::new()).unwrap()
+}
+
+fn is_stalled(watchdog: &mut Watchdog, name: &str) -> bool {
+    watchdog.check().iter().any(|stats| stats.name == name)
+}
+
+#[tokio::test]
+async fn test_watchdog_flags_channel_without_receives() {
+    let (tx, _rx) = mpsc_channel(4, metrics("test_watchdog_stalled"));
+    tx.send(1).await.unwrap();
+    tx.send(2).await.unwrap();
+    let gauge = tx.observer().stalled.clone().unwrap();
+
+    let mut watchdog = Watchdog::new(THRESHOLD);
+    assert!(!is_stalled(&mut watchdog, "test_watchdog_stalled"));
+    assert_eq!(gauge.get(), 0);
+
+    tokio::time::sleep(THRESHOLD * 2).await;
+    let stalled = watchdog.check();
+    let stats = stalled
+        .iter()
+        .find(|stats| stats.name == "test_watchdog_stalled")
+        .unwrap();
+    assert_eq!(stats.len, 2);
+    assert_eq!(gauge.get(), 1);
+}
+
+#[tokio::test]
+async fn test_watchdog_clears_flag_on_progress() {
+    let (tx, mut rx) = mpsc_channel(4, metrics("test_watchdog_progress"));
+    tx.send(1).await.unwrap();
+    tx.send(2).await.unwrap();
+    let gauge = tx.observer().stalled.clone().unwrap();
+
+    let mut watchdog = Watchdog::new(THRESHOLD);
+    watchdog.check();
+    tokio::time::sleep(THRESHOLD * 2).await;
+    assert!(is_stalled(&mut watchdog, "test_watchdog_progress"));
+
+    rx.recv().await.unwrap();
+    assert!(!is_stalled(&mut watchdog, "test_watchdog_progress"));
+    assert_eq!(gauge.get(), 0);
+
+    // The remaining value stalls again without further receives
+    tokio::time::sleep(THRESHOLD * 2).await;
+    assert!(is_stalled(&mut watchdog, "test_watchdog_progress"));
+    assert_eq!(gauge.get(), 1);
+}
+
+#[tokio::test]
+async fn test_watchdog_ignores_empty_channels() {
+    let (tx, mut rx) = mpsc_channel(4, metrics("test_watchdog_empty"));
+    let mut watchdog = Watchdog::new(THRESHOLD);
+    watchdog.check();
+    tokio::time::sleep(THRESHOLD * 2).await;
+    assert!(!is_stalled(&mut watchdog, "test_watchdog_empty"));
+
+    tx.send(1).await.unwrap();
+    rx.recv().await.unwrap();
+    tokio::time::sleep(THRESHOLD * 2).await;
+    assert!(!is_stalled(&mut watchdog, "test_watchdog_empty"));
+    assert_eq!(tx.observer().stalled.as_ref().unwrap().get(), 0);
+}
+
+#[tokio::test]
+async fn test_spawned_watchdog() {
+    let (tx, _rx) = mpsc_channel(4, metrics("test_watchdog_spawned"));
+    tx.send(1).await.unwrap();
+
+    let task = Watchdog::new(Duration::from_millis(5)).spawn();
+    tokio::time::sleep(THRESHOLD * 4).await;
+    assert_eq!(tx.observer().stalled.as_ref().unwrap().get(), 1);
+    task.abort();
+}
+
+#[tokio::test]
+async fn test_stall_threshold_added_to_shared_metrics() {
+    let registry = Registry::new();
+    let opts = ChannelMetricsOpts::new("test_watchdog_shared").with_stats();
+    let first = ChannelMetrics::get_or_register(&opts, &registry).unwrap();
+    let second =
+        ChannelMetrics::get_or_register(&opts.with_stall_threshold(THRESHOLD), &registry).unwrap();
+    let gauge = second.stalled.clone().unwrap();
+
+    let (tx, _rx) = mpsc_channel(4, first);
+    tx.send(1).await.unwrap();
+    let mut watchdog = Watchdog::new(THRESHOLD);
+    watchdog.check();
+    tokio::time::sleep(THRESHOLD * 2).await;
+    assert!(is_stalled(&mut watchdog, "test_watchdog_shared"));
+    assert_eq!(gauge.get(), 1);
+}
+
+#[tokio::test]
+async fn test_builder_stall_threshold() {
+    let registry = Registry::new();
+    let (tx, _rx) = MeteredChannelBuilder::new()
+        .name("test_builder_stall")
+        .capacity(4)
+        .registry(&registry)
+        .with_stall_threshold(THRESHOLD)
+        .build_mpsc::<i32>()
+        .unwrap();
+
+    let families = registry.gather();
+    let names: Vec<_> = families.iter().map(|family| family.get_name()).collect();
+    assert!(names.contains(&"test_builder_stall_stalled"));
+    // Stall checks track the channel like stats do
+    assert!(tx.observer().stats().is_some());
+}
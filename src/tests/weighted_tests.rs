@@ -0,0 +1,138 @@
+use crate::weighted::{ByteLen, Limit};
+use crate::{
+    weighted_channel, weighted_channel_with_observer, ChannelMetrics, ChannelMetricsOpts,
+    CountingObserver, MeteredChannelBuilder, SendError,
+};
+use futures::task::{noop_waker, Context, Poll};
+use futures::FutureExt;
+use prometheus::Registry;
+
+#[tokio::test]
+async fn test_weighted_byte_budget() {
+    let registry = Registry::new();
+    let opts = ChannelMetricsOpts::new("test_weighted_bytes")
+        .with_total()
+        .with_bytes();
+    let metrics = ChannelMetrics::with_opts(&opts, &registry).unwrap();
+    let queue_bytes = metrics.queue_bytes.clone().unwrap();
+    let bytes_total = metrics.bytes_total.clone().unwrap();
+
+    let (tx, mut rx) = weighted_channel(Limit::Bytes(10), ByteLen, metrics);
+    tx.send("hello").await.unwrap();
+    tx.send("abc").await.unwrap();
+    assert!(matches!(
+        tx.try_send("world"),
+        Err(SendError::Full("world"))
+    ));
+    assert_eq!(queue_bytes.get(), 8);
+    assert_eq!(rx.len(), 8);
+
+    assert_eq!(rx.recv().await, Some("hello"));
+    tx.try_send("world").unwrap();
+    assert_eq!(queue_bytes.get(), 8);
+    assert_eq!(bytes_total.get(), 13);
+    assert_eq!(rx.total_messages().unwrap().get(), 3);
+}
+
+#[tokio::test]
+async fn test_weighted_item_limit() {
+    let observer = CountingObserver::new();
+    let (tx, mut rx) =
+        weighted_channel_with_observer(Limit::Items(2), |v: &Vec<u8>| v.len(), observer.clone());
+
+    tx.send(vec![0; 100]).await.unwrap();
+    tx.send(vec![0; 200]).await.unwrap();
+    assert!(matches!(tx.try_send(vec![]), Err(SendError::Full(_))));
+    assert_eq!(rx.len(), 2);
+    assert_eq!(rx.queued_bytes(), 300);
+
+    rx.recv().await.unwrap();
+    assert_eq!(observer.capacity(), 2);
+    assert_eq!(observer.bytes_sent(), 300);
+    assert_eq!(observer.bytes_received(), 100);
+    assert_eq!(observer.queued_bytes(), 200);
+}
+
+#[tokio::test]
+async fn test_weighted_oversized_value() {
+    let (tx, mut rx) = weighted_channel_with_observer(
+        Limit::Bytes(4),
+        |v: &Vec<u8>| v.len(),
+        CountingObserver::new(),
+    );
+
+    tx.send(vec![1]).await.unwrap();
+    assert!(matches!(tx.try_send(vec![0; 16]), Err(SendError::Full(_))));
+
+    rx.recv().await.unwrap();
+    tx.try_send(vec![0; 16]).unwrap();
+    assert_eq!(tx.queued_bytes(), 16);
+    assert!(tx.try_send(vec![]).is_ok());
+    assert!(tx.try_send(vec![2]).is_err());
+}
+
+#[tokio::test]
+async fn test_weighted_waiting_sender() {
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    let observer = CountingObserver::new();
+    let (tx, mut rx) = weighted_channel_with_observer(Limit::Bytes(8), ByteLen, observer.clone());
+
+    tx.send(vec![0u8; 6]).await.unwrap();
+    let mut send_fut = Box::pin(tx.send(vec![0u8; 4]));
+    assert!(matches!(send_fut.poll_unpin(&mut cx), Poll::Pending));
+
+    rx.recv().await.unwrap();
+    assert!(send_fut.now_or_never().unwrap().is_ok());
+    assert_eq!(observer.waits(), 1);
+    assert_eq!(rx.queued_bytes(), 4);
+
+    let mut send_fut = Box::pin(tx.send(vec![0u8; 8]));
+    assert!(matches!(send_fut.poll_unpin(&mut cx), Poll::Pending));
+    drop(rx);
+    assert!(matches!(
+        send_fut.now_or_never(),
+        Some(Err(SendError::Closed(_)))
+    ));
+    assert!(tx.is_closed());
+}
+
+#[tokio::test]
+async fn test_builder_weighted() {
+    let registry = Registry::new();
+    let (tx, mut rx) = MeteredChannelBuilder::new()
+        .name("test_builder_weighted")
+        .registry(&registry)
+        .byte_budget(1024)
+        .build_weighted(|v: &String| v.len())
+        .unwrap();
+    assert_eq!(tx.limit(), Limit::Bytes(1024));
+
+    tx.send("payload".to_string()).await.unwrap();
+    assert_eq!(tx.observer().queue_bytes.as_ref().unwrap().get(), 7);
+    assert_eq!(tx.observer().capacity.as_ref().unwrap().get(), 1024);
+    rx.recv().await.unwrap();
+    assert_eq!(tx.observer().queue_bytes.as_ref().unwrap().get(), 0);
+
+    let missing_capacity = MeteredChannelBuilder::new()
+        .name("test_builder_weighted_items")
+        .registry(&registry)
+        .build_weighted(|v: &String| v.len());
+    assert!(missing_capacity.is_err());
+}
+
+#[tokio::test]
+async fn test_weighted_drop_receiver_discards_queued() {
+    let observer = CountingObserver::new();
+    let (tx, rx) = weighted_channel_with_observer(Limit::Bytes(10), ByteLen, observer.clone());
+
+    tx.send("hello").await.unwrap();
+    tx.send("").await.unwrap();
+    drop(rx);
+
+    assert_eq!(observer.dropped(), 2);
+    assert_eq!(observer.queued_bytes(), 0);
+    assert_eq!(tx.queued_bytes(), 0);
+    assert!(matches!(tx.try_send("abc"), Err(SendError::Closed("abc"))));
+}
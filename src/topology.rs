@@ -0,0 +1,213 @@
+use crate::json;
+use crate::rates::{Rates, Throughput};
+use crate::stats::{live_trackers, ChannelStats};
+use std::fmt::Write as _;
+
+/// The dataflow graph between [named tasks](crate::track_task): the listed
+/// channels, each with the tasks sending to and receiving from it.
+///
+/// Every channel is an edge from each of its producers to each of its
+/// consumers, annotated with its depth and, if [rates are tracked](crate::ChannelMetricsOpts::with_rates),
+/// its throughput.
+///
+/// # Examples
+///
+/// ```rust
+/// use tokio_prometheus_metered_channel::{topology, track_task, MeteredChannelBuilder};
+/// use prometheus::Registry;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let (tx, _rx) = MeteredChannelBuilder::new()
+///         .name("parsed")
+///         .capacity(10)
+///         .registry(&Registry::new())
+///         .with_stats()
+///         .build_mpsc()
+///         .unwrap();
+///     tx.observer().consumed_by("indexer");
+///
+///     track_task("parser", async { tx.send(1).await.unwrap() }).await;
+///
+///     let topology = topology();
+///     let dot = topology.to_dot();
+///     assert!(dot.contains(r#""parser" -> "indexer" [label="parsed\n1/10 queued"]"#));
+///     assert_eq!(topology.stages(), ["indexer", "parser"]);
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Topology {
+    /// The listed channels, sorted by name
+    pub channels: Vec<ChannelLinks>,
+}
+
+/// A channel of the [`Topology`], with the named tasks using it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelLinks {
+    /// Live state of the channel
+    pub stats: ChannelStats,
+    /// Tasks that sent to the channel or were declared as producers
+    pub producers: Vec<String>,
+    /// Tasks that received from the channel or were declared as consumers
+    pub consumers: Vec<String>,
+    /// Send and receive rates, if tracked
+    pub throughput: Option<Throughput>,
+}
+
+/// Capture the current [`Topology`] of the live channels created with
+/// [stats](crate::ChannelMetricsOpts::with_stats)
+pub fn topology() -> Topology {
+    let mut channels: Vec<_> = live_trackers()
+        .iter()
+        .map(|tracker| {
+            let stats = tracker.stats();
+            let links = tracker.tasks().get();
+            ChannelLinks {
+                throughput: tracker.throughput(stats.len as i64),
+                stats,
+                producers: sorted(links.producers.iter().map(|task| task.to_string())),
+                consumers: sorted(links.consumers.iter().map(|task| task.to_string())),
+            }
+        })
+        .collect();
+    channels.sort_by(|a, b| a.stats.name.cmp(&b.stats.name));
+    Topology { channels }
+}
+
+fn sorted(tasks: impl Iterator<Item = String>) -> Vec<String> {
+    let mut tasks: Vec<_> = tasks.collect();
+    tasks.sort();
+    tasks
+}
+
+impl Topology {
+    /// Names of the tasks using the channels, sorted
+    pub fn stages(&self) -> Vec<&str> {
+        let mut stages: Vec<&str> = self
+            .channels
+            .iter()
+            .flat_map(|channel| channel.producers.iter().chain(&channel.consumers))
+            .map(String::as_str)
+            .collect();
+        stages.sort_unstable();
+        stages.dedup();
+        stages
+    }
+
+    /// Render the graph in the Graphviz DOT language.
+    ///
+    /// Channels without known producers or consumers start or end at an
+    /// unlabeled point.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph channels {\n");
+        for stage in self.stages() {
+            let _ = writeln!(dot, "    {};", dot_id(stage));
+        }
+        for channel in &self.channels {
+            let name = &channel.stats.name;
+            let producers = endpoints(&channel.producers, name, "in", &mut dot);
+            let consumers = endpoints(&channel.consumers, name, "out", &mut dot);
+            let label = dot_id(&label(channel));
+            for producer in &producers {
+                for consumer in &consumers {
+                    let _ = writeln!(dot, "    {} -> {} [label={}];", producer, consumer, label);
+                }
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Render the graph as a JSON object with the sorted `stages` and the
+    /// `channels`, each with its stats, `producers`, `consumers` and
+    /// `throughput`
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\"stages\":");
+        push_names(&mut json, self.stages());
+        json.push_str(",\"channels\":[");
+        for (i, channel) in self.channels.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            json.push('{');
+            json::push_stats_fields(&mut json, &channel.stats);
+            json.push_str(",\"producers\":");
+            push_names(&mut json, channel.producers.iter().map(String::as_str));
+            json.push_str(",\"consumers\":");
+            push_names(&mut json, channel.consumers.iter().map(String::as_str));
+            json.push_str(",\"throughput\":");
+            match channel.throughput {
+                Some(throughput) => {
+                    json.push_str("{\"send\":");
+                    push_rates(&mut json, &throughput.send);
+                    json.push_str(",\"recv\":");
+                    push_rates(&mut json, &throughput.recv);
+                    json.push('}');
+                }
+                None => json.push_str("null"),
+            }
+            json.push('}');
+        }
+        json.push_str("]}");
+        json
+    }
+}
+
+/// DOT identifiers of the tasks at one end of a channel, or of a point node
+/// standing for the unknown ones
+fn endpoints(tasks: &[String], channel: &str, side: &str, dot: &mut String) -> Vec<String> {
+    if tasks.is_empty() {
+        let point = dot_id(&format!("{}/{}", channel, side));
+        let _ = writeln!(dot, "    {} [shape=point];", point);
+        vec![point]
+    } else {
+        tasks.iter().map(|task| dot_id(task)).collect()
+    }
+}
+
+/// Edge label: the channel name, its depth and its receive rate
+fn label(channel: &ChannelLinks) -> String {
+    let stats = &channel.stats;
+    let mut label = match stats.capacity {
+        Some(capacity) => format!("{}\n{}/{} queued", stats.name, stats.len, capacity),
+        None => format!("{}\n{} queued", stats.name, stats.len),
+    };
+    if let Some(throughput) = channel.throughput {
+        let _ = write!(label, "\n{:.1}/s", throughput.recv.ten_seconds);
+    }
+    label
+}
+
+/// Quote `value` as a DOT string, with newlines as `\n` line breaks
+fn dot_id(value: &str) -> String {
+    let mut id = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => id.push_str("\\\""),
+            '\\' => id.push_str("\\\\"),
+            '\n' => id.push_str("\\n"),
+            c => id.push(c),
+        }
+    }
+    id.push('"');
+    id
+}
+
+fn push_names<'a>(json: &mut String, names: impl IntoIterator<Item = &'a str>) {
+    json.push('[');
+    for (i, name) in names.into_iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        json::push_str(json, name);
+    }
+    json.push(']');
+}
+
+fn push_rates(json: &mut String, rates: &Rates) {
+    let _ = write!(
+        json,
+        "{{\"one_second\":{},\"ten_seconds\":{},\"one_minute\":{}}}",
+        rates.one_second, rates.ten_seconds, rates.one_minute
+    );
+}
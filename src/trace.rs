@@ -0,0 +1,393 @@
+//! Tracing macros that compile to nothing without the `tracing` feature, and
+//! the per-channel span configuration.
+
+#[cfg(any(
+    feature = "mpsc",
+    feature = "broadcast",
+    feature = "watch",
+    feature = "overflow",
+    feature = "resizable",
+    feature = "weighted"
+))]
+use crate::observer::ChannelObserver;
+#[cfg(any(
+    feature = "mpsc",
+    feature = "broadcast",
+    feature = "watch",
+    feature = "overflow",
+    feature = "resizable",
+    feature = "weighted"
+))]
+use std::future::Future;
+#[cfg(feature = "tracing")]
+use tracing::Level;
+#[cfg(all(
+    feature = "tracing",
+    any(
+        feature = "mpsc",
+        feature = "broadcast",
+        feature = "watch",
+        feature = "overflow",
+        feature = "resizable",
+        feature = "weighted"
+    )
+))]
+use tracing::{Instrument, Span};
+
+// Each logging macro is used by optional modules only, so any may go unused
+#[cfg(feature = "tracing")]
+#[allow(unused_macros)]
+macro_rules! debug {
+    ($($arg:tt)*) => { tracing::debug!($($arg)*) };
+}
+
+#[cfg(not(feature = "tracing"))]
+#[allow(unused_macros)]
+macro_rules! debug {
+    ($($arg:tt)*) => {};
+}
+
+#[cfg(feature = "tracing")]
+#[allow(unused_macros)]
+macro_rules! info {
+    ($($arg:tt)*) => { tracing::info!($($arg)*) };
+}
+
+#[cfg(not(feature = "tracing"))]
+#[allow(unused_macros)]
+macro_rules! info {
+    ($($arg:tt)*) => {};
+}
+
+#[cfg(feature = "tracing")]
+#[allow(unused_macros)]
+macro_rules! warning {
+    ($($arg:tt)*) => { tracing::warn!($($arg)*) };
+}
+
+#[cfg(not(feature = "tracing"))]
+#[allow(unused_macros)]
+macro_rules! warning {
+    ($($arg:tt)*) => {};
+}
+
+#[cfg(feature = "tracing")]
+#[allow(unused_macros)]
+macro_rules! error {
+    ($($arg:tt)*) => { tracing::error!($($arg)*) };
+}
+
+#[cfg(not(feature = "tracing"))]
+#[allow(unused_macros)]
+macro_rules! error {
+    ($($arg:tt)*) => {};
+}
+
+#[allow(unused_imports)]
+pub(crate) use {debug, error, info, warning};
+
+/// Creates a span named `$name` at a level only known at runtime
+#[cfg(all(
+    feature = "tracing",
+    any(
+        feature = "mpsc",
+        feature = "broadcast",
+        feature = "watch",
+        feature = "overflow",
+        feature = "resizable",
+        feature = "weighted"
+    )
+))]
+macro_rules! dyn_span {
+    ($level:expr, $name:literal, $($fields:tt)*) => {
+        match $level {
+            Level::ERROR => tracing::error_span!($name, $($fields)*),
+            Level::WARN => tracing::warn_span!($name, $($fields)*),
+            Level::INFO => tracing::info_span!($name, $($fields)*),
+            Level::DEBUG => tracing::debug_span!($name, $($fields)*),
+            _ => tracing::trace_span!($name, $($fields)*),
+        }
+    };
+}
+
+/// Channel operations that get their own span
+#[cfg(any(
+    feature = "mpsc",
+    feature = "broadcast",
+    feature = "watch",
+    feature = "overflow",
+    feature = "resizable",
+    feature = "weighted"
+))]
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Op {
+    Send,
+    Recv,
+    #[cfg(feature = "mpsc")]
+    WithPermit,
+}
+
+/// Tracing configuration of a channel.
+///
+/// Operations on a channel run in spans named after the operation (`send`,
+/// `recv`, `with_permit`) with a `channel` field holding the
+/// [name of its observer](crate::ChannelObserver::name), at the configured
+/// level. When propagation is enabled, each queued value carries the span it
+/// was sent from, and the consumer's span, current when the value is received,
+/// is marked as following from it, so traces survive the hop between tasks.
+/// To process a value inside the sender's span instead, receive it with
+/// [`MpscReceiver::recv_traced`](crate::MpscReceiver::recv_traced).
+///
+/// Watch channels do not propagate trace context: their values are
+/// overwritten rather than queued, so most sends are never received.
+///
+/// # Examples
+///
+/// ```rust
+/// use tokio_prometheus_metered_channel::{ChannelTrace, MeteredChannelBuilder};
+/// use tracing::Level;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let (tx, mut rx) = MeteredChannelBuilder::new()
+///         .name("traced_ingest")
+///         .capacity(10)
+///         .trace(ChannelTrace::new().level(Level::INFO).propagate(true))
+///         .build_mpsc::<u64>()
+///         .unwrap();
+///
+///     tx.send(42).await.unwrap();
+///     assert_eq!(rx.recv().await, Some(42));
+/// }
+/// ```
+#[cfg(feature = "tracing")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelTrace {
+    level: Level,
+    propagate: bool,
+}
+
+#[cfg(feature = "tracing")]
+impl Default for ChannelTrace {
+    fn default() -> Self {
+        Self {
+            level: Level::DEBUG,
+            propagate: false,
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl ChannelTrace {
+    /// Create a configuration tracing at debug level without propagation
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the level of the channel's spans
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Capture the sender's span with each value and link the receiving span to it
+    pub fn propagate(mut self, propagate: bool) -> Self {
+        self.propagate = propagate;
+        self
+    }
+
+    /// Get the level of the channel's spans
+    pub fn get_level(&self) -> Level {
+        self.level
+    }
+
+    /// Returns true if the sender's span is propagated to receivers
+    pub fn is_propagating(&self) -> bool {
+        self.propagate
+    }
+}
+
+/// Create the span of `op` on the channel reporting to `observer`
+#[cfg(all(
+    feature = "tracing",
+    any(
+        feature = "mpsc",
+        feature = "broadcast",
+        feature = "watch",
+        feature = "overflow",
+        feature = "resizable",
+        feature = "weighted"
+    )
+))]
+pub(crate) fn span<O: ChannelObserver + ?Sized>(observer: &O, op: Op) -> OpSpan {
+    let level = observer.trace().level;
+    let channel = observer.name();
+    OpSpan(match op {
+        Op::Send => dyn_span!(level, "send", channel),
+        Op::Recv => dyn_span!(level, "recv", channel),
+        #[cfg(feature = "mpsc")]
+        Op::WithPermit => dyn_span!(level, "with_permit", channel),
+    })
+}
+
+/// Create the span of `op` on the channel reporting to `observer`
+#[cfg(all(
+    not(feature = "tracing"),
+    any(
+        feature = "mpsc",
+        feature = "broadcast",
+        feature = "watch",
+        feature = "overflow",
+        feature = "resizable",
+        feature = "weighted"
+    )
+))]
+#[inline]
+pub(crate) fn span<O: ChannelObserver + ?Sized>(_observer: &O, _op: Op) -> OpSpan {
+    OpSpan
+}
+
+/// The span of a channel operation
+#[cfg(all(
+    feature = "tracing",
+    any(
+        feature = "mpsc",
+        feature = "broadcast",
+        feature = "watch",
+        feature = "overflow",
+        feature = "resizable",
+        feature = "weighted"
+    )
+))]
+pub(crate) struct OpSpan(Span);
+
+/// The span of a channel operation, empty without the `tracing` feature
+#[cfg(all(
+    not(feature = "tracing"),
+    any(
+        feature = "mpsc",
+        feature = "broadcast",
+        feature = "watch",
+        feature = "overflow",
+        feature = "resizable",
+        feature = "weighted"
+    )
+))]
+pub(crate) struct OpSpan;
+
+#[cfg(any(
+    feature = "mpsc",
+    feature = "broadcast",
+    feature = "watch",
+    feature = "overflow",
+    feature = "resizable",
+    feature = "weighted"
+))]
+impl OpSpan {
+    /// Run `future` inside the span
+    #[cfg(feature = "tracing")]
+    pub(crate) async fn instrument<F: Future>(self, future: F) -> F::Output {
+        future.instrument(self.0).await
+    }
+
+    /// Run `future` inside the span
+    #[cfg(not(feature = "tracing"))]
+    #[inline]
+    pub(crate) async fn instrument<F: Future>(self, future: F) -> F::Output {
+        future.await
+    }
+
+    /// Run `f` inside the span
+    #[cfg(any(feature = "broadcast", feature = "watch"))]
+    #[inline]
+    pub(crate) fn in_scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        #[cfg(feature = "tracing")]
+        return self.0.in_scope(f);
+        #[cfg(not(feature = "tracing"))]
+        f()
+    }
+}
+
+/// Capture the caller's trace context, then create the `send` span on the
+/// channel reporting to `observer`.
+///
+/// The context must be captured before the span is entered, so that receivers
+/// link to the caller's span rather than to the channel's own.
+#[cfg(any(
+    feature = "mpsc",
+    feature = "broadcast",
+    feature = "overflow",
+    feature = "resizable",
+    feature = "weighted"
+))]
+pub(crate) fn send_span<O: ChannelObserver + ?Sized>(observer: &O) -> (SpanContext, OpSpan) {
+    let context = SpanContext::capture(observer);
+    (context, span(observer, Op::Send))
+}
+
+/// The span a value was sent from, if its channel propagates trace context
+#[cfg(all(
+    feature = "tracing",
+    any(
+        feature = "mpsc",
+        feature = "broadcast",
+        feature = "overflow",
+        feature = "resizable",
+        feature = "weighted"
+    )
+))]
+#[derive(Debug, Clone)]
+pub(crate) struct SpanContext(Option<Span>);
+
+/// The span a value was sent from, empty without the `tracing` feature
+#[cfg(all(
+    not(feature = "tracing"),
+    any(
+        feature = "mpsc",
+        feature = "broadcast",
+        feature = "overflow",
+        feature = "resizable",
+        feature = "weighted"
+    )
+))]
+#[derive(Debug, Clone)]
+pub(crate) struct SpanContext;
+
+#[cfg(any(
+    feature = "mpsc",
+    feature = "broadcast",
+    feature = "overflow",
+    feature = "resizable",
+    feature = "weighted"
+))]
+impl SpanContext {
+    /// Capture the current span if the channel reporting to `observer` propagates it
+    #[inline]
+    pub(crate) fn capture<O: ChannelObserver + ?Sized>(observer: &O) -> Self {
+        #[cfg(feature = "tracing")]
+        return Self(observer.trace().propagate.then(Span::current));
+        #[cfg(not(feature = "tracing"))]
+        {
+            let _ = observer;
+            Self
+        }
+    }
+
+    /// Mark the current span as following from the sender's span.
+    ///
+    /// Called outside the channel's own `recv` span, so that the span linked
+    /// is the consumer's.
+    #[inline]
+    pub(crate) fn link(&self) {
+        #[cfg(feature = "tracing")]
+        if let Some(ref span) = self.0 {
+            Span::current().follows_from(span);
+        }
+    }
+
+    /// Get the sender's span, or a disabled span if it was not captured
+    #[cfg(all(feature = "tracing", feature = "mpsc"))]
+    pub(crate) fn into_span(self) -> Span {
+        self.0.unwrap_or_else(Span::none)
+    }
+}
@@ -1,8 +1,11 @@
+#[cfg(feature = "prometheus")]
+use crate::builder::MeteredChannelBuilder;
 use crate::error::SendError;
+#[cfg(feature = "prometheus")]
 use crate::metrics::ChannelMetrics;
-use std::sync::Arc;
+use crate::observer::{ChannelKind, ChannelObserver, HandleKind, Idle};
+use crate::trace::{self, debug, error, Op};
 use tokio::sync::watch;
-use tracing::{debug, error, instrument};
 
 /// A sender for the watch channel.
 ///
@@ -36,51 +39,89 @@ use tracing::{debug, error, instrument};
 /// }
 /// ```
 #[derive(Debug)]
-pub struct Sender<T> {
+pub struct Sender<
+    T,
+    #[cfg(feature = "prometheus")] O: ChannelObserver = ChannelMetrics,
+    #[cfg(not(feature = "prometheus"))] O: ChannelObserver,
+> {
     inner: watch::Sender<T>,
-    gauge: prometheus::IntGauge,
-    total_messages: Option<prometheus::IntCounter>,
+    observer: O,
 }
 
 /// A receiver for the watch channel
-#[derive(Debug, Clone)]
-pub struct Receiver<T> {
+#[derive(Debug)]
+pub struct Receiver<
+    T,
+    #[cfg(feature = "prometheus")] O: ChannelObserver = ChannelMetrics,
+    #[cfg(not(feature = "prometheus"))] O: ChannelObserver,
+> {
     inner: watch::Receiver<T>,
-    gauge: Arc<prometheus::IntGauge>,
-    total_messages: Option<prometheus::IntCounter>,
+    observer: O,
+}
+
+impl<T, O: ChannelObserver + Clone> Clone for Receiver<T, O> {
+    fn clone(&self) -> Self {
+        self.observer.on_attach(HandleKind::Receiver);
+        Self {
+            inner: self.inner.clone(),
+            observer: self.observer.clone(),
+        }
+    }
 }
 
 /// Creates a new watch channel with an initial value and metrics
+#[cfg(feature = "prometheus")]
 pub fn channel<T>(initial: T, metrics: ChannelMetrics) -> (Sender<T>, Receiver<T>) {
+    channel_with_observer(initial, metrics)
+}
+
+/// Creates a new watch channel with an initial value, reporting to `observer`
+pub fn channel_with_observer<T, O: ChannelObserver + Clone>(
+    initial: T,
+    observer: O,
+) -> (Sender<T, O>, Receiver<T, O>) {
     let (tx, rx) = watch::channel(initial);
-    let gauge = metrics.queue_size;
-    let total_messages = metrics.total_messages;
+    observer.on_open(ChannelKind::Watch);
 
     (
         Sender {
             inner: tx,
-            gauge: gauge.clone(),
-            total_messages: total_messages.clone(),
+            observer: observer.clone(),
         },
         Receiver {
             inner: rx,
-            gauge: Arc::new(gauge),
-            total_messages,
+            observer,
         },
     )
 }
 
-impl<T> Sender<T> {
+/// Creates a new watch channel with an initial value, registering metrics
+/// prefixed with `name` in the [default registry](crate::default_registry)
+///
+/// Channels created with the same name share their metrics.
+#[cfg(feature = "prometheus")]
+pub fn channel_named<T>(
+    name: &str,
+    initial: T,
+) -> Result<(Sender<T>, Receiver<T>), prometheus::Error> {
+    MeteredChannelBuilder::new()
+        .name(name)
+        .with_total()
+        .shared()
+        .build_watch(initial)
+}
+
+impl<T, O: ChannelObserver> Sender<T, O> {
     /// Send a value, replacing the current value
-    #[instrument(skip(self, value), level = "debug")]
     pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        trace::span(&self.observer, Op::Send).in_scope(|| self.send_inner(value))
+    }
+
+    fn send_inner(&self, value: T) -> Result<(), SendError<T>> {
         debug!("attempting to update watch value");
         match self.inner.send(value) {
             Ok(()) => {
-                self.gauge.inc();
-                if let Some(ref counter) = self.total_messages {
-                    counter.inc();
-                }
+                self.observer.on_send();
                 debug!("watch value updated successfully");
                 Ok(())
             }
@@ -95,9 +136,14 @@ impl<T> Sender<T> {
     pub fn receiver_count(&self) -> usize {
         self.inner.receiver_count()
     }
+
+    /// Get the observer receiving the channel's events
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
 }
 
-impl<T: Clone> Receiver<T> {
+impl<T: Clone, O: ChannelObserver> Receiver<T, O> {
     /// Get the current value
     pub fn borrow(&self) -> watch::Ref<'_, T> {
         self.inner.borrow()
@@ -105,14 +151,21 @@ impl<T: Clone> Receiver<T> {
 
     /// Wait for the value to change
     pub async fn changed(&mut self) -> Result<(), watch::error::RecvError> {
-        let result = self.inner.changed().await;
-        if result.is_ok() {
-            self.gauge.dec();
-            if let Some(ref counter) = self.total_messages {
-                counter.inc();
+        let span = trace::span(&self.observer, Op::Recv);
+        span.instrument(async {
+            let result = if let Ok(true) = self.inner.has_changed() {
+                self.inner.changed().await
+            } else {
+                let _idle = Idle::new(&self.observer);
+                self.inner.changed().await
+            };
+            if result.is_ok() {
+                self.observer.on_recv(None);
+                self.observer.on_deliver();
             }
-        }
-        result
+            result
+        })
+        .await
     }
 
     /// Returns true if the sender has been dropped
@@ -120,8 +173,28 @@ impl<T: Clone> Receiver<T> {
         self.inner.has_changed().unwrap_or(false)
     }
 
+    /// Get the observer receiving the channel's events
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+}
+
+#[cfg(feature = "prometheus")]
+impl<T: Clone> Receiver<T, ChannelMetrics> {
     /// Get the total messages counter if enabled
     pub fn total_messages(&self) -> Option<&prometheus::IntCounter> {
-        self.total_messages.as_ref()
+        self.observer.total_messages.as_ref()
+    }
+}
+
+impl<T, O: ChannelObserver> Drop for Sender<T, O> {
+    fn drop(&mut self) {
+        self.observer.on_detach(HandleKind::Sender);
+    }
+}
+
+impl<T, O: ChannelObserver> Drop for Receiver<T, O> {
+    fn drop(&mut self) {
+        self.observer.on_detach(HandleKind::Receiver);
     }
 }
@@ -0,0 +1,161 @@
+#[cfg(feature = "tasks")]
+use crate::deadlock::{find_deadlocks, Deadlock};
+use crate::stats::{live_trackers, ChannelStats, Tracker};
+#[cfg(feature = "tasks")]
+use crate::trace::error;
+use crate::trace::{debug, warning};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, MissedTickBehavior};
+
+/// Flags channels holding values without receive progress.
+///
+/// Every check looks at the live channels created with a
+/// [stall threshold](crate::ChannelMetricsOpts::with_stall_threshold). A channel
+/// that stays non-empty without any value being received for longer than its
+/// threshold is stalled: its `{name}_stalled` gauge is set to 1 and a warning
+/// with the channel's name and depth is logged. The gauge is reset once values
+/// are received again or the channel empties.
+///
+/// With the `tasks` feature, it also logs an error for every new
+/// [deadlock](crate::find_deadlocks) between named tasks.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+/// use tokio_prometheus_metered_channel::{MeteredChannelBuilder, Watchdog};
+/// use prometheus::Registry;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let (tx, _rx) = MeteredChannelBuilder::new()
+///         .name("orders")
+///         .capacity(1)
+///         .registry(&Registry::new())
+///         .with_stall_threshold(Duration::from_millis(10))
+///         .build_mpsc()
+///         .unwrap();
+///     tx.send(1).await.unwrap();
+///
+///     let mut watchdog = Watchdog::new(Duration::from_secs(1));
+///     watchdog.check();
+///     tokio::time::sleep(Duration::from_millis(20)).await;
+///
+///     let stalled = watchdog.check();
+///     assert!(stalled.iter().any(|stats| stats.name == "orders"));
+///     assert_eq!(tx.observer().stalled.as_ref().unwrap().get(), 1);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Watchdog {
+    interval: Duration,
+    watched: Vec<Watched>,
+    #[cfg(feature = "tasks")]
+    deadlocks: Vec<Deadlock>,
+}
+
+/// Receive progress of a channel as of the last check
+#[derive(Debug)]
+struct Watched {
+    tracker: Weak<Tracker>,
+    received: u64,
+    since: Instant,
+    stalled: bool,
+}
+
+impl Watchdog {
+    /// Create a watchdog checking the channels every `interval` once spawned
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            watched: Vec::new(),
+            #[cfg(feature = "tasks")]
+            deadlocks: Vec::new(),
+        }
+    }
+
+    /// Check every channel once, returning the stats of the stalled ones.
+    ///
+    /// A channel is only known to make no progress from the first check that
+    /// sees it, so it is flagged at the earliest one threshold after that.
+    pub fn check(&mut self) -> Vec<ChannelStats> {
+        let now = Instant::now();
+        let mut watched = Vec::with_capacity(self.watched.len());
+        let mut stalled = Vec::new();
+
+        for tracker in live_trackers() {
+            let Some(check) = tracker.stall_check() else {
+                continue;
+            };
+            let stats = tracker.stats();
+            let previous = self
+                .watched
+                .iter()
+                .position(|watched| watched.tracker.as_ptr() == Arc::as_ptr(&tracker))
+                .map(|index| self.watched.swap_remove(index));
+            let mut state = match previous {
+                Some(state) if state.received == stats.received && stats.len > 0 => state,
+                // Progress or an empty channel restarts the clock
+                previous => Watched {
+                    tracker: Arc::downgrade(&tracker),
+                    received: stats.received,
+                    since: now,
+                    stalled: previous.map_or(false, |state| state.stalled),
+                },
+            };
+
+            let stalled_for = now.saturating_duration_since(state.since);
+            if stats.len > 0 && stalled_for >= check.threshold {
+                if !state.stalled {
+                    warning!(
+                        channel = %stats.name,
+                        depth = stats.len,
+                        stalled_for = ?stalled_for,
+                        "channel stalled, no values received"
+                    );
+                    check.gauge.set(1);
+                }
+                state.stalled = true;
+                stalled.push(stats);
+            } else if state.stalled {
+                debug!(channel = %stats.name, "channel no longer stalled");
+                check.gauge.set(0);
+                state.stalled = false;
+            }
+            watched.push(state);
+        }
+
+        self.watched = watched;
+        stalled
+    }
+
+    /// Find the current deadlocks, logging those not found by the previous call
+    #[cfg(feature = "tasks")]
+    pub fn check_deadlocks(&mut self) -> Vec<Deadlock> {
+        let deadlocks = find_deadlocks();
+        for deadlock in &deadlocks {
+            if !self.deadlocks.contains(deadlock) {
+                error!(cycle = %deadlock, "tasks deadlocked on channels");
+            }
+        }
+        self.deadlocks = deadlocks.clone();
+        deadlocks
+    }
+
+    /// Run the watchdog in a background task
+    pub fn spawn(mut self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+            loop {
+                interval.tick().await;
+                self.check();
+                #[cfg(feature = "tasks")]
+                self.check_deadlocks();
+            }
+        })
+    }
+}
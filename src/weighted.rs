@@ -0,0 +1,446 @@
+use crate::envelope::Envelope;
+use crate::error::SendError;
+use crate::guard::MessageGuard;
+#[cfg(feature = "prometheus")]
+use crate::metrics::ChannelMetrics;
+use crate::observer::{ChannelKind, ChannelObserver, HandleKind, Idle, Waiting};
+use crate::trace::{self, debug, error, Op, SpanContext};
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::sync::{mpsc, Semaphore, TryAcquireError};
+
+/// Measures the weight of values, usually their size in bytes.
+///
+/// Implemented by closures taking a reference to the value.
+pub trait Weigh<T>: Send + Sync {
+    /// Get the weight of `value`
+    fn weigh(&self, value: &T) -> usize;
+}
+
+impl<T, F: Fn(&T) -> usize + Send + Sync> Weigh<T> for F {
+    fn weigh(&self, value: &T) -> usize {
+        self(value)
+    }
+}
+
+/// Weighs values by the length of their bytes
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ByteLen;
+
+impl<T: AsRef<[u8]>> Weigh<T> for ByteLen {
+    fn weigh(&self, value: &T) -> usize {
+        value.as_ref().len()
+    }
+}
+
+/// What bounds the contents of a weighted channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    /// At most this many values are queued, whatever their weight
+    Items(usize),
+    /// The weight of the queued values adds up to at most this many bytes.
+    ///
+    /// A value weighing more than the whole budget is accepted once the
+    /// channel is empty.
+    Bytes(usize),
+}
+
+impl Limit {
+    /// Budget permits taken by a value of the given weight
+    fn permits(self, weight: usize) -> u32 {
+        match self {
+            Limit::Items(_) => 1,
+            // Limits are validated to fit in a u32
+            Limit::Bytes(budget) => weight.min(budget) as u32,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Limit::Items(size) | Limit::Bytes(size) => size,
+        }
+    }
+}
+
+/// A sender handle to a weighted channel.
+///
+/// # Examples
+///
+/// ```rust
+/// use tokio_prometheus_metered_channel::weighted::{self, Limit};
+/// use tokio_prometheus_metered_channel::{ChannelMetrics, ChannelMetricsOpts};
+/// use prometheus::Registry;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let registry = Registry::new();
+///     let opts = ChannelMetricsOpts::new("payloads").with_total().with_bytes();
+///     let metrics = ChannelMetrics::with_opts(&opts, &registry).unwrap();
+///     let queue_bytes = metrics.queue_bytes.clone().unwrap();
+///
+///     let (tx, mut rx) = weighted::channel(Limit::Bytes(1024), |v: &Vec<u8>| v.len(), metrics);
+///     tx.send(vec![0; 1000]).await.unwrap();
+///     assert!(tx.try_send(vec![0; 100]).is_err());
+///     assert_eq!(queue_bytes.get(), 1000);
+///
+///     rx.recv().await.unwrap();
+///     tx.try_send(vec![0; 100]).unwrap();
+///     assert_eq!(queue_bytes.get(), 100);
+/// }
+/// ```
+pub struct Sender<
+    T,
+    #[cfg(feature = "prometheus")] O: ChannelObserver = ChannelMetrics,
+    #[cfg(not(feature = "prometheus"))] O: ChannelObserver,
+> {
+    inner: mpsc::UnboundedSender<Weighed<T>>,
+    budget: Arc<Budget<T>>,
+    observer: O,
+}
+
+/// A receiver handle to a weighted channel
+pub struct Receiver<
+    T,
+    #[cfg(feature = "prometheus")] O: ChannelObserver = ChannelMetrics,
+    #[cfg(not(feature = "prometheus"))] O: ChannelObserver,
+> {
+    inner: mpsc::UnboundedReceiver<Weighed<T>>,
+    budget: Arc<Budget<T>>,
+    observer: O,
+}
+
+/// A queued value with its weight and the budget permits it holds
+struct Weighed<T> {
+    envelope: Envelope<T>,
+    weight: usize,
+    permits: u32,
+}
+
+struct Budget<T> {
+    limit: Limit,
+    semaphore: Semaphore,
+    queued_bytes: AtomicUsize,
+    weigher: Box<dyn Weigh<T>>,
+}
+
+impl<T> Budget<T> {
+    fn len(&self) -> usize {
+        match self.limit {
+            Limit::Items(size) => size.saturating_sub(self.semaphore.available_permits()),
+            Limit::Bytes(_) => self.queued_bytes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Creates a new weighted channel bounded by `limit`
+///
+/// # Panics
+///
+/// Panics if the limit is 0 or larger than [`u32::MAX`].
+#[cfg(feature = "prometheus")]
+pub fn channel<T>(
+    limit: Limit,
+    weigher: impl Weigh<T> + 'static,
+    metrics: ChannelMetrics,
+) -> (Sender<T>, Receiver<T>) {
+    channel_with_observer(limit, weigher, metrics)
+}
+
+/// Creates a new weighted channel bounded by `limit`, reporting to `observer`
+///
+/// # Panics
+///
+/// Panics if the limit is 0 or larger than [`u32::MAX`].
+pub fn channel_with_observer<T, O: ChannelObserver + Clone>(
+    limit: Limit,
+    weigher: impl Weigh<T> + 'static,
+    observer: O,
+) -> (Sender<T, O>, Receiver<T, O>) {
+    let size = limit.size();
+    assert!(size > 0, "channel limit must be greater than 0");
+    assert!(
+        size <= u32::MAX as usize,
+        "channel limit cannot exceed {}",
+        u32::MAX
+    );
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    observer.on_open(ChannelKind::Weighted);
+    observer.on_capacity(size);
+    let budget = Arc::new(Budget {
+        limit,
+        semaphore: Semaphore::new(size),
+        queued_bytes: AtomicUsize::new(0),
+        weigher: Box::new(weigher),
+    });
+
+    (
+        Sender {
+            inner: tx,
+            budget: budget.clone(),
+            observer: observer.clone(),
+        },
+        Receiver {
+            inner: rx,
+            budget,
+            observer,
+        },
+    )
+}
+
+impl<T, O: ChannelObserver> Sender<T, O> {
+    /// Try to send a value without waiting for room in the budget
+    pub fn try_send(&self, value: T) -> Result<(), SendError<T>> {
+        let weight = self.budget.weigher.weigh(&value);
+        let permits = self.budget.limit.permits(weight);
+        match self.budget.semaphore.try_acquire_many(permits) {
+            Ok(permit) => permit.forget(),
+            Err(TryAcquireError::NoPermits) => return Err(SendError::Full(value)),
+            Err(TryAcquireError::Closed) => return Err(SendError::Closed(value)),
+        }
+        self.enqueue(value, weight, permits, SpanContext::capture(&self.observer))
+    }
+
+    /// Send a value, waiting for room in the budget if needed
+    pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let (context, span) = trace::send_span(&self.observer);
+        span.instrument(self.send_inner(value, context)).await
+    }
+
+    async fn send_inner(&self, value: T, context: SpanContext) -> Result<(), SendError<T>> {
+        debug!("attempting to send value");
+        let weight = self.budget.weigher.weigh(&value);
+        let permits = self.budget.limit.permits(weight);
+        let acquired = match self.budget.semaphore.try_acquire_many(permits) {
+            Ok(permit) => Ok(permit),
+            Err(TryAcquireError::NoPermits) => {
+                debug!(weight, "channel full, waiting for capacity");
+                let waiting = Waiting::new(&self.observer);
+                let permit = self.budget.semaphore.acquire_many(permits).await;
+                waiting.finish();
+                permit.map_err(|_| ())
+            }
+            Err(TryAcquireError::Closed) => Err(()),
+        };
+        match acquired {
+            Ok(permit) => permit.forget(),
+            Err(()) => {
+                error!("failed to send value, channel closed");
+                return Err(SendError::Closed(value));
+            }
+        }
+        let result = self.enqueue(value, weight, permits, context);
+        if result.is_ok() {
+            debug!("value sent successfully");
+        }
+        result
+    }
+
+    fn enqueue(
+        &self,
+        value: T,
+        weight: usize,
+        permits: u32,
+        context: SpanContext,
+    ) -> Result<(), SendError<T>> {
+        let weighed = Weighed {
+            envelope: Envelope::with_context(value, &self.observer, context),
+            weight,
+            permits,
+        };
+        self.budget
+            .queued_bytes
+            .fetch_add(weight, Ordering::Relaxed);
+        match self.inner.send(weighed) {
+            Ok(()) => {
+                self.observer.on_send();
+                self.observer.on_send_bytes(weight);
+                Ok(())
+            }
+            Err(err) => {
+                self.budget
+                    .queued_bytes
+                    .fetch_sub(weight, Ordering::Relaxed);
+                self.budget.semaphore.add_permits(permits as usize);
+                Err(SendError::Closed(err.0.envelope.value))
+            }
+        }
+    }
+
+    /// Returns true if the channel has been closed
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    /// Get the limit bounding the channel
+    pub fn limit(&self) -> Limit {
+        self.budget.limit
+    }
+
+    /// Get the total weight of the queued values
+    pub fn queued_bytes(&self) -> usize {
+        self.budget.queued_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Get the observer receiving the channel's events
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+}
+
+impl<T, O: ChannelObserver + Clone> Clone for Sender<T, O> {
+    fn clone(&self) -> Self {
+        self.observer.on_attach(HandleKind::Sender);
+        Self {
+            inner: self.inner.clone(),
+            budget: self.budget.clone(),
+            observer: self.observer.clone(),
+        }
+    }
+}
+
+impl<T, O: ChannelObserver> Receiver<T, O> {
+    /// Receive the next value
+    pub async fn recv(&mut self) -> Option<T> {
+        let span = trace::span(&self.observer, Op::Recv);
+        let msg = span
+            .instrument(async {
+                debug!("waiting to receive value");
+                let msg = match self.inner.try_recv() {
+                    Ok(weighed) => Some(weighed),
+                    Err(TryRecvError::Disconnected) => None,
+                    Err(TryRecvError::Empty) => {
+                        let _idle = Idle::new(&self.observer);
+                        self.inner.recv().await
+                    }
+                };
+                if msg.is_some() {
+                    debug!("value received successfully");
+                } else {
+                    debug!("channel closed, no more values");
+                }
+                msg
+            })
+            .await;
+        msg.map(|weighed| self.received(weighed))
+    }
+
+    /// Receive the next value in a guard recording how long the consumer
+    /// spends processing it
+    pub async fn recv_guarded(&mut self) -> Option<MessageGuard<T, O>>
+    where
+        O: Clone,
+    {
+        let value = self.recv().await?;
+        Some(MessageGuard::new(value, self.observer().clone()))
+    }
+
+    /// Try to receive a value without waiting
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.inner.try_recv().map(|weighed| self.received(weighed))
+    }
+
+    fn received(&self, weighed: Weighed<T>) -> T {
+        self.budget
+            .queued_bytes
+            .fetch_sub(weighed.weight, Ordering::Relaxed);
+        self.budget.semaphore.add_permits(weighed.permits as usize);
+        self.observer.on_recv_bytes(weighed.weight);
+        weighed.envelope.open(&self.observer)
+    }
+
+    /// Close the channel, letting buffered values still be received
+    pub fn close(&mut self) {
+        self.budget.semaphore.close();
+        self.inner.close();
+        self.observer.on_close();
+    }
+
+    /// Close the channel and discard the values still queued, reporting them
+    /// as dropped
+    fn discard(&mut self) -> usize {
+        self.budget.semaphore.close();
+        self.inner.close();
+        let mut count = 0;
+        while let Ok(weighed) = self.inner.try_recv() {
+            count += 1;
+            self.budget
+                .queued_bytes
+                .fetch_sub(weighed.weight, Ordering::Relaxed);
+            self.budget.semaphore.add_permits(weighed.permits as usize);
+            self.observer.on_recv_bytes(weighed.weight);
+        }
+        if count > 0 {
+            self.observer.on_drop(count);
+        }
+        count
+    }
+
+    /// Get the limit bounding the channel
+    pub fn limit(&self) -> Limit {
+        self.budget.limit
+    }
+
+    /// Get the number of queued values, or their total weight with a byte budget
+    pub fn len(&self) -> usize {
+        self.budget.len()
+    }
+
+    /// Returns true if [`len`](Self::len) is 0, so with a byte budget
+    /// zero-weight values do not count
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the total weight of the queued values
+    pub fn queued_bytes(&self) -> usize {
+        self.budget.queued_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Get the observer receiving the channel's events
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+}
+
+#[cfg(feature = "prometheus")]
+impl<T> Receiver<T, ChannelMetrics> {
+    /// Get the total messages counter if enabled
+    pub fn total_messages(&self) -> Option<&prometheus::IntCounter> {
+        self.observer.total_messages.as_ref()
+    }
+}
+
+impl<T, O: ChannelObserver> Drop for Sender<T, O> {
+    fn drop(&mut self) {
+        self.observer.on_detach(HandleKind::Sender);
+    }
+}
+
+impl<T, O: ChannelObserver> Drop for Receiver<T, O> {
+    fn drop(&mut self) {
+        // Report the values still queued instead of letting them vanish
+        self.discard();
+        self.observer.on_detach(HandleKind::Receiver);
+    }
+}
+
+impl<T, O: ChannelObserver + fmt::Debug> fmt::Debug for Sender<T, O> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender")
+            .field("limit", &self.budget.limit)
+            .field("observer", &self.observer)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T, O: ChannelObserver + fmt::Debug> fmt::Debug for Receiver<T, O> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver")
+            .field("limit", &self.budget.limit)
+            .field("observer", &self.observer)
+            .finish_non_exhaustive()
+    }
+}